@@ -0,0 +1,640 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::{
+    Bias, Config, ConfigDiff, DEFAULT_CHORD_WINDOW, DEFAULT_HOLD_TIME, DEFAULT_REPEAT_DELAY,
+    DEFAULT_REPEAT_RATE, DEFAULT_TAP_WINDOW, DEFAULT_TAPPING_TERM, Identity, InputMode, KeyMode,
+    MacroStep, MouseConfig, Output,
+};
+use crate::gpio::{GpioBackend, LineConfig};
+use crate::input_listener::{
+    ChordConfig, ExecConfig, HoldConfig, InputListener, InputListenerConfig, InputsConfig,
+    KeyConfig, MultiTapConfig, PowerSwitch, RepeatConfig, TurboConfig, TurboSwitch, Waker,
+};
+use crate::keyboard::{OutputConfig, OutputDevice};
+use crate::{signals, watcher};
+
+/// The gpio2key application.
+///
+/// It owns the configuration and the input listener, and applies configuration reloads
+/// requested with `SIGHUP` or, if watching is enabled, when the configuration file changes.
+pub struct App<B>
+where
+    B: GpioBackend,
+{
+    backend: B,
+    config: Config,
+    config_path: PathBuf,
+    /// Name of the output device when no `[[device]]` is declared
+    keyboard_name: String,
+    /// Identity of the output devices set on the command line, unless configured per device
+    identity: Identity,
+    /// Whether to reload the configuration when the file changes
+    watch: bool,
+    /// Configuration of the current output devices
+    outputs: Vec<OutputConfig>,
+    /// Keycodes registered on each current output device
+    keycodes: Vec<HashSet<evdev::KeyCode>>,
+}
+
+impl<B> App<B>
+where
+    B: GpioBackend,
+{
+    /// Create a new [`App`] with the configuration loaded from `config_path`
+    pub fn new(
+        backend: B,
+        config: Config,
+        config_path: PathBuf,
+        keyboard_name: String,
+        identity: Identity,
+        watch: bool,
+    ) -> Self {
+        App {
+            backend,
+            config,
+            config_path,
+            keyboard_name,
+            identity,
+            watch,
+            outputs: Vec::new(),
+            keycodes: Vec::new(),
+        }
+    }
+
+    /// Run the application until exit is requested
+    pub fn run(mut self) -> anyhow::Result<()> {
+        log_config(&self.config);
+        self.backend.check_config(&self.config);
+
+        // setup signal handlers
+        let exit = Arc::new(AtomicBool::default());
+        let reload = Arc::new(AtomicBool::default());
+        let waker = Arc::new(Waker::try_new()?);
+        signals::install(exit.clone(), reload.clone(), waker.clone())?;
+        if self.watch {
+            watcher::watch(&self.config_path, reload.clone(), waker.clone())?;
+        }
+
+        // setup output devices
+        debug!("Initializing output devices...");
+        self.outputs = self.outputs(&self.config);
+        self.keycodes = keycodes(&self.config, self.outputs.len());
+        let keyboards = open_outputs(&self.outputs, &self.keycodes)?;
+        info!("Output devices initialized.");
+
+        // setup input listener
+        debug!("Initializing GPIOs...");
+        let config = InputListenerConfig {
+            exit: exit.clone(),
+            reload: reload.clone(),
+            waker,
+            keyboards,
+            inputs: self.inputs(&self.config),
+        };
+        let mut listener = InputListener::try_new(config, |line| self.backend.open(line))?;
+        info!("GPIOs initialized.");
+
+        loop {
+            listener.run();
+            if exit.load(Ordering::SeqCst) {
+                break;
+            }
+            if reload.swap(false, Ordering::SeqCst) {
+                self.reload(&mut listener);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reload the configuration file and apply it to the running `listener`.
+    ///
+    /// If the new configuration can't be loaded, the current one is kept.
+    /// The output devices are rebuilt only if they changed or new keycodes are configured.
+    fn reload(&mut self, listener: &mut InputListener<OutputDevice, B::Gpio>) {
+        info!("Reloading configuration from {:?}", self.config_path);
+        let config = match Config::load_from_file(&self.config_path) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("{e}; keeping current configuration");
+                return;
+            }
+        };
+        let diff = ConfigDiff::new(&self.config, &config);
+        if diff.is_empty() {
+            info!("Configuration unchanged");
+            return;
+        }
+        for change in diff.changes() {
+            info!("  {change}");
+        }
+        self.backend.check_config(&config);
+
+        // devices are replaced first, so the reloaded keys are routed to the new ones
+        let outputs = self.outputs(&config);
+        let keycodes = keycodes(&config, outputs.len());
+        let new_keycodes = keycodes
+            .iter()
+            .zip(&self.keycodes)
+            .any(|(new, old)| !new.is_subset(old));
+        if outputs != self.outputs || new_keycodes {
+            info!("Output devices changed, rebuilding them");
+            match open_outputs(&outputs, &keycodes) {
+                Ok(keyboards) => {
+                    listener.replace_keyboards(keyboards);
+                    self.outputs = outputs;
+                    self.keycodes = keycodes;
+                }
+                Err(e) => {
+                    error!("Failed to rebuild output devices: {e}; keeping current configuration");
+                    return;
+                }
+            }
+        }
+
+        if let Err(e) = listener.reload(self.inputs(&config), |line| self.backend.open(line)) {
+            error!("Configuration partially applied: {e}");
+        }
+
+        self.config = config;
+        info!("Configuration reloaded");
+    }
+
+    /// Get the [`OutputConfig`]s of the devices declared in `config`.
+    ///
+    /// If no device is declared, a single one named `keyboard_name` is used.
+    fn outputs(&self, config: &Config) -> Vec<OutputConfig> {
+        // the command line takes precedence over the configuration file
+        let identity = self.identity.clone().or(&config.identity());
+        if config.devices.is_empty() {
+            return vec![OutputConfig {
+                name: self.keyboard_name.clone(),
+                output: config.output,
+                identity,
+                mouse: Self::mouse(config, config.output),
+            }];
+        }
+
+        config
+            .devices
+            .iter()
+            .map(|device| {
+                let output = device.kind.unwrap_or(config.output);
+                OutputConfig {
+                    name: device.name.clone(),
+                    output,
+                    identity: device.identity().or(&identity),
+                    mouse: Self::mouse(config, output),
+                }
+            })
+            .collect()
+    }
+
+    /// Get the [`MouseConfig`] of an `output` device, only set for mice
+    fn mouse(config: &Config, output: Output) -> Option<MouseConfig> {
+        (output == Output::Mouse).then(|| config.mouse.clone())
+    }
+
+    /// Get the listener [`InputsConfig`] for `config`
+    fn inputs(&self, config: &Config) -> InputsConfig {
+        let mode = if config.input_mode == InputMode::Event && !self.backend.supports_edge_events()
+        {
+            warn!("Edge events are not supported by the GPIO backend; falling back to polling");
+            InputMode::Poll
+        } else {
+            config.input_mode
+        };
+        let edge_events = mode == InputMode::Event;
+
+        let keys = config
+            .keys
+            .iter()
+            .map(|k| KeyConfig {
+                line: LineConfig {
+                    gpio: k.gpio,
+                    active_low: k.active_low.unwrap_or(config.default_active_low),
+                    bias: k.bias,
+                    pull: config.pull(k.pull, k.active_low),
+                    edge_events,
+                },
+                keycode: k.keycode.clone(),
+                name: k.name(),
+                // keys referring to undeclared devices are rejected when loading the configuration
+                device: config.device_index(k.device.as_deref()).unwrap_or_default(),
+                debounce: k.debounce().unwrap_or_else(|| config.default_debounce()),
+                mode: k.mode,
+                repeat: if k.repeat {
+                    Some(RepeatConfig {
+                        delay: k.repeat_delay().unwrap_or(DEFAULT_REPEAT_DELAY),
+                        rate: k.repeat_rate().unwrap_or(DEFAULT_REPEAT_RATE),
+                    })
+                } else {
+                    None
+                },
+                hold: k.hold_keycode.clone().map(|keycode| HoldConfig {
+                    keycode,
+                    time: k.hold_time().unwrap_or(match k.mode {
+                        KeyMode::TapHold => DEFAULT_TAPPING_TERM,
+                        KeyMode::Momentary | KeyMode::Toggle => DEFAULT_HOLD_TIME,
+                    }),
+                    permissive: k.permissive_hold,
+                }),
+                turbo: k.turbo_pulse().map(|(down, up)| TurboConfig {
+                    down,
+                    up,
+                    switch: k.turbo_gpio,
+                }),
+                multi_tap: k.tap_keycodes().map(|keycodes| MultiTapConfig {
+                    keycodes,
+                    window: k.tap_window().unwrap_or(DEFAULT_TAP_WINDOW),
+                }),
+                layer: k.layer.clone(),
+                layer_keycodes: k
+                    .layers
+                    .iter()
+                    .map(|(name, keycode)| (name.clone(), keycode.clone()))
+                    .collect(),
+                // keys referring to undeclared macros are rejected when loading the configuration
+                key_macro: k
+                    .macro_name
+                    .as_deref()
+                    .and_then(|name| config.macro_config(name))
+                    .cloned()
+                    .or_else(|| k.text_macro(config.keyboard_layout)),
+                exec: k.exec.as_ref().map(|exec| ExecConfig {
+                    on_press: exec.on_press.clone(),
+                    on_release: exec.on_release.clone(),
+                    on_hold: exec.on_hold.clone(),
+                    hold: k.hold_time().unwrap_or(DEFAULT_HOLD_TIME),
+                    concurrency: exec.concurrency,
+                    timeout: exec.timeout(),
+                }),
+            })
+            .collect();
+        let chords = config
+            .chords
+            .iter()
+            .map(|c| ChordConfig {
+                gpios: c.gpios.clone(),
+                keycode: c.keycode.clone(),
+                device: config.device_index(c.device.as_deref()).unwrap_or_default(),
+                window: c.window().unwrap_or(DEFAULT_CHORD_WINDOW),
+            })
+            .collect();
+        let mut turbo_switches: Vec<TurboSwitch> = Vec::new();
+        for gpio in config.keys.iter().filter_map(|k| k.turbo_gpio) {
+            if turbo_switches.iter().any(|ts| ts.line.gpio == gpio) {
+                continue;
+            }
+            turbo_switches.push(TurboSwitch {
+                line: LineConfig {
+                    gpio,
+                    active_low: config.default_active_low,
+                    bias: Bias::AsIs,
+                    pull: config.pull(None, None),
+                    edge_events,
+                },
+                debounce: config.default_debounce(),
+            });
+        }
+        let power_switches = config
+            .power_switches
+            .iter()
+            .map(|ps| PowerSwitch {
+                line: LineConfig {
+                    gpio: ps.gpio,
+                    active_low: ps.active_low.unwrap_or(config.default_active_low),
+                    bias: ps.bias,
+                    pull: config.pull(ps.pull, ps.active_low),
+                    edge_events,
+                },
+            })
+            .collect();
+
+        InputsConfig {
+            keys,
+            chords,
+            turbo_switches,
+            power_switches,
+            mode,
+            poll_interval: config.poll_interval(),
+        }
+    }
+}
+
+/// Get the set of keycodes emitted on each of the `devices` output devices with `config`
+fn keycodes(config: &Config, devices: usize) -> Vec<HashSet<evdev::KeyCode>> {
+    let mut keycodes = vec![HashSet::new(); devices];
+    for key in &config.keys {
+        if let Some(device) = config.device_index(key.device.as_deref()) {
+            // the keycode of keys with an action is never emitted
+            let combos = [
+                key.keycode.as_ref().filter(|_| !key.has_action()),
+                key.hold_keycode.as_ref(),
+                key.double_tap_keycode.as_ref(),
+                key.triple_tap_keycode.as_ref(),
+            ];
+            let macro_steps = key
+                .macro_name
+                .as_deref()
+                .and_then(|name| config.macro_config(name))
+                .cloned()
+                .or_else(|| key.text_macro(config.keyboard_layout))
+                .map(|m| m.steps)
+                .unwrap_or_default();
+            keycodes[device].extend(
+                combos
+                    .into_iter()
+                    .chain(key.layers.values().map(Some))
+                    .chain(macro_steps.iter().map(|step| match step {
+                        MacroStep::Keys(keycode) => Some(keycode),
+                        MacroStep::Delay { .. } => None,
+                    }))
+                    .flatten()
+                    .flat_map(|combo| combo.keycodes())
+                    .map(|k| k.keycode()),
+            );
+        }
+    }
+
+    for chord in &config.chords {
+        if let Some(device) = config.device_index(chord.device.as_deref()) {
+            keycodes[device].extend(chord.keycode.keycodes().iter().map(|k| k.keycode()));
+        }
+    }
+
+    keycodes
+}
+
+/// Create the output devices described by `outputs`, each emitting its `keycodes`
+fn open_outputs(
+    outputs: &[OutputConfig],
+    keycodes: &[HashSet<evdev::KeyCode>],
+) -> anyhow::Result<Vec<OutputDevice>> {
+    outputs
+        .iter()
+        .zip(keycodes)
+        .map(|(output, keycodes)| {
+            debug!("Initializing {:?} device {:?}", output.output, output.name);
+            OutputDevice::try_new(output, &keycodes.iter().copied().collect::<Vec<_>>())
+        })
+        .collect()
+}
+
+fn log_config(config: &Config) {
+    info!("Configuration:");
+    info!(
+        "  Default debounce: {}",
+        config.default_debounce().as_millis()
+    );
+    info!("  Default active_low: {}", config.default_active_low);
+    info!("  Poll interval: {}", config.poll_interval().as_millis());
+    info!("  Input mode: {:?}", config.input_mode);
+    info!("  Default pull: {:?}", config.default_pull);
+    info!("  Output: {:?}", config.output);
+    info!("  Identity: {:?}", config.identity());
+    info!("  Keyboard layout: {:?}", config.keyboard_layout);
+    info!("  Mouse:");
+    info!("    Speed (px/s): {}", config.mouse.speed);
+    info!("    Max Speed (px/s): {}", config.mouse.max_speed);
+    info!(
+        "    Acceleration (ms): {}",
+        config.mouse.acceleration().as_millis()
+    );
+    info!(
+        "    Acceleration Curve: {}",
+        config.mouse.acceleration_curve
+    );
+    info!("    Wheel Speed (notches/s): {}", config.mouse.wheel_speed);
+    info!("  Devices:");
+    for device in &config.devices {
+        info!("    Name: {}", device.name);
+        info!("    Type: {:?}", device.kind.unwrap_or(config.output));
+        info!("    Identity: {:?}", device.identity());
+    }
+    info!("  Keys:");
+    for key in &config.keys {
+        info!("    GPIO: {}", key.gpio);
+        if let Some(keycode) = &key.keycode {
+            info!("    Keycode: {}", keycode);
+        }
+        if let Some(device) = &key.device {
+            info!("    Device: {}", device);
+        }
+        if let Some(debounce) = key.debounce() {
+            info!("    Debounce (ms): {}", debounce.as_millis());
+        }
+        info!("    Active Low: {:?}", key.active_low);
+        info!("    Bias: {:?}", key.bias);
+        info!("    Pull: {:?}", key.pull);
+        info!("    Mode: {:?}", key.mode);
+        info!("    Repeat: {}", key.repeat);
+        if let Some(delay) = key.repeat_delay() {
+            info!("    Repeat Delay (ms): {}", delay.as_millis());
+        }
+        if let Some(rate) = key.repeat_rate() {
+            info!("    Repeat Rate (ms): {}", rate.as_millis());
+        }
+        if let Some(hold_keycode) = &key.hold_keycode {
+            info!("    Hold Keycode: {}", hold_keycode);
+        }
+        if let Some(hold) = key.hold_time() {
+            info!("    Hold Time (ms): {}", hold.as_millis());
+        }
+        if key.permissive_hold {
+            info!("    Permissive Hold: true");
+        }
+        if let Some(double_tap_keycode) = &key.double_tap_keycode {
+            info!("    Double Tap Keycode: {}", double_tap_keycode);
+        }
+        if let Some(triple_tap_keycode) = &key.triple_tap_keycode {
+            info!("    Triple Tap Keycode: {}", triple_tap_keycode);
+        }
+        if let Some(window) = key.tap_window() {
+            info!("    Tap Window (ms): {}", window.as_millis());
+        }
+        if let Some(turbo_hz) = key.turbo_hz {
+            info!("    Turbo (Hz): {}", turbo_hz);
+        }
+        if let Some(turbo_duty) = key.turbo_duty {
+            info!("    Turbo Duty (%): {}", turbo_duty);
+        }
+        if let Some(turbo_gpio) = key.turbo_gpio {
+            info!("    Turbo Switch GPIO: {}", turbo_gpio);
+        }
+        if let Some(layer) = &key.layer {
+            info!("    Layer: {}", layer);
+        }
+        for (layer, keycode) in &key.layers {
+            info!("    Layer {} Keycode: {}", layer, keycode);
+        }
+        if let Some(macro_name) = &key.macro_name {
+            info!("    Macro: {}", macro_name);
+        }
+        if let Some(text) = &key.text {
+            info!("    Text: {:?}", text);
+        }
+        if let Some(exec) = &key.exec {
+            if let Some(command) = &exec.on_press {
+                info!("    Exec On Press: {}", command);
+            }
+            if let Some(command) = &exec.on_release {
+                info!("    Exec On Release: {}", command);
+            }
+            if let Some(command) = &exec.on_hold {
+                info!("    Exec On Hold: {}", command);
+            }
+            info!("    Exec Concurrency: {:?}", exec.concurrency);
+            if let Some(timeout) = exec.timeout() {
+                info!("    Exec Timeout (ms): {}", timeout.as_millis());
+            }
+        }
+    }
+    info!("  Chords:");
+    for chord in &config.chords {
+        info!("    GPIOs {:?}", chord.gpios);
+        info!("    Keycode: {}", chord.keycode);
+        if let Some(device) = &chord.device {
+            info!("    Device: {}", device);
+        }
+        if let Some(window) = chord.window() {
+            info!("    Window (ms): {}", window.as_millis());
+        }
+    }
+    info!("  Macros:");
+    for macro_config in &config.macros {
+        info!("    Name: {}", macro_config.name);
+        let steps = macro_config
+            .steps
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        info!("    Steps: {}", steps.join(", "));
+    }
+    info!("  Power Switches:");
+    for ps in &config.power_switches {
+        info!("    GPIO {}", ps.gpio);
+        info!(
+            "    Active Low: {}",
+            ps.active_low.unwrap_or(config.default_active_low)
+        );
+        info!("    Bias: {:?}", ps.bias);
+        info!("    Pull: {:?}", ps.pull);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use super::*;
+    use crate::gpio::MockBackend;
+
+    #[test]
+    fn test_should_apply_default_key_settings() {
+        let config: Config =
+            toml::from_str("[[key]]\ngpio = 17\nkeycode = \"A\"\nrepeat = true").unwrap();
+        let inputs = app(&config).inputs(&config);
+
+        let key = &inputs.keys[0];
+        assert_eq!(
+            key.repeat,
+            Some(RepeatConfig {
+                delay: Duration::from_millis(500),
+                rate: Duration::from_millis(30),
+            })
+        );
+        assert_eq!(key.debounce, Duration::from_millis(20));
+        assert!(key.line.active_low);
+        assert_eq!(key.line.bias, Bias::AsIs);
+        assert_eq!(inputs.mode, InputMode::Poll);
+        assert_eq!(inputs.poll_interval, Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_should_apply_configured_repeat_settings() {
+        let config: Config = toml::from_str(
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nrepeat = true\nrepeat_delay_ms = 300\nrepeat_rate_ms = 80\n\n[[key]]\ngpio = 18\nkeycode = \"B\"",
+        )
+        .unwrap();
+        let inputs = app(&config).inputs(&config);
+
+        assert_eq!(
+            inputs.keys[0].repeat,
+            Some(RepeatConfig {
+                delay: Duration::from_millis(300),
+                rate: Duration::from_millis(80),
+            })
+        );
+        assert_eq!(inputs.keys[1].repeat, None);
+    }
+
+    #[test]
+    fn test_should_set_mouse_config_on_mice_only() {
+        let config: Config = toml::from_str(
+            "[[device]]\nname = \"keyboard\"\n\n[[device]]\nname = \"mouse\"\ntype = \"mouse\"\n\n[mouse]\nspeed = 300",
+        )
+        .unwrap();
+        let outputs = app(&config).outputs(&config);
+
+        assert_eq!(outputs[0].mouse, None);
+        assert_eq!(outputs[1].mouse, Some(config.mouse.clone()));
+    }
+
+    #[test]
+    fn test_should_not_enable_keycodes_of_action_keys() {
+        let config: Config = toml::from_str(
+            "[[macro]]\nname = \"menu\"\nsteps = [\"ESC\"]\n\n[[key]]\ngpio = 17\nkeycode = \"F1\"\nmacro = \"menu\"\n\n[[key]]\ngpio = 18\ntext = \"a\"",
+        )
+        .unwrap();
+        let keycodes = keycodes(&config, 1);
+
+        assert_eq!(
+            keycodes[0],
+            HashSet::from([evdev::KeyCode::KEY_ESC, evdev::KeyCode::KEY_A])
+        );
+        let inputs = app(&config).inputs(&config);
+        assert_eq!(inputs.keys[1].keycode, None);
+        assert_eq!(inputs.keys[1].name, "text \"a\"");
+    }
+
+    #[test]
+    fn test_should_merge_identities() {
+        let config: Config = toml::from_str(
+            "vendor = 0x1209\nproduct = 0x0003\nversion = 0x0002\n\n[[device]]\nname = \"player1\"\nproduct = 0x0004",
+        )
+        .unwrap();
+        let mut app = app(&config);
+        app.identity = Identity {
+            version: Some(0x0005),
+            ..Default::default()
+        };
+        let outputs = app.outputs(&config);
+
+        assert_eq!(
+            outputs[0].identity,
+            Identity {
+                bustype: None,
+                vendor: Some(0x1209),
+                product: Some(0x0004),
+                version: Some(0x0005),
+                phys: None,
+            }
+        );
+    }
+
+    fn app(config: &Config) -> App<MockBackend> {
+        App::new(
+            MockBackend,
+            config.clone(),
+            PathBuf::from("config.toml"),
+            "gpio2key".to_string(),
+            Identity::default(),
+            false,
+        )
+    }
+}
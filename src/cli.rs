@@ -3,6 +3,7 @@ mod log_level;
 use std::path::PathBuf;
 
 pub use self::log_level::LogLevel;
+use crate::config::{BusType, Identity};
 
 /// gpio2key command line arguments
 #[derive(Debug, argh::FromArgs)]
@@ -13,13 +14,130 @@ pub struct Args {
     /// chip device (default: /dev/gpiochip0)
     #[argh(option, short = 'd', default = "PathBuf::from(\"/dev/gpiochip0\")")]
     pub device: PathBuf,
-    /// keyboard name (default: gpio2key)
+    /// name of the virtual keyboard or gamepad (default: gpio2key)
     #[argh(option, short = 'k', default = "String::from(\"gpio2key\")")]
     pub keyboard_name: String,
+    /// bus type reported by the virtual devices, e.g. usb, bluetooth or virtual (default: usb)
+    #[argh(option)]
+    pub bustype: Option<BusType>,
+    /// vendor id reported by the virtual devices, in decimal or 0x hexadecimal
+    #[argh(option, from_str_fn(parse_id))]
+    pub vendor: Option<u16>,
+    /// product id reported by the virtual devices, in decimal or 0x hexadecimal
+    #[argh(option, from_str_fn(parse_id))]
+    pub product: Option<u16>,
+    /// version reported by the virtual devices, in decimal or 0x hexadecimal
+    #[argh(option, from_str_fn(parse_id))]
+    pub device_version: Option<u16>,
+    /// physical location reported by the virtual devices, e.g. gpio2key/input0
+    #[argh(option)]
+    pub phys: Option<String>,
     /// log level (error, warn, info, debug, trace)
     #[argh(option, short = 'l', default = "LogLevel::Info")]
     pub log_level: LogLevel,
-    /// enable this flag on Raspberry Pi boards to enable pull-up gpio configuration
+    /// enable this flag on Raspberry Pi boards to enable pull resistor gpio configuration
     #[argh(switch, short = 'r')]
     pub raspberry: bool,
+    /// reload the configuration when the configuration file changes
+    #[argh(switch, short = 'w')]
+    pub watch: bool,
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// Identity of the virtual devices set on the command line
+    pub fn identity(&self) -> Identity {
+        Identity {
+            bustype: self.bustype,
+            vendor: self.vendor,
+            product: self.product,
+            version: self.device_version,
+            phys: self.phys.clone(),
+        }
+    }
+}
+
+/// Parse a device id, either decimal or hexadecimal with the `0x` prefix
+fn parse_id(value: &str) -> Result<u16, String> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|e| format!("invalid id {value:?}: {e}"))
+}
+
+/// gpio2key subcommands
+#[derive(Debug, argh::FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Check(CheckArgs),
+    ListKeys(ListKeysArgs),
+}
+
+/// check the configuration file and report every problem, without accessing any device
+#[derive(Debug, argh::FromArgs)]
+#[argh(subcommand, name = "check")]
+pub struct CheckArgs {
+    /// path to configuration file (default: the `--config` option)
+    #[argh(positional)]
+    pub config: Option<PathBuf>,
+}
+
+/// list every key name accepted by `keycode`, with the key it resolves to and its category
+#[derive(Debug, argh::FromArgs)]
+#[argh(subcommand, name = "list-keys")]
+pub struct ListKeysArgs {
+    /// only list the keys whose name, resolved name or category contains this text
+    #[argh(positional)]
+    pub filter: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use argh::FromArgs;
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_id() {
+        assert_eq!(parse_id("0x1209"), Ok(0x1209));
+        assert_eq!(parse_id("0X00ff"), Ok(0xff));
+        assert_eq!(parse_id("4617"), Ok(4617));
+        assert!(parse_id("0x10000").is_err());
+        assert!(parse_id("usb").is_err());
+    }
+
+    #[test]
+    fn test_should_parse_identity() {
+        let args = Args::from_args(
+            &["gpio2key"],
+            &[
+                "--bustype",
+                "bluetooth",
+                "--vendor",
+                "0x1209",
+                "--device-version",
+                "2",
+                "--phys",
+                "gpio2key/input0",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            args.identity(),
+            Identity {
+                bustype: "bluetooth".parse().ok(),
+                vendor: Some(0x1209),
+                product: None,
+                version: Some(2),
+                phys: Some("gpio2key/input0".to_string()),
+            }
+        );
+    }
 }
@@ -1,24 +1,102 @@
+mod check;
+mod combo;
+mod diff;
+mod identity;
 mod keycode;
+mod layout;
+mod rules;
 
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::Path;
 use std::time::Duration;
 
 use serde::Deserialize;
 
-pub use self::keycode::Keycode;
+pub use self::check::check;
+pub use self::combo::KeyCombo;
+pub use self::diff::ConfigDiff;
+pub use self::identity::{BusType, Identity};
+pub use self::keycode::{Keycode, names as key_names};
+pub use self::layout::KeyboardLayout;
+
+/// Default delay before auto-repeat starts
+pub const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(500);
+/// Default interval between auto-repeats
+pub const DEFAULT_REPEAT_RATE: Duration = Duration::from_millis(30);
+/// Default time a key must be held to emit its `hold_keycode`
+pub const DEFAULT_HOLD_TIME: Duration = Duration::from_millis(500);
+/// Default time a tap-hold key must be held, unless another key resolves it, to emit its `hold_keycode`
+pub const DEFAULT_TAPPING_TERM: Duration = Duration::from_millis(200);
+/// Default time after a release within which the next tap of a multi-tap must start
+pub const DEFAULT_TAP_WINDOW: Duration = Duration::from_millis(250);
+/// Default time within which all the keys of a chord must be pressed
+pub const DEFAULT_CHORD_WINDOW: Duration = Duration::from_millis(50);
+/// Default percentage of each turbo pulse the key is held down
+pub const DEFAULT_TURBO_DUTY: u8 = 50;
+/// Maximum turbo frequency
+pub const MAX_TURBO_HZ: f64 = 1000.0;
+/// Maximum length in bytes of a virtual device name
+pub const MAX_DEVICE_NAME_LEN: usize = 78;
+
+const DEFAULT_DEBOUNCE_MS: u64 = 20;
+const DEFAULT_ACTIVE_LOW: bool = true;
+const DEFAULT_POLL_INTERVAL_MS: u64 = 5;
+const DEFAULT_MOUSE_SPEED: u32 = 200;
+const DEFAULT_MOUSE_MAX_SPEED: u32 = 1500;
+const DEFAULT_MOUSE_ACCELERATION_MS: u64 = 1000;
+const DEFAULT_MOUSE_ACCELERATION_CURVE: f64 = 2.0;
+const DEFAULT_MOUSE_WHEEL_SPEED: u32 = 10;
 
 /// Application configuration structure
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     /// default debounce time in milliseconds
+    #[serde(default = "default_debounce_ms")]
     default_debounce_ms: u64,
     /// default active_low setting for keys; if true, key is active when GPIO is low
+    #[serde(default = "default_active_low")]
     pub default_active_low: bool,
     /// polling interval in milliseconds
+    #[serde(default = "default_poll_interval_ms")]
     poll_interval_ms: u64,
+    /// how GPIO changes are detected
+    #[serde(default)]
+    pub input_mode: InputMode,
+    /// default pull resistor on Raspberry Pi; if unset, it's derived from `active_low`
+    pub default_pull: Option<Pull>,
+    /// kind of virtual device emitting the events
+    #[serde(default)]
+    pub output: Output,
+    /// bus type of the virtual devices, unless set per device
+    pub bustype: Option<BusType>,
+    /// USB vendor id of the virtual devices, unless set per device
+    pub vendor: Option<u16>,
+    /// USB product id of the virtual devices, unless set per device
+    pub product: Option<u16>,
+    /// version of the virtual devices, unless set per device
+    pub version: Option<u16>,
+    /// physical location of the virtual devices, unless set per device
+    pub phys: Option<String>,
+    /// keyboard layout of the host, used to type the `text` of the keys
+    #[serde(default)]
+    pub keyboard_layout: KeyboardLayout,
+    /// pointer and wheel motion of the mouse devices
+    #[serde(default)]
+    pub mouse: MouseConfig,
+    /// Virtual devices configuration
+    #[serde(rename = "device", default)]
+    pub devices: Vec<DeviceConfig>,
     /// Keys configuration
     #[serde(rename = "key", default)]
     pub keys: Vec<KeyConfig>,
+    /// Chords configuration
+    #[serde(rename = "chord", default)]
+    pub chords: Vec<ChordConfig>,
+    /// Macros configuration
+    #[serde(rename = "macro", default)]
+    pub macros: Vec<MacroConfig>,
     /// Power switches configuration
     #[serde(rename = "powerswitch", default)]
     pub power_switches: Vec<PowerSwitchConfig>,
@@ -31,9 +109,133 @@ impl Config {
             .map_err(|e| anyhow::anyhow!("Failed to read config file {:?}: {}", path, e))?;
         let config: Config = toml::from_str(&config_str)
             .map_err(|e| anyhow::anyhow!("Failed to parse config file {:?}: {}", path, e))?;
+        config
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Invalid config file {:?}: {}", path, e))?;
         Ok(config)
     }
 
+    /// Identity of the virtual devices, unless configured per device
+    pub fn identity(&self) -> Identity {
+        Identity {
+            bustype: self.bustype,
+            vendor: self.vendor,
+            product: self.product,
+            version: self.version,
+            phys: self.phys.clone(),
+        }
+    }
+
+    /// Check the rules shared with `check`, then the references between the configuration tables
+    fn validate(&self) -> anyhow::Result<()> {
+        let violation = rules::global_violations(self)
+            .into_iter()
+            .chain(self.devices.iter().flat_map(rules::device_violations))
+            .chain(
+                self.keys
+                    .iter()
+                    .flat_map(|key| rules::key_violations(self, key)),
+            )
+            .chain(self.chords.iter().flat_map(rules::chord_violations))
+            .chain(self.macros.iter().flat_map(rules::macro_violations))
+            .next();
+        if let Some((_, message)) = violation {
+            anyhow::bail!(message);
+        }
+
+        for (i, device) in self.devices.iter().enumerate() {
+            if self.devices[..i].iter().any(|d| d.name == device.name) {
+                anyhow::bail!("device {:?} is declared more than once", device.name);
+            }
+        }
+        let reuses = rules::gpio_reuses(
+            &self.keys.iter().collect::<Vec<_>>(),
+            &self.power_switches.iter().collect::<Vec<_>>(),
+        );
+        if let Some(reuse) = reuses.into_iter().next() {
+            anyhow::bail!(reuse.violation.1);
+        }
+        for key in &self.keys {
+            if let Some(layer) = &key.layer {
+                if self
+                    .keys
+                    .iter()
+                    .filter(|k| k.layer.as_ref() == Some(layer))
+                    .count()
+                    > 1
+                {
+                    anyhow::bail!("layer {:?} is declared by more than one key", layer);
+                }
+            }
+            for layer in key.layers.keys() {
+                if !self.keys.iter().any(|k| k.layer.as_ref() == Some(layer)) {
+                    anyhow::bail!("key GPIO {} uses undeclared layer {:?}", key.gpio, layer);
+                }
+            }
+            if let Some(name) = &key.macro_name {
+                if self.macro_config(name).is_none() {
+                    anyhow::bail!("key GPIO {} uses undeclared macro {:?}", key.gpio, name);
+                }
+            }
+            if self.device_index(key.device.as_deref()).is_none() {
+                anyhow::bail!(
+                    "key GPIO {} uses undeclared device {:?}",
+                    key.gpio,
+                    key.device.as_deref().unwrap_or_default()
+                );
+            }
+        }
+
+        for (i, chord) in self.chords.iter().enumerate() {
+            for gpio in &chord.gpios {
+                let Some(key) = self.keys.iter().find(|k| k.gpio == *gpio) else {
+                    anyhow::bail!(
+                        "chord {} uses GPIO {} which is not a key",
+                        chord.keycode,
+                        gpio
+                    );
+                };
+                if let Some((_, message)) = rules::chord_key_violation(chord, key) {
+                    anyhow::bail!(message);
+                }
+                if self.chords[..i].iter().any(|c| c.gpios.contains(gpio)) {
+                    anyhow::bail!("GPIO {} is used by more than one chord", gpio);
+                }
+            }
+            if self.device_index(chord.device.as_deref()).is_none() {
+                anyhow::bail!(
+                    "chord {} uses undeclared device {:?}",
+                    chord.keycode,
+                    chord.device.as_deref().unwrap_or_default()
+                );
+            }
+        }
+
+        for (i, macro_config) in self.macros.iter().enumerate() {
+            if self.macros[..i].iter().any(|m| m.name == macro_config.name) {
+                anyhow::bail!("macro {:?} is declared more than once", macro_config.name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The macro called `name`, if declared
+    pub fn macro_config(&self, name: &str) -> Option<&MacroConfig> {
+        self.macros.iter().find(|m| m.name == name)
+    }
+
+    /// Index in [`Config::devices`] of the `device` a key or chord emits on.
+    ///
+    /// Keys without `device` emit on the first device;
+    /// if no device is declared, all the keys emit on the default one, with index 0.
+    pub fn device_index(&self, device: Option<&str>) -> Option<usize> {
+        match device {
+            None => Some(0),
+            Some(name) => self.devices.iter().position(|d| d.name == name),
+        }
+    }
+
     /// Default debounce time
     pub fn default_debounce(&self) -> Duration {
         Duration::from_millis(self.default_debounce_ms)
@@ -43,22 +245,233 @@ impl Config {
     pub fn poll_interval(&self) -> Duration {
         Duration::from_millis(self.poll_interval_ms)
     }
+
+    /// Resolve the pull resistor for a GPIO, given its own `pull` and `active_low` settings
+    pub fn pull(&self, pull: Option<Pull>, active_low: Option<bool>) -> Pull {
+        pull.or(self.default_pull)
+            .unwrap_or_else(|| Pull::for_active_low(active_low.unwrap_or(self.default_active_low)))
+    }
+}
+
+fn default_debounce_ms() -> u64 {
+    DEFAULT_DEBOUNCE_MS
+}
+
+fn default_active_low() -> bool {
+    DEFAULT_ACTIVE_LOW
+}
+
+fn default_poll_interval_ms() -> u64 {
+    DEFAULT_POLL_INTERVAL_MS
+}
+
+/// Describes how the input listener detects GPIO changes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputMode {
+    /// Read every GPIO each `poll_interval_ms`
+    #[default]
+    Poll,
+    /// Wait for edge events on the GPIO lines
+    Event,
+}
+
+/// Describes how a key emits its keycode
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyMode {
+    /// The keycode is held down while the key is pressed
+    #[default]
+    Momentary,
+    /// Each press flips the keycode between held down and released
+    Toggle,
+    /// Dual-role key: the keycode is tapped if the key is tapped alone,
+    /// the hold keycode is held down if the key is held or used together with other keys
+    TapHold,
+}
+
+/// Kind of virtual device emitting the events
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    /// A keyboard emitting the configured keys
+    #[default]
+    Keyboard,
+    /// A gamepad with buttons and a d-pad hat
+    Gamepad,
+    /// A mouse with buttons, a pointer moved by the direction keys and a wheel
+    Mouse,
+}
+
+/// Configuration for the pointer and wheel motion of the mouse devices
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct MouseConfig {
+    /// Pointer speed in pixels per second when a direction key is pressed
+    pub speed: u32,
+    /// Pointer speed in pixels per second reached after `acceleration_ms`
+    pub max_speed: u32,
+    acceleration_ms: u64,
+    /// Exponent of the acceleration curve: 1 accelerates linearly, higher values start slower
+    pub acceleration_curve: f64,
+    /// Wheel notches per second while a wheel key is held, after the notch of the press
+    pub wheel_speed: u32,
+}
+
+impl Default for MouseConfig {
+    fn default() -> Self {
+        MouseConfig {
+            speed: DEFAULT_MOUSE_SPEED,
+            max_speed: DEFAULT_MOUSE_MAX_SPEED,
+            acceleration_ms: DEFAULT_MOUSE_ACCELERATION_MS,
+            acceleration_curve: DEFAULT_MOUSE_ACCELERATION_CURVE,
+            wheel_speed: DEFAULT_MOUSE_WHEEL_SPEED,
+        }
+    }
+}
+
+impl MouseConfig {
+    /// Time the pointer takes to accelerate from `speed` to `max_speed`
+    pub fn acceleration(&self) -> Duration {
+        Duration::from_millis(self.acceleration_ms)
+    }
+
+    /// Pointer speed in pixels per second after moving for `elapsed`
+    pub fn pointer_speed(&self, elapsed: Duration) -> f64 {
+        let progress = if self.acceleration_ms == 0 {
+            1.0
+        } else {
+            (elapsed.as_secs_f64() / self.acceleration().as_secs_f64()).min(1.0)
+        };
+        let speed = f64::from(self.speed);
+
+        speed + (f64::from(self.max_speed) - speed) * progress.powf(self.acceleration_curve)
+    }
+}
+
+/// Bias applied to a GPIO line on generic Linux systems
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Bias {
+    /// Enable the internal pull-up resistor
+    PullUp,
+    /// Enable the internal pull-down resistor
+    PullDown,
+    /// Disable any internal bias
+    Disabled,
+    /// Leave the bias as currently configured
+    #[default]
+    AsIs,
+}
+
+/// Pull resistor configuration on Raspberry Pi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pull {
+    /// Enable the internal pull-up resistor
+    Up,
+    /// Enable the internal pull-down resistor
+    Down,
+    /// Leave the input floating
+    None,
+}
+
+impl Pull {
+    /// Default pull for a GPIO: active low inputs are pulled up, active high inputs are pulled down
+    pub fn for_active_low(active_low: bool) -> Self {
+        if active_low { Self::Up } else { Self::Down }
+    }
+}
+
+/// Configuration for a virtual device
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    /// Device name, also used by keys to refer to it
+    pub name: String,
+    /// Kind of device; `output` by default
+    #[serde(rename = "type")]
+    pub kind: Option<Output>,
+    /// Bus type
+    pub bustype: Option<BusType>,
+    /// USB vendor id
+    pub vendor: Option<u16>,
+    /// USB product id
+    pub product: Option<u16>,
+    /// Version
+    pub version: Option<u16>,
+    /// Physical location
+    pub phys: Option<String>,
+}
+
+impl DeviceConfig {
+    /// Identity configured for the device
+    pub fn identity(&self) -> Identity {
+        Identity {
+            bustype: self.bustype,
+            vendor: self.vendor,
+            product: self.product,
+            version: self.version,
+            phys: self.phys.clone(),
+        }
+    }
 }
 
 /// Configuration for an individual key
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct KeyConfig {
     /// GPIO pin number
     pub gpio: u8,
-    /// [`Keycode`] to emit
-    pub keycode: Keycode,
+    /// Key, or [`KeyCombo`], to emit; only required without `macro`, `text` or `exec`
+    pub keycode: Option<KeyCombo>,
+    /// Name of the device to emit the key on
+    pub device: Option<String>,
     debounce_ms: Option<u64>,
     /// Whether the key is active low; if true, key is active when GPIO is low
     pub active_low: Option<bool>,
+    /// Line bias
+    #[serde(default)]
+    pub bias: Bias,
+    /// Pull resistor on Raspberry Pi
+    pub pull: Option<Pull>,
+    /// How the key emits `keycode`
+    #[serde(default)]
+    pub mode: KeyMode,
     /// Whether auto-repeat is enabled
+    #[serde(default)]
     pub repeat: bool,
     repeat_delay_ms: Option<u64>,
     repeat_rate_ms: Option<u64>,
+    /// Key, or [`KeyCombo`], to emit instead of `keycode` when the key is held for `hold_ms`
+    pub hold_keycode: Option<KeyCombo>,
+    hold_ms: Option<u64>,
+    /// Whether a tap-hold key is held as soon as another key is pressed and released while it's held
+    #[serde(default)]
+    pub permissive_hold: bool,
+    /// Key, or [`KeyCombo`], to emit when the key is tapped twice
+    pub double_tap_keycode: Option<KeyCombo>,
+    /// Key, or [`KeyCombo`], to emit when the key is tapped three times
+    pub triple_tap_keycode: Option<KeyCombo>,
+    tap_window_ms: Option<u64>,
+    /// Frequency of the presses emitted while the key is held, enabling turbo
+    pub turbo_hz: Option<f64>,
+    /// Percentage of each turbo pulse the key is held down
+    pub turbo_duty: Option<u8>,
+    /// GPIO pin number of a button turning turbo on and off
+    pub turbo_gpio: Option<u8>,
+    /// Name of the layer activated while the key is held; the key then only emits `keycode` if tapped alone
+    pub layer: Option<String>,
+    /// Keys, or [`KeyCombo`]s, to emit instead of `keycode` while a layer is active, by layer name
+    #[serde(default)]
+    pub layers: BTreeMap<String, KeyCombo>,
+    /// Name of the macro played back when the key is pressed, instead of emitting `keycode`
+    #[serde(rename = "macro")]
+    pub macro_name: Option<String>,
+    /// Text typed when the key is pressed, instead of emitting `keycode`
+    pub text: Option<String>,
+    /// Commands run by the key, instead of emitting `keycode`
+    pub exec: Option<ExecConfig>,
 }
 
 impl KeyConfig {
@@ -76,15 +489,165 @@ impl KeyConfig {
     pub fn repeat_rate(&self) -> Option<Duration> {
         self.repeat_rate_ms.map(Duration::from_millis)
     }
+
+    /// Time the key must be held to emit `hold_keycode`, or to run its `on_hold` command
+    pub fn hold_time(&self) -> Option<Duration> {
+        self.hold_ms.map(Duration::from_millis)
+    }
+
+    /// Whether the key plays a macro, types text or runs commands, instead of emitting `keycode`
+    pub fn has_action(&self) -> bool {
+        self.macro_name.is_some() || self.text.is_some() || self.exec.is_some()
+    }
+
+    /// Macro typing `text` with `layout`, if the key has a text which can be typed
+    pub fn text_macro(&self, layout: KeyboardLayout) -> Option<MacroConfig> {
+        let text = self.text.as_ref()?;
+        let steps = layout.type_text(text).ok()?;
+
+        Some(MacroConfig {
+            name: format!("text {text:?}"),
+            steps: steps.into_iter().map(MacroStep::Keys).collect(),
+        })
+    }
+
+    /// What the key emits, or the action it runs if it has no keycode
+    pub fn name(&self) -> String {
+        match (&self.keycode, &self.macro_name, &self.text) {
+            (Some(keycode), _, _) => keycode.to_string(),
+            (None, Some(name), _) => format!("macro {name:?}"),
+            (None, None, Some(text)) => format!("text {text:?}"),
+            (None, None, None) => "exec".to_string(),
+        }
+    }
+
+    /// Keys to emit for one, two and three taps, if multi-tap is configured
+    pub fn tap_keycodes(&self) -> Option<Vec<KeyCombo>> {
+        let single = self.keycode.clone()?;
+        let double = self.double_tap_keycode.clone()?;
+        Some(
+            [Some(single), Some(double), self.triple_tap_keycode.clone()]
+                .into_iter()
+                .flatten()
+                .collect(),
+        )
+    }
+
+    /// Time after a release within which the next tap must start
+    pub fn tap_window(&self) -> Option<Duration> {
+        self.tap_window_ms.map(Duration::from_millis)
+    }
+
+    /// Time the key is held down and released in each turbo pulse, if turbo is enabled.
+    ///
+    /// `turbo_hz` and `turbo_duty` must be valid.
+    pub fn turbo_pulse(&self) -> Option<(Duration, Duration)> {
+        let period = Duration::from_secs_f64(1.0 / self.turbo_hz?);
+        let duty = self.turbo_duty.unwrap_or(DEFAULT_TURBO_DUTY);
+        let down = period.mul_f64(f64::from(duty) / 100.0);
+
+        Some((down, period - down))
+    }
+}
+
+/// Configuration for a chord: keys pressed together to emit a different key
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChordConfig {
+    /// GPIO pin numbers of the keys of the chord
+    pub gpios: Vec<u8>,
+    /// Key, or [`KeyCombo`], to emit when all the keys are pressed
+    pub keycode: KeyCombo,
+    /// Name of the device to emit the chord on
+    pub device: Option<String>,
+    window_ms: Option<u64>,
+}
+
+impl ChordConfig {
+    /// Time within which all the keys must be pressed
+    pub fn window(&self) -> Option<Duration> {
+        self.window_ms.map(Duration::from_millis)
+    }
+}
+
+/// Configuration for a macro: a sequence of keys typed by a single key press
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacroConfig {
+    /// Macro name, used by keys to refer to it
+    pub name: String,
+    /// Steps played back in order
+    pub steps: Vec<MacroStep>,
+}
+
+/// A step of a macro
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum MacroStep {
+    /// Wait before playing back the next step
+    Delay { delay_ms: u64 },
+    /// Press and release a key, or [`KeyCombo`]
+    Keys(KeyCombo),
+}
+
+impl fmt::Display for MacroStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Delay { delay_ms } => write!(f, "wait {delay_ms}ms"),
+            Self::Keys(keycode) => write!(f, "{keycode}"),
+        }
+    }
+}
+
+/// Configuration for the commands run by a key
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExecConfig {
+    /// Command run when the key is pressed
+    pub on_press: Option<String>,
+    /// Command run when the key is released
+    pub on_release: Option<String>,
+    /// Command run when the key is held for `hold_ms`
+    pub on_hold: Option<String>,
+    /// What to do with a command triggered while the previous one is running
+    #[serde(default)]
+    pub concurrency: Concurrency,
+    timeout_ms: Option<u64>,
+}
+
+impl ExecConfig {
+    /// Time after which a running command is killed
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_ms.map(Duration::from_millis)
+    }
+}
+
+/// What to do with a command triggered while the previous command of the same key is running
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Concurrency {
+    /// The new command is not run
+    #[default]
+    Ignore,
+    /// The new command is run once the previous ones are finished
+    Queue,
+    /// The previous command is killed, then the new one is run
+    Kill,
 }
 
 /// Configuration for an individual power switch
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PowerSwitchConfig {
     /// GPIO pin number
     pub gpio: u8,
     /// Whether the switch is active low; if true, switch is active when GPIO is low
     pub active_low: Option<bool>,
+    /// Line bias
+    #[serde(default)]
+    pub bias: Bias,
+    /// Pull resistor on Raspberry Pi
+    pub pull: Option<Pull>,
 }
 
 #[cfg(test)]
@@ -99,25 +662,35 @@ mod tests {
         let config: Config = toml::from_str(DEFAULT_CONFIG).unwrap();
 
         assert_eq!(config.default_debounce_ms, 20);
-        assert_eq!(config.default_active_low, true);
+        assert!(config.default_active_low);
         assert_eq!(config.poll_interval_ms, 5);
+        assert_eq!(config.input_mode, InputMode::Poll);
 
         assert_eq!(config.keys.len(), 2);
         assert_eq!(config.keys[0].gpio, 17);
-        assert_eq!(config.keys[0].keycode.keycode(), evdev::KeyCode::KEY_A);
+        assert_eq!(
+            config.keys[0].keycode.as_ref().unwrap().key().keycode(),
+            evdev::KeyCode::KEY_A
+        );
         assert_eq!(config.keys[0].active_low, Some(true));
         assert_eq!(config.keys[0].debounce_ms, Some(20));
-        assert_eq!(config.keys[0].repeat, false);
+        assert!(!config.keys[0].repeat);
+        assert_eq!(config.keys[0].bias, Bias::PullUp);
 
         assert_eq!(config.keys[1].gpio, 22);
-        assert_eq!(config.keys[1].keycode.keycode(), evdev::KeyCode::KEY_UP);
-        assert_eq!(config.keys[1].repeat, true);
+        assert_eq!(
+            config.keys[1].keycode.as_ref().unwrap().key().keycode(),
+            evdev::KeyCode::KEY_UP
+        );
+        assert!(config.keys[1].repeat);
         assert_eq!(config.keys[1].repeat_delay_ms, Some(300));
         assert_eq!(config.keys[1].repeat_rate_ms, Some(80));
+        assert_eq!(config.keys[1].bias, Bias::AsIs);
 
         assert_eq!(config.power_switches.len(), 1);
         assert_eq!(config.power_switches[0].gpio, 27);
         assert_eq!(config.power_switches[0].active_low, Some(false));
+        assert_eq!(config.power_switches[0].bias, Bias::PullDown);
     }
 
     #[test]
@@ -135,6 +708,648 @@ mod tests {
         let _config: Config = toml::from_str(CONFIG_WNO_ARRAYS).unwrap();
     }
 
+    #[test]
+    fn test_should_parse_input_mode() {
+        let config: Config =
+            toml::from_str(&format!("input_mode = \"event\"\n{CONFIG_WNO_ARRAYS}")).unwrap();
+        assert_eq!(config.input_mode, InputMode::Event);
+
+        let config: Config =
+            toml::from_str(&format!("input_mode = \"poll\"\n{CONFIG_WNO_ARRAYS}")).unwrap();
+        assert_eq!(config.input_mode, InputMode::Poll);
+
+        assert!(
+            toml::from_str::<Config>(&format!("input_mode = \"irq\"\n{CONFIG_WNO_ARRAYS}"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_should_parse_output() {
+        let config: Config = toml::from_str("output = \"gamepad\"").unwrap();
+        assert_eq!(config.output, Output::Gamepad);
+        let config: Config = toml::from_str("output = \"keyboard\"").unwrap();
+        assert_eq!(config.output, Output::Keyboard);
+        let config: Config = toml::from_str("output = \"mouse\"").unwrap();
+        assert_eq!(config.output, Output::Mouse);
+        assert!(toml::from_str::<Config>("output = \"joystick\"").is_err());
+    }
+
+    #[test]
+    fn test_should_parse_identity() {
+        let config: Config = toml::from_str(CONFIG_WNO_ARRAYS).unwrap();
+        assert_eq!(config.identity(), Identity::default());
+
+        let config: Config =
+            toml::from_str("bustype = \"virtual\"\nvendor = 0x1209\nphys = \"gpio2key/input0\"")
+                .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.identity(),
+            Identity {
+                bustype: "virtual".parse().ok(),
+                vendor: Some(0x1209),
+                product: None,
+                version: None,
+                phys: Some("gpio2key/input0".to_string()),
+            }
+        );
+
+        let config: Config = toml::from_str("phys = \"gpio\\u0000key\"").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_should_parse_devices() {
+        let config: Config = toml::from_str(
+            r#"
+output = "gamepad"
+
+[[device]]
+name = "player1"
+bustype = "bluetooth"
+vendor = 0x1209
+product = 0x0002
+version = 0x0110
+phys = "gpio2key/input0"
+
+[[device]]
+name = "hotkeys"
+type = "keyboard"
+
+[[key]]
+gpio = 17
+keycode = "BTN_SOUTH"
+
+[[key]]
+gpio = 18
+keycode = "F1"
+device = "hotkeys"
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.devices,
+            vec![
+                DeviceConfig {
+                    name: "player1".to_string(),
+                    kind: None,
+                    bustype: "bluetooth".parse().ok(),
+                    vendor: Some(0x1209),
+                    product: Some(0x0002),
+                    version: Some(0x0110),
+                    phys: Some("gpio2key/input0".to_string()),
+                },
+                DeviceConfig {
+                    name: "hotkeys".to_string(),
+                    kind: Some(Output::Keyboard),
+                    bustype: None,
+                    vendor: None,
+                    product: None,
+                    version: None,
+                    phys: None,
+                },
+            ]
+        );
+        assert_eq!(config.keys[1].device.as_deref(), Some("hotkeys"));
+        assert_eq!(
+            config.device_index(config.keys[0].device.as_deref()),
+            Some(0)
+        );
+        assert_eq!(
+            config.device_index(config.keys[1].device.as_deref()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_should_reject_invalid_devices() {
+        for config in [
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\ndevice = \"player3\"",
+            "[[device]]\nname = \"player1\"\n[[device]]\nname = \"player1\"",
+            &format!(
+                "[[device]]\nname = \"{}\"",
+                "a".repeat(MAX_DEVICE_NAME_LEN + 1)
+            ),
+            "[[device]]\nname = \"player1\"\nphys = \"gpio\\u0000key\"",
+        ] {
+            let config: Config = toml::from_str(config).unwrap();
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_should_reject_rules_reported_by_check() {
+        for config in [
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nrepeat = true\nrepeat_rate_ms = 0",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nrepeat = true\ndebounce_ms = 1000",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\n[[key]]\ngpio = 17\nkeycode = \"B\"",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\n[[powerswitch]]\ngpio = 17",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nturbo_hz = 10.0\nturbo_gpio = 17",
+            "output = \"gamepad\"\n[[key]]\ngpio = 17\nkeycode = \"A\"",
+            "[[device]]\nname = \"pad\"\ntype = \"gamepad\"\n[[key]]\ngpio = 17\nkeycode = \"A\"",
+        ] {
+            let parsed: Config = toml::from_str(config).unwrap();
+            assert!(parsed.validate().is_err(), "{config}");
+            assert!(!check::check(config).is_empty(), "{config}");
+        }
+    }
+
+    #[test]
+    fn test_should_parse_bias() {
+        for (value, bias) in [
+            ("pull-up", Bias::PullUp),
+            ("pull-down", Bias::PullDown),
+            ("disabled", Bias::Disabled),
+            ("as-is", Bias::AsIs),
+        ] {
+            let config: Config = toml::from_str(&format!(
+                "{CONFIG_WNO_ARRAYS}\n[[key]]\ngpio = 17\nkeycode = \"A\"\nrepeat = false\nbias = \"{value}\""
+            ))
+            .unwrap();
+            assert_eq!(config.keys[0].bias, bias);
+        }
+
+        assert!(
+            toml::from_str::<Config>(&format!(
+                "{CONFIG_WNO_ARRAYS}\n[[powerswitch]]\ngpio = 27\nbias = \"up\""
+            ))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_should_resolve_pull() {
+        let mut config: Config = toml::from_str(CONFIG_WNO_ARRAYS).unwrap();
+        assert_eq!(config.default_pull, None);
+
+        // derived from active_low
+        assert_eq!(config.pull(None, None), Pull::Up);
+        assert_eq!(config.pull(None, Some(true)), Pull::Up);
+        assert_eq!(config.pull(None, Some(false)), Pull::Down);
+        // explicit
+        assert_eq!(config.pull(Some(Pull::None), Some(true)), Pull::None);
+
+        // global default
+        config.default_pull = Some(Pull::Down);
+        assert_eq!(config.pull(None, Some(true)), Pull::Down);
+        assert_eq!(config.pull(Some(Pull::Up), Some(false)), Pull::Up);
+    }
+
+    #[test]
+    fn test_should_parse_pull() {
+        let config: Config = toml::from_str(&format!(
+            "default_pull = \"none\"\n{CONFIG_WNO_ARRAYS}\n[[key]]\ngpio = 17\nkeycode = \"A\"\nrepeat = false\npull = \"down\"\n[[powerswitch]]\ngpio = 27\npull = \"up\""
+        ))
+        .unwrap();
+        assert_eq!(config.default_pull, Some(Pull::None));
+        assert_eq!(config.keys[0].pull, Some(Pull::Down));
+        assert_eq!(config.power_switches[0].pull, Some(Pull::Up));
+    }
+
+    #[test]
+    fn test_should_apply_global_defaults() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.default_debounce(), Duration::from_millis(20));
+        assert!(config.default_active_low);
+        assert_eq!(config.poll_interval(), Duration::from_millis(5));
+        assert_eq!(config.input_mode, InputMode::Poll);
+        assert_eq!(config.default_pull, None);
+        assert_eq!(config.output, Output::Keyboard);
+        assert!(config.keys.is_empty());
+        assert!(config.power_switches.is_empty());
+    }
+
+    #[test]
+    fn test_should_apply_key_defaults() {
+        let config: Config = toml::from_str("[[key]]\ngpio = 17\nkeycode = \"A\"").unwrap();
+        let key = &config.keys[0];
+        assert!(!key.repeat);
+        assert_eq!(key.debounce(), None);
+        assert_eq!(key.active_low, None);
+        assert_eq!(key.bias, Bias::AsIs);
+        assert_eq!(key.pull, None);
+        assert_eq!(key.repeat_delay(), None);
+        assert_eq!(key.repeat_rate(), None);
+        assert_eq!(key.hold_keycode, None);
+        assert_eq!(key.hold_time(), None);
+    }
+
+    #[test]
+    fn test_should_require_keycode_without_action() {
+        let config: Config = toml::from_str("[[key]]\ngpio = 17").unwrap();
+        assert_eq!(config.keys[0].keycode, None);
+        assert!(config.validate().is_err());
+
+        let config: Config =
+            toml::from_str("[[key]]\ngpio = 17\nkeycode = \"F1\"\ntext = \"a\"").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_should_name_keys_by_keycode_or_action() {
+        for (key, name) in [
+            ("keycode = \"A\"\ntext = \"a\"", "KEY_A"),
+            ("macro = \"menu\"", "macro \"menu\""),
+            ("text = \"a\"", "text \"a\""),
+            ("exec = { on_press = \"true\" }", "exec"),
+        ] {
+            let key: KeyConfig = toml::from_str(&format!("gpio = 17\n{key}")).unwrap();
+            assert_eq!(key.name(), name);
+        }
+    }
+
+    #[test]
+    fn test_should_parse_hold_keycode() {
+        let config: Config = toml::from_str(
+            "[[key]]\ngpio = 17\nkeycode = \"ENTER\"\nhold_keycode = \"ESC\"\nhold_ms = 1000",
+        )
+        .unwrap();
+        let key = &config.keys[0];
+        assert_eq!(key.hold_keycode, Some("ESC".parse().unwrap()));
+        assert_eq!(key.hold_time(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_should_parse_layers() {
+        let config: Config = toml::from_str(
+            r#"
+[[key]]
+gpio = 4
+keycode = "BTN_MODE"
+layer = "hotkey"
+
+[[key]]
+gpio = 17
+keycode = "BTN_START"
+layers = { hotkey = "ESC" }
+
+[[key]]
+gpio = 18
+keycode = "BTN_TR"
+
+[key.layers]
+hotkey = "F2"
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.keys[0].layer.as_deref(), Some("hotkey"));
+        assert!(config.keys[0].layers.is_empty());
+        assert_eq!(
+            config.keys[1].layers.get("hotkey"),
+            Some(&"ESC".parse().unwrap())
+        );
+        assert_eq!(
+            config.keys[2].layers.get("hotkey"),
+            Some(&"F2".parse().unwrap())
+        );
+
+        for config in [
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nlayers = { hotkey = \"B\" }",
+            "[[key]]\ngpio = 4\nkeycode = \"A\"\nlayer = \"hotkey\"\n[[key]]\ngpio = 5\nkeycode = \"B\"\nlayer = \"hotkey\"",
+            "[[key]]\ngpio = 4\nkeycode = \"A\"\nlayer = \"hotkey\"\nhold_keycode = \"B\"",
+        ] {
+            let config: Config = toml::from_str(config).unwrap();
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+    }
+
+    #[test]
+    fn test_should_parse_key_mode() {
+        let config: Config = toml::from_str(
+            r#"
+[[key]]
+gpio = 17
+keycode = "MUTE"
+mode = "toggle"
+
+[[key]]
+gpio = 18
+keycode = "A"
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.keys[0].mode, KeyMode::Toggle);
+        assert_eq!(config.keys[1].mode, KeyMode::Momentary);
+
+        let config: Config = toml::from_str(
+            r#"
+[[key]]
+gpio = 17
+keycode = "SPACE"
+mode = "tap-hold"
+hold_keycode = "LSHIFT"
+permissive_hold = true
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.keys[0].mode, KeyMode::TapHold);
+        assert!(config.keys[0].permissive_hold);
+
+        assert!(
+            toml::from_str::<Config>("[[key]]\ngpio = 17\nkeycode = \"A\"\nmode = \"latch\"")
+                .is_err()
+        );
+        for config in [
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nmode = \"toggle\"\nhold_keycode = \"B\"",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nmode = \"toggle\"\nlayer = \"fn\"",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nmode = \"tap-hold\"",
+        ] {
+            let config: Config = toml::from_str(config).unwrap();
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+    }
+
+    #[test]
+    fn test_should_parse_turbo() {
+        let config: Config = toml::from_str(
+            r#"
+[[key]]
+gpio = 17
+keycode = "BTN_SOUTH"
+turbo_hz = 10
+turbo_duty = 30
+turbo_gpio = 5
+
+[[key]]
+gpio = 18
+keycode = "BTN_EAST"
+turbo_hz = 12.5
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.keys[0].turbo_gpio, Some(5));
+        assert_eq!(
+            config.keys[0].turbo_pulse(),
+            Some((Duration::from_millis(30), Duration::from_millis(70)))
+        );
+        assert_eq!(
+            config.keys[1].turbo_pulse(),
+            Some((Duration::from_millis(40), Duration::from_millis(40)))
+        );
+
+        for config in [
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nturbo_hz = 0",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nturbo_hz = 10\nturbo_duty = 100",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nturbo_hz = 10\nhold_keycode = \"B\"",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nturbo_hz = 10\nturbo_gpio = 17",
+        ] {
+            let config: Config = toml::from_str(config).unwrap();
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+    }
+
+    #[test]
+    fn test_should_parse_chords() {
+        let config: Config = toml::from_str(
+            r#"
+[[key]]
+gpio = 4
+keycode = "BTN_SELECT"
+
+[[key]]
+gpio = 17
+keycode = "BTN_START"
+
+[[chord]]
+gpios = [4, 17]
+keycode = "ESC"
+window_ms = 80
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.chords.len(), 1);
+        assert_eq!(config.chords[0].gpios, vec![4, 17]);
+        assert_eq!(config.chords[0].keycode.to_string(), "KEY_ESC");
+        assert_eq!(config.chords[0].window(), Some(Duration::from_millis(80)));
+
+        let keys = "[[key]]\ngpio = 4\nkeycode = \"A\"\n[[key]]\ngpio = 17\nkeycode = \"B\"\n";
+        for chords in [
+            "[[chord]]\ngpios = [4]\nkeycode = \"C\"",
+            "[[chord]]\ngpios = [4, 4]\nkeycode = \"C\"",
+            "[[chord]]\ngpios = [4, 18]\nkeycode = \"C\"",
+            "[[chord]]\ngpios = [4, 17]\nkeycode = \"C\"\n[[chord]]\ngpios = [17, 4]\nkeycode = \"D\"",
+            "[[chord]]\ngpios = [4, 17]\nkeycode = \"C\"\ndevice = \"player2\"",
+        ] {
+            let config: Config = toml::from_str(&format!("{keys}{chords}")).unwrap();
+            assert!(config.validate().is_err(), "{chords}");
+        }
+        let config: Config = toml::from_str(&format!(
+            "{keys}hold_keycode = \"C\"\n[[chord]]\ngpios = [4, 17]\nkeycode = \"D\""
+        ))
+        .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_should_parse_macros() {
+        let config: Config = toml::from_str(
+            r#"
+[[macro]]
+name = "menu"
+steps = ["ESC", { delay_ms = 100 }, "DOWN", "DOWN", "LSHIFT+ENTER"]
+
+[[key]]
+gpio = 17
+macro = "menu"
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.keys[0].keycode, None);
+        assert_eq!(config.keys[0].macro_name.as_deref(), Some("menu"));
+        let steps = &config.macro_config("menu").unwrap().steps;
+        assert_eq!(steps.len(), 5);
+        assert_eq!(steps[1], MacroStep::Delay { delay_ms: 100 });
+        assert_eq!(
+            steps.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "KEY_ESC",
+                "wait 100ms",
+                "KEY_DOWN",
+                "KEY_DOWN",
+                "KEY_LEFTSHIFT+KEY_ENTER"
+            ]
+        );
+
+        assert!(
+            toml::from_str::<Config>("[[macro]]\nname = \"menu\"\nsteps = [{ wait = 100 }]")
+                .is_err()
+        );
+        let key = "[[key]]\ngpio = 17\nkeycode = \"A\"\nmacro = \"menu\"\n";
+        for config in [
+            key.to_string(),
+            format!("{key}[[macro]]\nname = \"menu\"\nsteps = []"),
+            format!("{key}hold_keycode = \"B\"\n[[macro]]\nname = \"menu\"\nsteps = [\"C\"]"),
+            format!(
+                "{key}[[macro]]\nname = \"menu\"\nsteps = [\"C\"]\n[[macro]]\nname = \"menu\"\nsteps = [\"D\"]"
+            ),
+        ] {
+            let config: Config = toml::from_str(&config).unwrap();
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_should_parse_text() {
+        let config: Config =
+            toml::from_str("keyboard_layout = \"de\"\n[[key]]\ngpio = 17\ntext = \"Hallo, Welt!\"")
+                .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.keyboard_layout, KeyboardLayout::De);
+        let text_macro = config.keys[0].text_macro(config.keyboard_layout).unwrap();
+        assert_eq!(text_macro.name, "text \"Hallo, Welt!\"");
+        assert_eq!(text_macro.steps.len(), 12);
+        assert_eq!(text_macro.steps[0].to_string(), "KEY_LEFTSHIFT+KEY_H");
+
+        let key = "[[key]]\ngpio = 17\nkeycode = \"A\"\n";
+        for config in [
+            format!("{key}text = \"\""),
+            format!("{key}text = \"café\""),
+            format!("keyboard_layout = \"de\"\n{key}text = \"^\""),
+            format!("{key}text = \"a\"\nrepeat = true\nturbo_hz = 10.0"),
+            format!("{key}text = \"a\"\nmacro = \"m\"\n[[macro]]\nname = \"m\"\nsteps = [\"B\"]"),
+        ] {
+            let config: Config = toml::from_str(&config).unwrap();
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+        assert!(toml::from_str::<Config>("keyboard_layout = \"dvorak\"").is_err());
+    }
+
+    #[test]
+    fn test_should_parse_exec() {
+        let config: Config = toml::from_str(
+            r#"
+[[key]]
+gpio = 17
+hold_ms = 1000
+
+[key.exec]
+on_press = "amixer set Master 5%+"
+on_hold = "systemctl restart wifi"
+concurrency = "queue"
+timeout_ms = 5000
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        let exec = config.keys[0].exec.as_ref().unwrap();
+        assert_eq!(exec.on_press.as_deref(), Some("amixer set Master 5%+"));
+        assert_eq!(exec.on_release, None);
+        assert_eq!(exec.on_hold.as_deref(), Some("systemctl restart wifi"));
+        assert_eq!(exec.concurrency, Concurrency::Queue);
+        assert_eq!(exec.timeout(), Some(Duration::from_secs(5)));
+
+        let key = "[[key]]\ngpio = 17\nkeycode = \"A\"\n";
+        for config in [
+            format!("{key}[key.exec]\nconcurrency = \"kill\""),
+            format!("{key}[key.exec]\non_press = \"true\"\ntimeout_ms = 0"),
+            format!("{key}text = \"a\"\n[key.exec]\non_press = \"true\""),
+            format!("{key}mode = \"toggle\"\n[key.exec]\non_press = \"true\""),
+        ] {
+            let config: Config = toml::from_str(&config).unwrap();
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+        assert!(
+            toml::from_str::<Config>(&format!(
+                "{key}[key.exec]\non_press = \"true\"\nconcurrency = \"parallel\""
+            ))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_should_parse_mouse() {
+        let config: Config = toml::from_str(CONFIG_WNO_ARRAYS).unwrap();
+        assert_eq!(config.mouse, MouseConfig::default());
+
+        let config: Config = toml::from_str(
+            "[mouse]\nspeed = 100\nmax_speed = 900\nacceleration_ms = 500\nacceleration_curve = 1.0\nwheel_speed = 0",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.mouse.acceleration(), Duration::from_millis(500));
+        assert_eq!(config.mouse.pointer_speed(Duration::ZERO), 100.0);
+        assert_eq!(
+            config.mouse.pointer_speed(Duration::from_millis(250)),
+            500.0
+        );
+        assert_eq!(config.mouse.pointer_speed(Duration::from_secs(2)), 900.0);
+        // quadratic by default
+        assert_eq!(
+            MouseConfig::default().pointer_speed(Duration::from_millis(500)),
+            525.0
+        );
+
+        for mouse in [
+            "speed = 0",
+            "speed = 2000",
+            "acceleration_curve = 0",
+            "acceleration_curve = nan",
+        ] {
+            let config: Config = toml::from_str(&format!("[mouse]\n{mouse}")).unwrap();
+            assert!(config.validate().is_err(), "{mouse}");
+        }
+        assert!(toml::from_str::<Config>("[mouse]\nacceleration = 1").is_err());
+    }
+
+    #[test]
+    fn test_should_parse_multi_tap() {
+        let config: Config = toml::from_str(
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\ndouble_tap_keycode = \"B\"\ntriple_tap_keycode = \"LCTRL+C\"\ntap_window_ms = 300",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        let key = &config.keys[0];
+        assert_eq!(
+            key.tap_keycodes()
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["KEY_A", "KEY_B", "KEY_LEFTCTRL+KEY_C"]
+        );
+        assert_eq!(key.tap_window(), Some(Duration::from_millis(300)));
+
+        let config: Config = toml::from_str("[[key]]\ngpio = 17\nkeycode = \"A\"").unwrap();
+        assert_eq!(config.keys[0].tap_keycodes(), None);
+
+        for config in [
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\ntriple_tap_keycode = \"C\"",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\ndouble_tap_keycode = \"B\"\nhold_keycode = \"C\"",
+        ] {
+            let config: Config = toml::from_str(config).unwrap();
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_should_apply_power_switch_defaults() {
+        let config: Config = toml::from_str("[[powerswitch]]\ngpio = 27").unwrap();
+        let power_switch = &config.power_switches[0];
+        assert_eq!(power_switch.active_low, None);
+        assert_eq!(power_switch.bias, Bias::AsIs);
+        assert_eq!(power_switch.pull, None);
+    }
+
+    #[test]
+    fn test_should_reject_unknown_fields() {
+        for config in [
+            "debounce_ms = 20",
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\ndebounce = 20",
+            "[[powerswitch]]\ngpio = 27\nrepeat = true",
+        ] {
+            let err = toml::from_str::<Config>(config).unwrap_err();
+            assert!(err.message().contains("unknown field"), "{err}");
+        }
+    }
+
     const DEFAULT_CONFIG: &str = r#"
 default_debounce_ms = 20 # default debounce time in milliseconds
 default_active_low = true # default active_low setting for keys; if true, key is active when GPIO is low
@@ -146,6 +1361,7 @@ keycode = "A"
 active_low = true # `default_active_low` by default
 debounce_ms = 20 # `default_debounce_ms` by default
 repeat = false # disabled by default
+bias = "pull-up" # "as-is" by default
 
 [[key]]
 gpio = 22
@@ -157,6 +1373,7 @@ repeat_rate_ms = 80
 [[powerswitch]]
 gpio = 27
 active_low = false
+bias = "pull-down"
     "#;
 
     const CONFIG_WNO_ARRAYS: &str = r#"
@@ -0,0 +1,952 @@
+use std::fmt;
+use std::ops::Range;
+
+use serde::Deserialize;
+use toml::Spanned;
+use toml::de::{DeTable, DeValue, ValueDeserializer};
+
+use crate::config::rules::{self, Violation};
+use crate::config::{
+    ChordConfig, Config, DeviceConfig, KeyConfig, KeyMode, MacroConfig, PowerSwitchConfig,
+};
+
+/// A problem found while checking a configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// Line of the problem, starting from 1
+    pub line: usize,
+    /// Column of the problem, starting from 1
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Check a configuration file content, reporting every problem found.
+///
+/// Besides syntax and type errors, this detects settings which are valid TOML
+/// but can't work, such as GPIOs used more than once.
+pub fn check(input: &str) -> Vec<Problem> {
+    let mut checker = Checker {
+        input,
+        problems: Vec::new(),
+    };
+    checker.check();
+    checker
+        .problems
+        .sort_by_key(|problem| (problem.line, problem.column));
+
+    checker.problems
+}
+
+/// A device, key, chord, macro or power switch which has been parsed successfully, with its position
+struct Parsed<'a, T> {
+    config: T,
+    table: &'a Spanned<DeValue<'a>>,
+}
+
+struct Checker<'i> {
+    input: &'i str,
+    problems: Vec<Problem>,
+}
+
+impl<'i> Checker<'i> {
+    fn check(&mut self) {
+        let (document, errors) = DeTable::parse_recoverable(self.input);
+        if !errors.is_empty() {
+            // the document is malformed; further errors would be misleading
+            for error in errors {
+                self.error(&error);
+            }
+            return;
+        }
+        let document = document.into_inner();
+
+        // global settings
+        let mut globals = document.clone();
+        globals.remove("device");
+        globals.remove("key");
+        globals.remove("chord");
+        globals.remove("macro");
+        globals.remove("powerswitch");
+        let mut config = match Config::deserialize(toml::de::Deserializer::from(Spanned::new(
+            0..self.input.len(),
+            globals,
+        ))) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                self.error(&e);
+                None
+            }
+        };
+
+        let devices: Vec<Parsed<DeviceConfig>> = self.parse_array(&document, "device");
+        let keys: Vec<Parsed<KeyConfig>> = self.parse_array(&document, "key");
+        let chords: Vec<Parsed<ChordConfig>> = self.parse_array(&document, "chord");
+        let macros: Vec<Parsed<MacroConfig>> = self.parse_array(&document, "macro");
+        let power_switches: Vec<Parsed<PowerSwitchConfig>> =
+            self.parse_array(&document, "powerswitch");
+
+        self.check_devices(&devices, &keys);
+        self.check_layers(&keys);
+        self.check_chords(&chords, &keys, &devices);
+        self.check_macros(&macros, &keys);
+        self.check_duplicate_gpios(&keys, &power_switches);
+        if let Some(config) = &mut config {
+            // the rules of keys depend on the devices they emit on
+            config.devices = devices.iter().map(|d| d.config.clone()).collect();
+            self.check_globals(config, &document);
+            for key in &keys {
+                self.check_key(config, key);
+            }
+        }
+    }
+
+    /// Parse every table of the array named `name`
+    fn parse_array<'a, T>(&mut self, document: &'a DeTable<'a>, name: &str) -> Vec<Parsed<'a, T>>
+    where
+        T: Deserialize<'a>,
+    {
+        let Some(array) = document.get(name) else {
+            return Vec::new();
+        };
+        let Some(items) = array.get_ref().as_array() else {
+            self.push(array.span(), format!("`{name}` must be an array of tables"));
+            return Vec::new();
+        };
+
+        let mut parsed = Vec::new();
+        for table in items.iter() {
+            match T::deserialize(ValueDeserializer::from(table.clone())) {
+                Ok(config) => parsed.push(Parsed { config, table }),
+                Err(e) => self.error(&e),
+            }
+        }
+
+        parsed
+    }
+
+    fn check_devices(&mut self, devices: &[Parsed<DeviceConfig>], keys: &[Parsed<KeyConfig>]) {
+        for (i, device) in devices.iter().enumerate() {
+            let name = &device.config.name;
+            self.push_violations(device.table, rules::device_violations(&device.config));
+            if let Some(first) = devices[..i].iter().find(|d| &d.config.name == name) {
+                let (line, _) = self.position(field_span(first.table, "name").start);
+                self.push(
+                    field_span(device.table, "name"),
+                    format!("device {name:?} is already declared at line {line}"),
+                );
+            }
+        }
+
+        for key in keys {
+            let Some(name) = &key.config.device else {
+                continue;
+            };
+            if !devices.iter().any(|d| &d.config.name == name) {
+                self.push(
+                    field_span(key.table, "device"),
+                    format!(
+                        "key GPIO {}: device {name:?} is not declared",
+                        key.config.gpio
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_layers(&mut self, keys: &[Parsed<KeyConfig>]) {
+        for (i, key) in keys.iter().enumerate() {
+            let gpio = key.config.gpio;
+            if let Some(layer) = &key.config.layer {
+                if let Some(first) = keys[..i]
+                    .iter()
+                    .find(|k| k.config.layer.as_ref() == Some(layer))
+                {
+                    let (line, _) = self.position(field_span(first.table, "layer").start);
+                    self.push(
+                        field_span(key.table, "layer"),
+                        format!("layer {layer:?} is already declared at line {line}"),
+                    );
+                }
+            }
+
+            for layer in key.config.layers.keys() {
+                if !keys.iter().any(|k| k.config.layer.as_ref() == Some(layer)) {
+                    self.push(
+                        field_span(key.table, "layers"),
+                        format!("key GPIO {gpio}: layer {layer:?} is not declared"),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_chords(
+        &mut self,
+        chords: &[Parsed<ChordConfig>],
+        keys: &[Parsed<KeyConfig>],
+        devices: &[Parsed<DeviceConfig>],
+    ) {
+        for (i, chord) in chords.iter().enumerate() {
+            let name = &chord.config.keycode;
+            let gpios = &chord.config.gpios;
+            let span = field_span(chord.table, "gpios");
+            self.push_violations(chord.table, rules::chord_violations(&chord.config));
+            for gpio in gpios {
+                match keys.iter().find(|key| key.config.gpio == *gpio) {
+                    None => self.push(
+                        span.clone(),
+                        format!("chord {name}: GPIO {gpio} is not a key"),
+                    ),
+                    Some(key) => self.push_violations(
+                        chord.table,
+                        rules::chord_key_violation(&chord.config, &key.config),
+                    ),
+                }
+                if let Some(first) = chords[..i].iter().find(|c| c.config.gpios.contains(gpio)) {
+                    let (line, _) = self.position(field_span(first.table, "gpios").start);
+                    self.push(
+                        span.clone(),
+                        format!(
+                            "chord {name}: GPIO {gpio} is already used by the chord at line {line}"
+                        ),
+                    );
+                }
+            }
+            if let Some(device) = &chord.config.device {
+                if !devices.iter().any(|d| &d.config.name == device) {
+                    self.push(
+                        field_span(chord.table, "device"),
+                        format!("chord {name}: device {device:?} is not declared"),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_macros(&mut self, macros: &[Parsed<MacroConfig>], keys: &[Parsed<KeyConfig>]) {
+        for (i, macro_config) in macros.iter().enumerate() {
+            let name = &macro_config.config.name;
+            if let Some(first) = macros[..i].iter().find(|m| &m.config.name == name) {
+                let (line, _) = self.position(field_span(first.table, "name").start);
+                self.push(
+                    field_span(macro_config.table, "name"),
+                    format!("macro {name:?} is already declared at line {line}"),
+                );
+            }
+            self.push_violations(
+                macro_config.table,
+                rules::macro_violations(&macro_config.config),
+            );
+        }
+
+        for key in keys {
+            let Some(name) = &key.config.macro_name else {
+                continue;
+            };
+            if !macros.iter().any(|m| &m.config.name == name) {
+                self.push(
+                    field_span(key.table, "macro"),
+                    format!(
+                        "key GPIO {}: macro {name:?} is not declared",
+                        key.config.gpio
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_duplicate_gpios(
+        &mut self,
+        keys: &[Parsed<KeyConfig>],
+        power_switches: &[Parsed<PowerSwitchConfig>],
+    ) {
+        let tables = keys
+            .iter()
+            .map(|key| key.table)
+            .chain(power_switches.iter().map(|ps| ps.table))
+            .collect::<Vec<_>>();
+        let reuses = rules::gpio_reuses(
+            &keys.iter().map(|key| &key.config).collect::<Vec<_>>(),
+            &power_switches
+                .iter()
+                .map(|ps| &ps.config)
+                .collect::<Vec<_>>(),
+        );
+        for reuse in reuses {
+            let (field, message) = reuse.violation;
+            let (line, _) = self.position(field_span(tables[reuse.first], "gpio").start);
+            self.push(
+                field_span(tables[reuse.index], field),
+                format!("{message} at line {line}"),
+            );
+        }
+    }
+
+    fn check_globals(&mut self, config: &Config, document: &DeTable) {
+        for (field, message) in rules::global_violations(config) {
+            let span = match field.split_once('.') {
+                Some((table, field)) => document.get(table).map(|table| field_span(table, field)),
+                None => document.get(field).map(|value| value.span()),
+            };
+            self.push(span.unwrap_or(0..0), message);
+        }
+    }
+
+    fn check_key(&mut self, config: &Config, key: &Parsed<KeyConfig>) {
+        let table = key.table;
+        let key = &key.config;
+        self.push_violations(table, rules::key_violations(config, key));
+        let on_hold = key.exec.as_ref().is_some_and(|exec| exec.on_hold.is_some());
+        if key.hold_keycode.is_none() && !on_hold && key.hold_time().is_some() {
+            self.push(
+                field_span(table, "hold_ms"),
+                format!(
+                    "key GPIO {}: `hold_ms` has no effect without `hold_keycode`",
+                    key.gpio
+                ),
+            );
+        }
+        if key.double_tap_keycode.is_none() && has_field(table, "tap_window_ms") {
+            self.push(
+                field_span(table, "tap_window_ms"),
+                format!(
+                    "key GPIO {}: `tap_window_ms` requires `double_tap_keycode`",
+                    key.gpio
+                ),
+            );
+        }
+        if key.turbo_hz.is_none() {
+            for field in ["turbo_duty", "turbo_gpio"] {
+                if has_field(table, field) {
+                    self.push(
+                        field_span(table, field),
+                        format!("key GPIO {}: `{field}` requires `turbo_hz`", key.gpio),
+                    );
+                }
+            }
+        }
+        if key.permissive_hold && key.mode != KeyMode::TapHold {
+            self.push(
+                field_span(table, "permissive_hold"),
+                format!(
+                    "key GPIO {}: `permissive_hold` has no effect without `mode = \"tap-hold\"`",
+                    key.gpio
+                ),
+            );
+        }
+        if !key.repeat {
+            return;
+        }
+        let action = [
+            ("macro", key.macro_name.is_some()),
+            ("text", key.text.is_some()),
+            ("exec", key.exec.is_some()),
+        ]
+        .into_iter()
+        .find_map(|(action, set)| set.then_some(action));
+        if let Some(action) = action {
+            self.push(
+                field_span(table, "repeat"),
+                format!(
+                    "key GPIO {}: `repeat` has no effect with `{action}`",
+                    key.gpio
+                ),
+            );
+        } else if key.turbo_hz.is_some() {
+            self.push(
+                field_span(table, "repeat"),
+                format!(
+                    "key GPIO {}: `repeat` has no effect with `turbo_hz`",
+                    key.gpio
+                ),
+            );
+        } else if key.mode == KeyMode::Toggle {
+            self.push(
+                field_span(table, "repeat"),
+                format!(
+                    "key GPIO {}: `repeat` has no effect with `mode = \"toggle\"`",
+                    key.gpio
+                ),
+            );
+        } else if key.double_tap_keycode.is_some() {
+            self.push(
+                field_span(table, "repeat"),
+                format!(
+                    "key GPIO {}: `repeat` has no effect with `double_tap_keycode`",
+                    key.gpio
+                ),
+            );
+        } else if key.hold_keycode.is_some() {
+            self.push(
+                field_span(table, "repeat"),
+                format!(
+                    "key GPIO {}: `repeat` has no effect with `hold_keycode`",
+                    key.gpio
+                ),
+            );
+        }
+    }
+
+    fn error(&mut self, error: &toml::de::Error) {
+        self.push(error.span().unwrap_or(0..0), error.message().to_string());
+    }
+
+    fn push_violations(
+        &mut self,
+        table: &Spanned<DeValue>,
+        violations: impl IntoIterator<Item = Violation>,
+    ) {
+        for (field, message) in violations {
+            self.push(field_span(table, field), message);
+        }
+    }
+
+    fn push(&mut self, span: Range<usize>, message: String) {
+        let (line, column) = self.position(span.start);
+        self.problems.push(Problem {
+            line,
+            column,
+            message,
+        });
+    }
+
+    /// Get line and column of the byte `offset` in the input
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.input[..offset.min(self.input.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rfind('\n')
+            .map(|newline| before[newline + 1..].chars().count())
+            .unwrap_or_else(|| before.chars().count())
+            + 1;
+
+        (line, column)
+    }
+}
+
+/// Whether `field` is set in `table`
+fn has_field(table: &Spanned<DeValue>, field: &str) -> bool {
+    table
+        .get_ref()
+        .as_table()
+        .is_some_and(|t| t.contains_key(field))
+}
+
+/// Span of the value of `field` in `table`, or of the table itself if the field is missing
+fn field_span(table: &Spanned<DeValue>, field: &str) -> Range<usize> {
+    table
+        .get_ref()
+        .as_table()
+        .and_then(|t| t.get(field))
+        .map(|value| value.span())
+        .unwrap_or_else(|| table.span())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::MAX_DEVICE_NAME_LEN;
+
+    #[test]
+    fn test_should_accept_valid_config() {
+        assert_eq!(check(VALID_CONFIG), vec![]);
+    }
+
+    #[test]
+    fn test_should_report_syntax_errors() {
+        let problems = check("default_debounce_ms = \npoll_interval_ms = [\n");
+        assert_eq!(problems.len(), 2, "{problems:#?}");
+        assert_eq!((problems[0].line, problems[0].column), (1, 23));
+        assert_eq!(problems[1].line, 3);
+    }
+
+    #[test]
+    fn test_should_report_every_problem() {
+        let problems = check(INVALID_CONFIG);
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![4, 9, 16, 17, 22], "{problems:#?}");
+
+        assert!(problems[0].message.contains("poll_interval_ms"));
+        assert!(problems[1].message.contains("Unsupported keycode"));
+        assert!(problems[2].message.contains("debounce"));
+        assert!(problems[3].message.contains("repeat_rate_ms"));
+        assert_eq!(problems[4].message, "GPIO 17 is already used at line 12");
+        assert_eq!(problems[4].column, 8);
+    }
+
+    #[test]
+    fn test_should_report_invalid_devices() {
+        let long_name = "x".repeat(MAX_DEVICE_NAME_LEN + 1);
+        let problems = check(&format!(
+            r#"[[device]]
+name = "player1"
+
+[[device]]
+name = "player1"
+
+[[device]]
+name = "{long_name}"
+phys = "gpio\u0000key"
+
+[[key]]
+gpio = 17
+keycode = "A"
+device = "player2"
+"#
+        ));
+        assert_eq!(problems.len(), 4, "{problems:#?}");
+        assert_eq!(
+            problems[0].to_string(),
+            "5:8: device \"player1\" is already declared at line 2"
+        );
+        assert_eq!(problems[1].line, 8);
+        assert!(problems[1].message.contains("is longer than"));
+        assert_eq!(problems[2].line, 9);
+        assert!(problems[2].message.contains("NUL"));
+        assert_eq!(
+            problems[3].to_string(),
+            "14:10: key GPIO 17: device \"player2\" is not declared"
+        );
+    }
+
+    #[test]
+    fn test_should_report_ineffective_hold_settings() {
+        let problems = check(
+            "[[key]]\ngpio = 17\nkeycode = \"A\"\nhold_ms = 100\n\n[[key]]\ngpio = 18\nkeycode = \"A\"\nhold_keycode = \"B\"\nrepeat = true\n",
+        );
+        assert_eq!(problems.len(), 2, "{problems:#?}");
+        assert_eq!(
+            problems[0].to_string(),
+            "4:11: key GPIO 17: `hold_ms` has no effect without `hold_keycode`"
+        );
+        assert_eq!(
+            problems[1].to_string(),
+            "10:10: key GPIO 18: `repeat` has no effect with `hold_keycode`"
+        );
+    }
+
+    #[test]
+    fn test_should_report_invalid_multi_tap_settings() {
+        let problems = check(
+            r#"[[key]]
+gpio = 17
+keycode = "A"
+triple_tap_keycode = "C"
+tap_window_ms = 300
+
+[[key]]
+gpio = 18
+keycode = "A"
+double_tap_keycode = "B"
+hold_keycode = "C"
+repeat = true
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![4, 5, 11, 12], "{problems:#?}");
+        assert!(
+            problems[0]
+                .message
+                .contains("requires `double_tap_keycode`")
+        );
+        assert!(problems[2].message.contains("`hold_keycode` can't be used"));
+        assert!(problems[3].message.contains("`repeat` has no effect"));
+    }
+
+    #[test]
+    fn test_should_report_invalid_layers() {
+        let problems = check(
+            r#"
+[[key]]
+gpio = 4
+keycode = "BTN_MODE"
+layer = "hotkey"
+hold_keycode = "A"
+
+[[key]]
+gpio = 5
+keycode = "B"
+layer = "hotkey"
+
+[[key]]
+gpio = 6
+keycode = "C"
+layers = { fn = "F1" }
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![6, 11, 16], "{problems:#?}");
+        assert!(problems[0].message.contains("can't be used with `layer`"));
+        assert!(problems[1].message.contains("already declared at line 5"));
+        assert!(problems[2].message.contains("layer \"fn\" is not declared"));
+    }
+
+    #[test]
+    fn test_should_report_invalid_toggle_keys() {
+        let problems = check(
+            r#"[[key]]
+gpio = 17
+keycode = "MUTE"
+mode = "toggle"
+hold_keycode = "A"
+repeat = true
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![5, 6], "{problems:#?}");
+        assert!(problems[0].message.contains("can't be used with `mode"));
+        assert!(problems[1].message.contains("`repeat` has no effect"));
+    }
+
+    #[test]
+    fn test_should_report_invalid_tap_hold_keys() {
+        let problems = check(
+            r#"[[key]]
+gpio = 17
+keycode = "SPACE"
+mode = "tap-hold"
+
+[[key]]
+gpio = 18
+keycode = "B"
+permissive_hold = true
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![4, 9], "{problems:#?}");
+        assert!(problems[0].message.contains("requires `hold_keycode`"));
+        assert!(problems[1].message.contains("has no effect without `mode"));
+    }
+
+    #[test]
+    fn test_should_report_invalid_turbo_settings() {
+        let problems = check(
+            r#"[[key]]
+gpio = 17
+keycode = "A"
+turbo_duty = 30
+
+[[key]]
+gpio = 18
+keycode = "B"
+turbo_hz = 0
+turbo_duty = 100
+turbo_gpio = 17
+repeat = true
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![4, 9, 10, 11, 12], "{problems:#?}");
+        assert!(problems[0].message.contains("requires `turbo_hz`"));
+        assert!(problems[1].message.contains("must be greater than 0"));
+        assert!(problems[2].message.contains("between 1 and 99"));
+        assert!(problems[3].message.contains("already used at line 2"));
+        assert!(problems[4].message.contains("`repeat` has no effect"));
+    }
+
+    #[test]
+    fn test_should_report_invalid_chords() {
+        let problems = check(
+            r#"
+[[key]]
+gpio = 4
+keycode = "BTN_SELECT"
+
+[[key]]
+gpio = 17
+keycode = "BTN_START"
+hold_keycode = "A"
+
+[[chord]]
+gpios = [4, 17]
+keycode = "ESC"
+device = "player2"
+
+[[chord]]
+gpios = [4, 22]
+keycode = "F1"
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![12, 14, 17, 17], "{problems:#?}");
+        assert!(problems[0].message.contains("can't have `hold_keycode`"));
+        assert!(
+            problems[1]
+                .message
+                .contains("device \"player2\" is not declared")
+        );
+        assert!(problems[2].message.contains("GPIO 4 is already used"));
+        assert!(problems[3].message.contains("GPIO 22 is not a key"));
+    }
+
+    #[test]
+    fn test_should_report_keyboard_keys_on_gamepads() {
+        let problems = check(
+            r#"output = "gamepad"
+
+[[device]]
+name = "player1"
+
+[[device]]
+name = "hotkeys"
+type = "keyboard"
+
+[[key]]
+gpio = 17
+keycode = "BTN_SOUTH"
+hold_keycode = "ENTER"
+
+[[key]]
+gpio = 18
+keycode = "UP"
+
+[[key]]
+gpio = 19
+keycode = "F1"
+device = "hotkeys"
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![13], "{problems:#?}");
+        assert!(problems[0].message.contains(
+            "KEY_ENTER is a keyboard key, which would make the gamepad look like a keyboard"
+        ));
+    }
+
+    #[test]
+    fn test_should_report_invalid_macros() {
+        let problems = check(
+            r#"
+[[macro]]
+name = "menu"
+steps = ["ESC", { delay_ms = 100 }, "ENTER"]
+
+[[macro]]
+name = "menu"
+steps = []
+
+[[key]]
+gpio = 17
+keycode = "A"
+macro = "menu"
+layer = "fn"
+repeat = true
+
+[[key]]
+gpio = 18
+keycode = "B"
+macro = "exit"
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![7, 8, 14, 15, 20], "{problems:#?}");
+        assert!(problems[0].message.contains("already declared at line 3"));
+        assert!(problems[1].message.contains("has no steps"));
+        assert!(problems[2].message.contains("can't be used with `macro`"));
+        assert!(problems[3].message.contains("`repeat` has no effect"));
+        assert!(
+            problems[4]
+                .message
+                .contains("macro \"exit\" is not declared")
+        );
+    }
+
+    #[test]
+    fn test_should_report_invalid_text_keys() {
+        let problems = check(
+            r#"keyboard_layout = "fr"
+
+[[key]]
+gpio = 17
+keycode = "A"
+text = "naïve"
+mode = "toggle"
+
+[[key]]
+gpio = 18
+keycode = "B"
+text = ""
+repeat = true
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![6, 7, 12, 13], "{problems:#?}");
+        assert!(
+            problems[0]
+                .message
+                .contains("character 'ï' which can't be typed with the Fr keyboard layout")
+        );
+        assert!(problems[1].message.contains("can't be used with `text`"));
+        assert!(problems[2].message.contains("`text` is empty"));
+        assert!(problems[3].message.contains("has no effect with `text`"));
+    }
+
+    #[test]
+    fn test_should_report_invalid_exec_keys() {
+        let problems = check(
+            r#"[[key]]
+gpio = 17
+keycode = "A"
+hold_ms = 1000
+exec = { on_hold = "reboot" }
+
+[[key]]
+gpio = 18
+keycode = "B"
+hold_ms = 1000
+text = "b"
+exec = { concurrency = "kill", timeout_ms = 0 }
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![10, 12, 12, 12], "{problems:#?}");
+        assert!(problems[0].message.contains("`hold_ms` has no effect"));
+        assert!(
+            problems[1]
+                .message
+                .contains("`exec` can't be used with `text`")
+        );
+        assert!(problems[2].message.contains("requires `on_press`"));
+        assert!(problems[3].message.contains("must be greater than 0"));
+    }
+
+    #[test]
+    fn test_should_require_keycode_without_action() {
+        let problems = check(
+            r#"[[key]]
+gpio = 17
+text = "a"
+
+[[key]]
+gpio = 18
+repeat = true
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![5], "{problems:#?}");
+        assert!(problems[0].message.contains("`keycode` is required"));
+    }
+
+    #[test]
+    fn test_should_report_invalid_mouse_settings() {
+        let problems = check(
+            r#"output = "mouse"
+
+[mouse]
+speed = 500
+max_speed = 300
+acceleration_curve = -1.0
+"#,
+        );
+        let lines = problems
+            .iter()
+            .map(|problem| problem.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![5, 6], "{problems:#?}");
+        assert!(problems[0].message.contains("at least `mouse.speed`"));
+        assert!(problems[1].message.contains("positive number"));
+    }
+
+    #[test]
+    fn test_should_report_invalid_identity() {
+        let problems = check("vendor = 0x1209\nphys = \"gpio\\u0000key\"\n");
+        assert_eq!(problems.len(), 1, "{problems:#?}");
+        assert_eq!((problems[0].line, problems[0].column), (2, 8));
+        assert!(problems[0].message.contains("NUL character"));
+    }
+
+    #[test]
+    fn test_should_report_unknown_fields() {
+        let problems = check("[[key]]\ngpio = 1\nkeycode = \"A\"\ndebounce = 10\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].line, problems[0].column), (4, 1));
+        assert!(problems[0].message.contains("unknown field `debounce`"));
+    }
+
+    #[test]
+    fn test_should_format_problem() {
+        let problem = Problem {
+            line: 3,
+            column: 7,
+            message: "oops".to_string(),
+        };
+        assert_eq!(problem.to_string(), "3:7: oops");
+    }
+
+    const VALID_CONFIG: &str = r#"default_debounce_ms = 20
+default_active_low = true
+poll_interval_ms = 5
+
+[[key]]
+gpio = 17
+keycode = "A"
+repeat = true
+"#;
+
+    const INVALID_CONFIG: &str = r#"default_debounce_ms = 20
+default_active_low = true
+input_mode = "poll"
+poll_interval_ms = 0
+
+[[key]]
+gpio = 16
+repeat = false
+keycode = "NOPE"
+
+[[key]]
+gpio = 17
+keycode = "A"
+repeat = true
+repeat_delay_ms = 10
+debounce_ms = 50
+repeat_rate_ms = 0
+
+[[powerswitch]]
+active_low = false
+
+gpio = 17
+"#;
+}
@@ -0,0 +1,189 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Deserializer};
+
+use crate::config::Keycode;
+
+/// A key combination, such as `LCTRL+LALT+T`, emitted as a unit.
+///
+/// The keys are pressed in order and released in reverse order, so the modifiers come first
+/// and the last key is the main one.
+/// It can be written as a single keycode, as keycodes joined by `+` or as a list of keycodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCombo(Vec<Keycode>);
+
+impl KeyCombo {
+    /// Create a new [`KeyCombo`] pressing `keycodes` in order
+    pub fn new(keycodes: Vec<Keycode>) -> Result<Self, String> {
+        if keycodes.is_empty() {
+            return Err("Key combination is empty".to_string());
+        }
+        for (i, keycode) in keycodes.iter().enumerate() {
+            if keycodes[..i].contains(keycode) {
+                return Err(format!(
+                    "Key {keycode} appears more than once in the combination"
+                ));
+            }
+        }
+
+        Ok(KeyCombo(keycodes))
+    }
+
+    /// Keys of the combination, in press order
+    pub fn keycodes(&self) -> &[Keycode] {
+        &self.0
+    }
+
+    /// The main key of the combination, which is the last one
+    pub fn key(&self) -> Keycode {
+        *self.0.last().expect("combination can't be empty")
+    }
+}
+
+impl From<Keycode> for KeyCombo {
+    fn from(keycode: Keycode) -> Self {
+        KeyCombo(vec![keycode])
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, keycode) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "+")?;
+            }
+            write!(f, "{keycode}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split('+')
+            .map(|name| match name.trim() {
+                "" => Err(format!("Missing key in combination {s:?}")),
+                name => Keycode::from_str(name),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(Self::new)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyCombo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyComboVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for KeyComboVisitor {
+            type Value = KeyCombo;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "a key name, a raw key code, or a combination of them joined by `+` or in a list"
+                )
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                KeyCombo::from_str(v).map_err(E::custom)
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Keycode::deserialize(v.into_deserializer()).map(KeyCombo::from)
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Keycode::deserialize(v.into_deserializer()).map(KeyCombo::from)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut keycodes = Vec::new();
+                while let Some(keycode) = seq.next_element::<Keycode>()? {
+                    keycodes.push(keycode);
+                }
+
+                KeyCombo::new(keycodes).map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_any(KeyComboVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use evdev::KeyCode;
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_combo() {
+        let combo = KeyCombo::from_str("LCTRL+LALT+T").unwrap();
+        assert_eq!(
+            codes(&combo),
+            vec![KeyCode::KEY_LEFTCTRL, KeyCode::KEY_LEFTALT, KeyCode::KEY_T]
+        );
+        assert_eq!(combo.key().keycode(), KeyCode::KEY_T);
+
+        let combo = KeyCombo::from_str("lshift + f1").unwrap();
+        assert_eq!(codes(&combo), vec![KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_F1]);
+
+        let combo = KeyCombo::from_str("A").unwrap();
+        assert_eq!(codes(&combo), vec![KeyCode::KEY_A]);
+    }
+
+    #[test]
+    fn test_should_reject_invalid_combo() {
+        assert!(KeyCombo::from_str("LCTRL+").is_err());
+        assert!(KeyCombo::from_str("LCTRL+NOPE").is_err());
+        assert!(KeyCombo::from_str("A+A").is_err());
+        assert!(KeyCombo::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_should_deserialize_combo() {
+        #[derive(Deserialize)]
+        struct Key {
+            keycode: KeyCombo,
+        }
+
+        for (toml, expected) in [
+            (r#"keycode = "LCTRL+T""#, "KEY_LEFTCTRL+KEY_T"),
+            (r#"keycode = ["LCTRL", "T"]"#, "KEY_LEFTCTRL+KEY_T"),
+            (r#"keycode = ["LCTRL", 20]"#, "KEY_LEFTCTRL+KEY_T"),
+            ("keycode = 20", "KEY_T"),
+        ] {
+            let key: Key = toml::from_str(toml).unwrap();
+            assert_eq!(key.keycode.to_string(), expected);
+        }
+
+        for toml in ["keycode = []", r#"keycode = ["A", "A"]"#, "keycode = -1"] {
+            assert!(toml::from_str::<Key>(toml).is_err(), "{toml}");
+        }
+    }
+
+    fn codes(combo: &KeyCombo) -> Vec<KeyCode> {
+        combo.keycodes().iter().map(Keycode::keycode).collect()
+    }
+}
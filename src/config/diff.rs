@@ -0,0 +1,465 @@
+use std::fmt::Debug;
+
+use crate::config::{ChordConfig, Config, DeviceConfig, KeyConfig, MacroConfig, PowerSwitchConfig};
+
+/// Human readable differences between two [`Config`]s, used to report what changed on reload
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    changes: Vec<String>,
+}
+
+impl ConfigDiff {
+    /// Compute the differences from `old` to `new`.
+    ///
+    /// Keys and power switches are matched by GPIO number, chords by their GPIO numbers,
+    /// devices and macros by name.
+    pub fn new(old: &Config, new: &Config) -> Self {
+        let mut diff = ConfigDiff::default();
+
+        diff.field(
+            "default_debounce_ms",
+            &old.default_debounce_ms,
+            &new.default_debounce_ms,
+        );
+        diff.field(
+            "default_active_low",
+            &old.default_active_low,
+            &new.default_active_low,
+        );
+        diff.field(
+            "poll_interval_ms",
+            &old.poll_interval_ms,
+            &new.poll_interval_ms,
+        );
+        diff.field("input_mode", &old.input_mode, &new.input_mode);
+        diff.field("default_pull", &old.default_pull, &new.default_pull);
+        diff.field("output", &old.output, &new.output);
+        diff.field("bustype", &old.bustype, &new.bustype);
+        diff.field("vendor", &old.vendor, &new.vendor);
+        diff.field("product", &old.product, &new.product);
+        diff.field("version", &old.version, &new.version);
+        diff.field("phys", &old.phys, &new.phys);
+        diff.field(
+            "keyboard_layout",
+            &old.keyboard_layout,
+            &new.keyboard_layout,
+        );
+        diff.field("mouse.speed", &old.mouse.speed, &new.mouse.speed);
+        diff.field(
+            "mouse.max_speed",
+            &old.mouse.max_speed,
+            &new.mouse.max_speed,
+        );
+        diff.field(
+            "mouse.acceleration_ms",
+            &old.mouse.acceleration_ms,
+            &new.mouse.acceleration_ms,
+        );
+        diff.field(
+            "mouse.acceleration_curve",
+            &old.mouse.acceleration_curve,
+            &new.mouse.acceleration_curve,
+        );
+        diff.field(
+            "mouse.wheel_speed",
+            &old.mouse.wheel_speed,
+            &new.mouse.wheel_speed,
+        );
+
+        for device in &old.devices {
+            if !new.devices.iter().any(|d| d.name == device.name) {
+                diff.changes
+                    .push(format!("device {:?} removed", device.name));
+            }
+        }
+        for device in &new.devices {
+            match old.devices.iter().find(|d| d.name == device.name) {
+                None => diff.changes.push(format!("device {:?} added", device.name)),
+                Some(old_device) => diff.device(old_device, device),
+            }
+        }
+
+        for key in &old.keys {
+            if !new.keys.iter().any(|k| k.gpio == key.gpio) {
+                diff.changes
+                    .push(format!("key GPIO {} removed ({})", key.gpio, key.name()));
+            }
+        }
+        for key in &new.keys {
+            match old.keys.iter().find(|k| k.gpio == key.gpio) {
+                None => diff
+                    .changes
+                    .push(format!("key GPIO {} added ({})", key.gpio, key.name())),
+                Some(old_key) => diff.key(old_key, key),
+            }
+        }
+
+        for chord in &old.chords {
+            if !new.chords.iter().any(|c| c.gpios == chord.gpios) {
+                diff.changes.push(format!(
+                    "chord GPIOs {:?} removed ({})",
+                    chord.gpios, chord.keycode
+                ));
+            }
+        }
+        for chord in &new.chords {
+            match old.chords.iter().find(|c| c.gpios == chord.gpios) {
+                None => diff.changes.push(format!(
+                    "chord GPIOs {:?} added ({})",
+                    chord.gpios, chord.keycode
+                )),
+                Some(old_chord) => diff.chord(old_chord, chord),
+            }
+        }
+
+        for macro_config in &old.macros {
+            if !new.macros.iter().any(|m| m.name == macro_config.name) {
+                diff.changes
+                    .push(format!("macro {:?} removed", macro_config.name));
+            }
+        }
+        for macro_config in &new.macros {
+            match old.macros.iter().find(|m| m.name == macro_config.name) {
+                None => diff
+                    .changes
+                    .push(format!("macro {:?} added", macro_config.name)),
+                Some(old_macro) => diff.macro_config(old_macro, macro_config),
+            }
+        }
+
+        for ps in &old.power_switches {
+            if !new.power_switches.iter().any(|p| p.gpio == ps.gpio) {
+                diff.changes
+                    .push(format!("power switch GPIO {} removed", ps.gpio));
+            }
+        }
+        for ps in &new.power_switches {
+            match old.power_switches.iter().find(|p| p.gpio == ps.gpio) {
+                None => diff
+                    .changes
+                    .push(format!("power switch GPIO {} added", ps.gpio)),
+                Some(old_ps) => diff.power_switch(old_ps, ps),
+            }
+        }
+
+        diff
+    }
+
+    /// Whether the configurations are equal
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// List of changes, one per line
+    pub fn changes(&self) -> &[String] {
+        &self.changes
+    }
+
+    /// Report the changes of a device with the same name
+    fn device(&mut self, old: &DeviceConfig, new: &DeviceConfig) {
+        let prefix = format!("device {:?}", new.name);
+        self.prefixed_field(&prefix, "type", &old.kind, &new.kind);
+        self.prefixed_field(&prefix, "bustype", &old.bustype, &new.bustype);
+        self.prefixed_field(&prefix, "vendor", &old.vendor, &new.vendor);
+        self.prefixed_field(&prefix, "product", &old.product, &new.product);
+        self.prefixed_field(&prefix, "version", &old.version, &new.version);
+        self.prefixed_field(&prefix, "phys", &old.phys, &new.phys);
+    }
+
+    /// Report the changes of a key with the same GPIO
+    fn key(&mut self, old: &KeyConfig, new: &KeyConfig) {
+        let prefix = format!("key GPIO {}", new.gpio);
+        self.prefixed_field(
+            &prefix,
+            "keycode",
+            &old.keycode.as_ref().map(ToString::to_string),
+            &new.keycode.as_ref().map(ToString::to_string),
+        );
+        self.prefixed_field(&prefix, "device", &old.device, &new.device);
+        self.prefixed_field(&prefix, "debounce_ms", &old.debounce_ms, &new.debounce_ms);
+        self.prefixed_field(&prefix, "active_low", &old.active_low, &new.active_low);
+        self.prefixed_field(&prefix, "bias", &old.bias, &new.bias);
+        self.prefixed_field(&prefix, "pull", &old.pull, &new.pull);
+        self.prefixed_field(&prefix, "mode", &old.mode, &new.mode);
+        self.prefixed_field(&prefix, "repeat", &old.repeat, &new.repeat);
+        self.prefixed_field(
+            &prefix,
+            "repeat_delay_ms",
+            &old.repeat_delay_ms,
+            &new.repeat_delay_ms,
+        );
+        self.prefixed_field(
+            &prefix,
+            "repeat_rate_ms",
+            &old.repeat_rate_ms,
+            &new.repeat_rate_ms,
+        );
+        self.prefixed_field(
+            &prefix,
+            "hold_keycode",
+            &old.hold_keycode.as_ref().map(ToString::to_string),
+            &new.hold_keycode.as_ref().map(ToString::to_string),
+        );
+        self.prefixed_field(&prefix, "hold_ms", &old.hold_ms, &new.hold_ms);
+        self.prefixed_field(
+            &prefix,
+            "permissive_hold",
+            &old.permissive_hold,
+            &new.permissive_hold,
+        );
+        self.prefixed_field(
+            &prefix,
+            "double_tap_keycode",
+            &old.double_tap_keycode.as_ref().map(ToString::to_string),
+            &new.double_tap_keycode.as_ref().map(ToString::to_string),
+        );
+        self.prefixed_field(
+            &prefix,
+            "triple_tap_keycode",
+            &old.triple_tap_keycode.as_ref().map(ToString::to_string),
+            &new.triple_tap_keycode.as_ref().map(ToString::to_string),
+        );
+        self.prefixed_field(
+            &prefix,
+            "tap_window_ms",
+            &old.tap_window_ms,
+            &new.tap_window_ms,
+        );
+        self.prefixed_field(&prefix, "turbo_hz", &old.turbo_hz, &new.turbo_hz);
+        self.prefixed_field(&prefix, "turbo_duty", &old.turbo_duty, &new.turbo_duty);
+        self.prefixed_field(&prefix, "turbo_gpio", &old.turbo_gpio, &new.turbo_gpio);
+        self.prefixed_field(&prefix, "layer", &old.layer, &new.layer);
+        self.prefixed_field(&prefix, "layers", &layers(old), &layers(new));
+        self.prefixed_field(&prefix, "macro", &old.macro_name, &new.macro_name);
+        self.prefixed_field(&prefix, "text", &old.text, &new.text);
+        let old_exec = old.exec.as_ref();
+        let new_exec = new.exec.as_ref();
+        self.prefixed_field(
+            &prefix,
+            "exec.on_press",
+            &old_exec.and_then(|exec| exec.on_press.as_ref()),
+            &new_exec.and_then(|exec| exec.on_press.as_ref()),
+        );
+        self.prefixed_field(
+            &prefix,
+            "exec.on_release",
+            &old_exec.and_then(|exec| exec.on_release.as_ref()),
+            &new_exec.and_then(|exec| exec.on_release.as_ref()),
+        );
+        self.prefixed_field(
+            &prefix,
+            "exec.on_hold",
+            &old_exec.and_then(|exec| exec.on_hold.as_ref()),
+            &new_exec.and_then(|exec| exec.on_hold.as_ref()),
+        );
+        self.prefixed_field(
+            &prefix,
+            "exec.concurrency",
+            &old_exec.map(|exec| exec.concurrency),
+            &new_exec.map(|exec| exec.concurrency),
+        );
+        self.prefixed_field(
+            &prefix,
+            "exec.timeout_ms",
+            &old_exec.and_then(|exec| exec.timeout_ms),
+            &new_exec.and_then(|exec| exec.timeout_ms),
+        );
+    }
+
+    /// Report the changes of a chord with the same GPIOs
+    fn chord(&mut self, old: &ChordConfig, new: &ChordConfig) {
+        let prefix = format!("chord GPIOs {:?}", new.gpios);
+        self.prefixed_field(
+            &prefix,
+            "keycode",
+            &old.keycode.to_string(),
+            &new.keycode.to_string(),
+        );
+        self.prefixed_field(&prefix, "device", &old.device, &new.device);
+        self.prefixed_field(&prefix, "window_ms", &old.window_ms, &new.window_ms);
+    }
+
+    /// Report the changes of a macro with the same name
+    fn macro_config(&mut self, old: &MacroConfig, new: &MacroConfig) {
+        let prefix = format!("macro {:?}", new.name);
+        self.prefixed_field(&prefix, "steps", &steps(old), &steps(new));
+    }
+
+    /// Report the changes of a power switch with the same GPIO
+    fn power_switch(&mut self, old: &PowerSwitchConfig, new: &PowerSwitchConfig) {
+        let prefix = format!("power switch GPIO {}", new.gpio);
+        self.prefixed_field(&prefix, "active_low", &old.active_low, &new.active_low);
+        self.prefixed_field(&prefix, "bias", &old.bias, &new.bias);
+        self.prefixed_field(&prefix, "pull", &old.pull, &new.pull);
+    }
+
+    /// Report a global setting change
+    fn field<T>(&mut self, name: &str, old: &T, new: &T)
+    where
+        T: Debug + PartialEq,
+    {
+        if old != new {
+            self.changes.push(format!("{name}: {old:?} -> {new:?}"));
+        }
+    }
+
+    /// Report a change of a setting of a key or power switch
+    fn prefixed_field<T>(&mut self, prefix: &str, name: &str, old: &T, new: &T)
+    where
+        T: Debug + PartialEq,
+    {
+        if old != new {
+            self.changes
+                .push(format!("{prefix} {name}: {old:?} -> {new:?}"));
+        }
+    }
+}
+
+/// Layer keycodes of `key`, written as strings so they can be compared
+fn layers(key: &KeyConfig) -> Vec<(&str, String)> {
+    key.layers
+        .iter()
+        .map(|(name, keycode)| (name.as_str(), keycode.to_string()))
+        .collect()
+}
+
+/// Steps of `macro_config`, written as strings so they can be compared
+fn steps(macro_config: &MacroConfig) -> Vec<String> {
+    macro_config.steps.iter().map(ToString::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_report_no_changes() {
+        let config: Config = toml::from_str(OLD_CONFIG).unwrap();
+        assert!(ConfigDiff::new(&config, &config).is_empty());
+    }
+
+    #[test]
+    fn test_should_report_changes() {
+        let old: Config = toml::from_str(OLD_CONFIG).unwrap();
+        let new: Config = toml::from_str(NEW_CONFIG).unwrap();
+
+        assert_eq!(
+            ConfigDiff::new(&old, &new).changes(),
+            &[
+                "poll_interval_ms: 5 -> 10",
+                "vendor: None -> Some(4617)",
+                "mouse.max_speed: 1500 -> 2000",
+                "device \"hotkeys\" removed",
+                "device \"player1\" vendor: None -> Some(4617)",
+                "device \"player2\" added",
+                "key GPIO 22 removed (KEY_UP)",
+                "key GPIO 17 keycode: Some(\"KEY_A\") -> Some(\"KEY_B\")",
+                "key GPIO 17 device: None -> Some(\"player2\")",
+                "key GPIO 17 debounce_ms: None -> Some(30)",
+                "key GPIO 17 exec.on_press: None -> Some(\"screenshot.sh\")",
+                "key GPIO 17 exec.concurrency: None -> Some(Ignore)",
+                "key GPIO 23 added (KEY_DOWN)",
+                "key GPIO 24 added (macro \"exit\")",
+                "chord GPIOs [17, 22] removed (KEY_ESC)",
+                "chord GPIOs [4, 17] window_ms: None -> Some(80)",
+                "chord GPIOs [17, 23] added (KEY_ENTER)",
+                "macro \"menu\" steps: [\"KEY_ESC\", \"KEY_ENTER\"] -> [\"KEY_ESC\", \"wait 100ms\", \"KEY_ENTER\"]",
+                "macro \"exit\" added",
+                "power switch GPIO 27 active_low: Some(false) -> None",
+            ]
+        );
+    }
+
+    const OLD_CONFIG: &str = r#"
+default_debounce_ms = 20
+default_active_low = true
+poll_interval_ms = 5
+
+[[device]]
+name = "player1"
+
+[[device]]
+name = "hotkeys"
+
+[[key]]
+gpio = 17
+keycode = "A"
+repeat = false
+
+[[key]]
+gpio = 22
+keycode = "UP"
+repeat = false
+
+[[chord]]
+gpios = [17, 22]
+keycode = "ESC"
+
+[[chord]]
+gpios = [4, 17]
+keycode = "F1"
+
+[[macro]]
+name = "menu"
+steps = ["ESC", "ENTER"]
+
+[[powerswitch]]
+gpio = 27
+active_low = false
+    "#;
+
+    const NEW_CONFIG: &str = r#"
+default_debounce_ms = 20
+default_active_low = true
+poll_interval_ms = 10
+vendor = 0x1209
+
+[mouse]
+max_speed = 2000
+
+[[device]]
+name = "player1"
+vendor = 0x1209
+
+[[device]]
+name = "player2"
+
+[[key]]
+gpio = 17
+keycode = "B"
+device = "player2"
+debounce_ms = 30
+repeat = false
+exec = { on_press = "screenshot.sh" }
+
+[[key]]
+gpio = 23
+keycode = "DOWN"
+repeat = false
+
+[[key]]
+gpio = 24
+macro = "exit"
+
+[[chord]]
+gpios = [4, 17]
+keycode = "F1"
+window_ms = 80
+
+[[chord]]
+gpios = [17, 23]
+keycode = "ENTER"
+
+[[macro]]
+name = "menu"
+steps = ["ESC", { delay_ms = 100 }, "ENTER"]
+
+[[macro]]
+name = "exit"
+steps = ["LALT+F4"]
+
+[[powerswitch]]
+gpio = 27
+    "#;
+}
@@ -0,0 +1,122 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, de};
+
+/// Identity reported by a virtual device, used by udev rules, hwdb entries and consumers to match it.
+///
+/// Unset fields use the defaults of the device type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    pub bustype: Option<BusType>,
+    pub vendor: Option<u16>,
+    pub product: Option<u16>,
+    pub version: Option<u16>,
+    /// Physical location of the device, e.g. `gpio2key/input0`
+    pub phys: Option<String>,
+}
+
+impl Identity {
+    /// Fill the unset fields with the ones of `defaults`
+    pub fn or(self, defaults: &Identity) -> Identity {
+        Identity {
+            bustype: self.bustype.or(defaults.bustype),
+            vendor: self.vendor.or(defaults.vendor),
+            product: self.product.or(defaults.product),
+            version: self.version.or(defaults.version),
+            phys: self.phys.or_else(|| defaults.phys.clone()),
+        }
+    }
+}
+
+/// Bus type of a virtual device, written as the name of a `BUS_*` constant, with or without prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusType(evdev::BusType);
+
+impl BusType {
+    pub fn bus_type(&self) -> evdev::BusType {
+        self.0
+    }
+}
+
+impl fmt::Display for BusType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = format!("{:?}", self.0);
+        write!(
+            f,
+            "{}",
+            name.strip_prefix("BUS_").unwrap_or(&name).to_lowercase()
+        )
+    }
+}
+
+impl FromStr for BusType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_uppercase();
+        let name = name.strip_prefix("BUS_").unwrap_or(&name);
+
+        evdev::BusType::from_str(&format!("BUS_{name}"))
+            .map(BusType)
+            .map_err(|_| format!("Unsupported bus type {s:?}"))
+    }
+}
+
+impl<'de> Deserialize<'de> for BusType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_bus_type() {
+        for name in ["usb", "USB", "BUS_USB", "bus_usb"] {
+            assert_eq!(
+                BusType::from_str(name).unwrap().bus_type(),
+                evdev::BusType::BUS_USB
+            );
+        }
+        assert_eq!(
+            BusType::from_str("bluetooth").unwrap().bus_type(),
+            evdev::BusType::BUS_BLUETOOTH
+        );
+        assert_eq!(BusType::from_str("virtual").unwrap().to_string(), "virtual");
+        assert!(BusType::from_str("floppy").is_err());
+    }
+
+    #[test]
+    fn test_should_fill_unset_fields() {
+        let identity = Identity {
+            vendor: Some(0x1209),
+            phys: Some("gpio2key/input0".to_string()),
+            ..Default::default()
+        };
+        let defaults = Identity {
+            bustype: Some(BusType(evdev::BusType::BUS_VIRTUAL)),
+            vendor: Some(0x1234),
+            phys: Some("defaults".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            identity.or(&defaults),
+            Identity {
+                bustype: Some(BusType(evdev::BusType::BUS_VIRTUAL)),
+                vendor: Some(0x1209),
+                product: None,
+                version: None,
+                phys: Some("gpio2key/input0".to_string()),
+            }
+        );
+    }
+}
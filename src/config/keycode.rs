@@ -1,126 +1,163 @@
 use std::fmt;
 use std::str::FromStr;
+use std::sync::LazyLock;
 
-/// Wrapper around [`evdev::KeyCode`] to facilitate deserialization
-#[derive(Debug, Clone, Copy)]
-pub struct Keycode(evdev::KeyCode);
+use evdev::KeyCode;
+
+/// Highest key code defined by the kernel (`KEY_MAX`)
+const KEY_MAX: u16 = 0x2ff;
+
+/// Maximum number of names suggested when a keycode is not found
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Friendly names, which take precedence over the evdev names
+const ALIASES: &[(&str, KeyCode)] = &[
+    ("ESCAPE", KeyCode::KEY_ESC),
+    ("LCTRL", KeyCode::KEY_LEFTCTRL),
+    ("RCTRL", KeyCode::KEY_RIGHTCTRL),
+    ("LSHIFT", KeyCode::KEY_LEFTSHIFT),
+    ("RSHIFT", KeyCode::KEY_RIGHTSHIFT),
+    ("LALT", KeyCode::KEY_LEFTALT),
+    ("RALT", KeyCode::KEY_RIGHTALT),
+    ("-", KeyCode::KEY_MINUS),
+    ("=", KeyCode::KEY_EQUAL),
+    ("[", KeyCode::KEY_LEFTBRACE),
+    ("]", KeyCode::KEY_RIGHTBRACE),
+    ("\\", KeyCode::KEY_BACKSLASH),
+    (";", KeyCode::KEY_SEMICOLON),
+    ("'", KeyCode::KEY_APOSTROPHE),
+    ("`", KeyCode::KEY_GRAVE),
+    (",", KeyCode::KEY_COMMA),
+    (".", KeyCode::KEY_DOT),
+    ("/", KeyCode::KEY_SLASH),
+    ("NUMPAD_0", KeyCode::KEY_KP0),
+    ("NUMPAD_1", KeyCode::KEY_KP1),
+    ("NUMPAD_2", KeyCode::KEY_KP2),
+    ("NUMPAD_3", KeyCode::KEY_KP3),
+    ("NUMPAD_4", KeyCode::KEY_KP4),
+    ("NUMPAD_5", KeyCode::KEY_KP5),
+    ("NUMPAD_6", KeyCode::KEY_KP6),
+    ("NUMPAD_7", KeyCode::KEY_KP7),
+    ("NUMPAD_8", KeyCode::KEY_KP8),
+    ("NUMPAD_9", KeyCode::KEY_KP9),
+    ("NUMPAD_ENTER", KeyCode::KEY_KPENTER),
+    ("NUMPAD_ADD", KeyCode::KEY_KPPLUS),
+    ("NUMPAD_SUBTRACT", KeyCode::KEY_KPMINUS),
+    ("NUMPAD_MULTIPLY", KeyCode::KEY_KPASTERISK),
+    ("NUMPAD_DIVIDE", KeyCode::KEY_KPSLASH),
+    // names from `input-event-codes.h` which evdev doesn't define
+    ("BTN_MISC", KeyCode::BTN_0),
+    ("BTN_MOUSE", KeyCode::BTN_LEFT),
+    ("BTN_JOYSTICK", KeyCode::BTN_TRIGGER),
+    ("BTN_GAMEPAD", KeyCode::BTN_SOUTH),
+    ("BTN_A", KeyCode::BTN_SOUTH),
+    ("BTN_B", KeyCode::BTN_EAST),
+    ("BTN_X", KeyCode::BTN_NORTH),
+    ("BTN_Y", KeyCode::BTN_WEST),
+    ("BTN_DIGI", KeyCode::BTN_TOOL_PEN),
+    ("BTN_WHEEL", KeyCode::BTN_GEAR_DOWN),
+];
+
+/// Every key name known by evdev, with its code, ordered by code
+static EVDEV_NAMES: LazyLock<Vec<(String, KeyCode)>> = LazyLock::new(|| {
+    (0..=KEY_MAX)
+        .map(KeyCode::new)
+        .filter_map(|code| evdev_name(code).map(|name| (name, code)))
+        .collect()
+});
+
+/// Wrapper around [`evdev::KeyCode`] to facilitate deserialization.
+///
+/// A keycode can be written as:
+///
+/// - one of the [`ALIASES`];
+/// - an evdev name, such as `KEY_VOLUMEUP` or `BTN_SOUTH`, optionally without the `KEY_`/`BTN_` prefix;
+/// - a raw code, either hexadecimal (`0x110`) or decimal with at least two digits (`272`),
+///   since single digits are the number keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keycode(KeyCode);
 
 impl Keycode {
     /// Get the underlying [`evdev::KeyCode`]
-    pub fn keycode(&self) -> evdev::KeyCode {
+    pub fn keycode(&self) -> KeyCode {
         self.0
     }
+
+    /// Get the [`Category`] of the key
+    pub fn category(&self) -> Category {
+        Category::of(self.0)
+    }
+
+    /// Whether the key is a keyboard key rather than a button: udev tags the devices emitting one
+    /// as keyboards
+    pub fn is_keyboard_key(&self) -> bool {
+        // everything but the buttons, which are `BTN_0..KEY_OK`, `BTN_DPAD_*` and above
+        (KeyCode::KEY_ESC..KeyCode::BTN_0).contains(&self.0)
+            || (KeyCode::KEY_OK..KeyCode::BTN_DPAD_UP).contains(&self.0)
+            || (KeyCode::BTN_DPAD_RIGHT < self.0 && self.0 < KeyCode::BTN_TRIGGER_HAPPY1)
+    }
+
+    /// Create a [`Keycode`] from a raw code, if it is in the valid range
+    fn from_code(code: u64) -> Result<Self, String> {
+        u16::try_from(code)
+            .ok()
+            .filter(|code| *code <= KEY_MAX)
+            .map(|code| Keycode(KeyCode::new(code)))
+            .ok_or_else(|| format!("Keycode {code} is out of range (max {KEY_MAX:#x})"))
+    }
+}
+
+impl From<KeyCode> for Keycode {
+    fn from(keycode: KeyCode) -> Self {
+        Keycode(keycode)
+    }
 }
 
 impl fmt::Display for Keycode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.0)
+        match evdev_name(self.0) {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{:#05x}", self.0.code()),
+        }
     }
 }
 
 impl FromStr for Keycode {
-    type Err = &'static str;
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_ascii_uppercase().as_str() {
-            "A" => Ok(Keycode(evdev::KeyCode::KEY_A)),
-            "B" => Ok(Keycode(evdev::KeyCode::KEY_B)),
-            "C" => Ok(Keycode(evdev::KeyCode::KEY_C)),
-            "D" => Ok(Keycode(evdev::KeyCode::KEY_D)),
-            "E" => Ok(Keycode(evdev::KeyCode::KEY_E)),
-            "F" => Ok(Keycode(evdev::KeyCode::KEY_F)),
-            "G" => Ok(Keycode(evdev::KeyCode::KEY_G)),
-            "H" => Ok(Keycode(evdev::KeyCode::KEY_H)),
-            "I" => Ok(Keycode(evdev::KeyCode::KEY_I)),
-            "J" => Ok(Keycode(evdev::KeyCode::KEY_J)),
-            "K" => Ok(Keycode(evdev::KeyCode::KEY_K)),
-            "L" => Ok(Keycode(evdev::KeyCode::KEY_L)),
-            "M" => Ok(Keycode(evdev::KeyCode::KEY_M)),
-            "N" => Ok(Keycode(evdev::KeyCode::KEY_N)),
-            "O" => Ok(Keycode(evdev::KeyCode::KEY_O)),
-            "P" => Ok(Keycode(evdev::KeyCode::KEY_P)),
-            "Q" => Ok(Keycode(evdev::KeyCode::KEY_Q)),
-            "R" => Ok(Keycode(evdev::KeyCode::KEY_R)),
-            "S" => Ok(Keycode(evdev::KeyCode::KEY_S)),
-            "T" => Ok(Keycode(evdev::KeyCode::KEY_T)),
-            "U" => Ok(Keycode(evdev::KeyCode::KEY_U)),
-            "V" => Ok(Keycode(evdev::KeyCode::KEY_V)),
-            "W" => Ok(Keycode(evdev::KeyCode::KEY_W)),
-            "X" => Ok(Keycode(evdev::KeyCode::KEY_X)),
-            "Y" => Ok(Keycode(evdev::KeyCode::KEY_Y)),
-            "Z" => Ok(Keycode(evdev::KeyCode::KEY_Z)),
-            "ENTER" => Ok(Keycode(evdev::KeyCode::KEY_ENTER)),
-            "SPACE" => Ok(Keycode(evdev::KeyCode::KEY_SPACE)),
-            "ESCAPE" => Ok(Keycode(evdev::KeyCode::KEY_ESC)),
-            "UP" => Ok(Keycode(evdev::KeyCode::KEY_UP)),
-            "DOWN" => Ok(Keycode(evdev::KeyCode::KEY_DOWN)),
-            "LEFT" => Ok(Keycode(evdev::KeyCode::KEY_LEFT)),
-            "RIGHT" => Ok(Keycode(evdev::KeyCode::KEY_RIGHT)),
-            "LCTRL" => Ok(Keycode(evdev::KeyCode::KEY_LEFTCTRL)),
-            "RCTRL" => Ok(Keycode(evdev::KeyCode::KEY_RIGHTCTRL)),
-            "LSHIFT" => Ok(Keycode(evdev::KeyCode::KEY_LEFTSHIFT)),
-            "RSHIFT" => Ok(Keycode(evdev::KeyCode::KEY_RIGHTSHIFT)),
-            "LALT" => Ok(Keycode(evdev::KeyCode::KEY_LEFTALT)),
-            "RALT" => Ok(Keycode(evdev::KeyCode::KEY_RIGHTALT)),
-            "TAB" => Ok(Keycode(evdev::KeyCode::KEY_TAB)),
-            "BACKSPACE" => Ok(Keycode(evdev::KeyCode::KEY_BACKSPACE)),
-            "CAPSLOCK" => Ok(Keycode(evdev::KeyCode::KEY_CAPSLOCK)),
-            "F1" => Ok(Keycode(evdev::KeyCode::KEY_F1)),
-            "F2" => Ok(Keycode(evdev::KeyCode::KEY_F2)),
-            "F3" => Ok(Keycode(evdev::KeyCode::KEY_F3)),
-            "F4" => Ok(Keycode(evdev::KeyCode::KEY_F4)),
-            "F5" => Ok(Keycode(evdev::KeyCode::KEY_F5)),
-            "F6" => Ok(Keycode(evdev::KeyCode::KEY_F6)),
-            "F7" => Ok(Keycode(evdev::KeyCode::KEY_F7)),
-            "F8" => Ok(Keycode(evdev::KeyCode::KEY_F8)),
-            "F9" => Ok(Keycode(evdev::KeyCode::KEY_F9)),
-            "F10" => Ok(Keycode(evdev::KeyCode::KEY_F10)),
-            "F11" => Ok(Keycode(evdev::KeyCode::KEY_F11)),
-            "F12" => Ok(Keycode(evdev::KeyCode::KEY_F12)),
-            "HOME" => Ok(Keycode(evdev::KeyCode::KEY_HOME)),
-            "END" => Ok(Keycode(evdev::KeyCode::KEY_END)),
-            "PAGEUP" => Ok(Keycode(evdev::KeyCode::KEY_PAGEUP)),
-            "PAGEDOWN" => Ok(Keycode(evdev::KeyCode::KEY_PAGEDOWN)),
-            "INSERT" => Ok(Keycode(evdev::KeyCode::KEY_INSERT)),
-            "DELETE" => Ok(Keycode(evdev::KeyCode::KEY_DELETE)),
-            "0" => Ok(Keycode(evdev::KeyCode::KEY_0)),
-            "1" => Ok(Keycode(evdev::KeyCode::KEY_1)),
-            "2" => Ok(Keycode(evdev::KeyCode::KEY_2)),
-            "3" => Ok(Keycode(evdev::KeyCode::KEY_3)),
-            "4" => Ok(Keycode(evdev::KeyCode::KEY_4)),
-            "5" => Ok(Keycode(evdev::KeyCode::KEY_5)),
-            "6" => Ok(Keycode(evdev::KeyCode::KEY_6)),
-            "7" => Ok(Keycode(evdev::KeyCode::KEY_7)),
-            "8" => Ok(Keycode(evdev::KeyCode::KEY_8)),
-            "9" => Ok(Keycode(evdev::KeyCode::KEY_9)),
-            "-" => Ok(Keycode(evdev::KeyCode::KEY_MINUS)),
-            "=" => Ok(Keycode(evdev::KeyCode::KEY_EQUAL)),
-            "[" => Ok(Keycode(evdev::KeyCode::KEY_LEFTBRACE)),
-            "]" => Ok(Keycode(evdev::KeyCode::KEY_RIGHTBRACE)),
-            "\\" => Ok(Keycode(evdev::KeyCode::KEY_BACKSLASH)),
-            ";" => Ok(Keycode(evdev::KeyCode::KEY_SEMICOLON)),
-            "'" => Ok(Keycode(evdev::KeyCode::KEY_APOSTROPHE)),
-            "`" => Ok(Keycode(evdev::KeyCode::KEY_GRAVE)),
-            "," => Ok(Keycode(evdev::KeyCode::KEY_COMMA)),
-            "." => Ok(Keycode(evdev::KeyCode::KEY_DOT)),
-            "/" => Ok(Keycode(evdev::KeyCode::KEY_SLASH)),
-            "NUMPAD_0" => Ok(Keycode(evdev::KeyCode::KEY_KP0)),
-            "NUMPAD_1" => Ok(Keycode(evdev::KeyCode::KEY_KP1)),
-            "NUMPAD_2" => Ok(Keycode(evdev::KeyCode::KEY_KP2)),
-            "NUMPAD_3" => Ok(Keycode(evdev::KeyCode::KEY_KP3)),
-            "NUMPAD_4" => Ok(Keycode(evdev::KeyCode::KEY_KP4)),
-            "NUMPAD_5" => Ok(Keycode(evdev::KeyCode::KEY_KP5)),
-            "NUMPAD_6" => Ok(Keycode(evdev::KeyCode::KEY_KP6)),
-            "NUMPAD_7" => Ok(Keycode(evdev::KeyCode::KEY_KP7)),
-            "NUMPAD_8" => Ok(Keycode(evdev::KeyCode::KEY_KP8)),
-            "NUMPAD_9" => Ok(Keycode(evdev::KeyCode::KEY_KP9)),
-            "NUMPAD_ENTER" => Ok(Keycode(evdev::KeyCode::KEY_KPENTER)),
-            "NUMPAD_ADD" => Ok(Keycode(evdev::KeyCode::KEY_KPPLUS)),
-            "NUMPAD_SUBTRACT" => Ok(Keycode(evdev::KeyCode::KEY_KPMINUS)),
-            "NUMPAD_MULTIPLY" => Ok(Keycode(evdev::KeyCode::KEY_KPASTERISK)),
-            "NUMPAD_DIVIDE" => Ok(Keycode(evdev::KeyCode::KEY_KPSLASH)),
-            _ => Err("Unsupported keycode"),
+        let name = s.trim().to_ascii_uppercase();
+
+        if let Some(hex) = name.strip_prefix("0X") {
+            let code =
+                u64::from_str_radix(hex, 16).map_err(|e| format!("Invalid keycode {s:?}: {e}"))?;
+            return Self::from_code(code);
+        }
+        if name.len() > 1 && name.bytes().all(|b| b.is_ascii_digit()) {
+            let code = name
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid keycode {s:?}: {e}"))?;
+            return Self::from_code(code);
         }
+
+        if let Some((_, code)) = ALIASES.iter().find(|(alias, _)| *alias == name) {
+            return Ok(Keycode(*code));
+        }
+        [name.clone(), format!("KEY_{name}"), format!("BTN_{name}")]
+            .iter()
+            .find_map(|name| KeyCode::from_str(name).ok())
+            .map(Keycode)
+            .ok_or_else(|| {
+                let suggestions = suggestions(&name);
+                if suggestions.is_empty() {
+                    format!("Unsupported keycode {s:?}")
+                } else {
+                    format!(
+                        "Unsupported keycode {s:?}; did you mean {}?",
+                        suggestions.join(", ")
+                    )
+                }
+            })
     }
 }
 
@@ -129,7 +166,407 @@ impl<'de> serde::Deserialize<'de> for Keycode {
     where
         D: serde::Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        Keycode::from_str(&s).map_err(serde::de::Error::custom)
+        struct KeycodeVisitor;
+
+        impl serde::de::Visitor<'_> for KeycodeVisitor {
+            type Value = Keycode;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a key name or a raw key code")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Keycode::from_str(v).map_err(E::custom)
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Keycode::from_code(v).map_err(E::custom)
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                u64::try_from(v)
+                    .map_err(|_| E::custom(format!("Keycode {v} is out of range")))
+                    .and_then(|v| self.visit_u64(v))
+            }
+        }
+
+        deserializer.deserialize_any(KeycodeVisitor)
+    }
+}
+
+/// Kind of key, used to group the key names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Letters,
+    Numbers,
+    Function,
+    Modifiers,
+    Navigation,
+    Punctuation,
+    Numpad,
+    Media,
+    Mouse,
+    Gamepad,
+    Other,
+}
+
+impl Category {
+    fn of(code: KeyCode) -> Self {
+        let within = |first: KeyCode, last: KeyCode| (first..=last).contains(&code);
+
+        match code {
+            _ if within(KeyCode::KEY_Q, KeyCode::KEY_P)
+                || within(KeyCode::KEY_A, KeyCode::KEY_L)
+                || within(KeyCode::KEY_Z, KeyCode::KEY_M) =>
+            {
+                Self::Letters
+            }
+            _ if within(KeyCode::KEY_1, KeyCode::KEY_0) => Self::Numbers,
+            KeyCode::KEY_F11 | KeyCode::KEY_F12 => Self::Function,
+            _ if within(KeyCode::KEY_F1, KeyCode::KEY_F10)
+                || within(KeyCode::KEY_F13, KeyCode::KEY_F24) =>
+            {
+                Self::Function
+            }
+            KeyCode::KEY_LEFTCTRL
+            | KeyCode::KEY_RIGHTCTRL
+            | KeyCode::KEY_LEFTSHIFT
+            | KeyCode::KEY_RIGHTSHIFT
+            | KeyCode::KEY_LEFTALT
+            | KeyCode::KEY_RIGHTALT
+            | KeyCode::KEY_LEFTMETA
+            | KeyCode::KEY_RIGHTMETA
+            | KeyCode::KEY_CAPSLOCK
+            | KeyCode::KEY_NUMLOCK
+            | KeyCode::KEY_SCROLLLOCK
+            | KeyCode::KEY_FN => Self::Modifiers,
+            KeyCode::KEY_ESC
+            | KeyCode::KEY_BACKSPACE
+            | KeyCode::KEY_TAB
+            | KeyCode::KEY_ENTER
+            | KeyCode::KEY_SPACE => Self::Navigation,
+            _ if within(KeyCode::KEY_HOME, KeyCode::KEY_DELETE) => Self::Navigation,
+            KeyCode::KEY_MINUS
+            | KeyCode::KEY_EQUAL
+            | KeyCode::KEY_LEFTBRACE
+            | KeyCode::KEY_RIGHTBRACE
+            | KeyCode::KEY_SEMICOLON
+            | KeyCode::KEY_APOSTROPHE
+            | KeyCode::KEY_GRAVE
+            | KeyCode::KEY_BACKSLASH
+            | KeyCode::KEY_COMMA
+            | KeyCode::KEY_DOT
+            | KeyCode::KEY_SLASH
+            | KeyCode::KEY_102ND => Self::Punctuation,
+            KeyCode::KEY_KPASTERISK
+            | KeyCode::KEY_KPENTER
+            | KeyCode::KEY_KPSLASH
+            | KeyCode::KEY_KPEQUAL
+            | KeyCode::KEY_KPPLUSMINUS
+            | KeyCode::KEY_KPCOMMA
+            | KeyCode::KEY_KPLEFTPAREN
+            | KeyCode::KEY_KPRIGHTPAREN => Self::Numpad,
+            _ if within(KeyCode::KEY_KP7, KeyCode::KEY_KPDOT) => Self::Numpad,
+            KeyCode::KEY_MUTE
+            | KeyCode::KEY_VOLUMEDOWN
+            | KeyCode::KEY_VOLUMEUP
+            | KeyCode::KEY_PLAYCD
+            | KeyCode::KEY_PAUSECD
+            | KeyCode::KEY_PLAY
+            | KeyCode::KEY_FASTFORWARD
+            | KeyCode::KEY_MEDIA
+            | KeyCode::KEY_MICMUTE
+            | KeyCode::KEY_MEDIA_REPEAT
+            | KeyCode::KEY_PAUSE_RECORD => Self::Media,
+            _ if within(KeyCode::KEY_EJECTCD, KeyCode::KEY_REWIND) => Self::Media,
+            _ if within(KeyCode::BTN_LEFT, KeyCode::BTN_TASK) => Self::Mouse,
+            _ if within(KeyCode::BTN_TRIGGER, KeyCode::BTN_THUMBR)
+                || within(KeyCode::BTN_DPAD_UP, KeyCode::BTN_DPAD_RIGHT)
+                || within(KeyCode::BTN_TRIGGER_HAPPY1, KeyCode::BTN_TRIGGER_HAPPY40) =>
+            {
+                Self::Gamepad
+            }
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Letters => "letters",
+            Self::Numbers => "numbers",
+            Self::Function => "function",
+            Self::Modifiers => "modifiers",
+            Self::Navigation => "navigation",
+            Self::Punctuation => "punctuation",
+            Self::Numpad => "numpad",
+            Self::Media => "media",
+            Self::Mouse => "mouse",
+            Self::Gamepad => "gamepad",
+            Self::Other => "other",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Get every name accepted by [`Keycode::from_str`], except raw codes, with the key it resolves to.
+///
+/// Names are ordered by code; for each code the evdev name comes first,
+/// followed by its unprefixed form, if it resolves to the same key, and by the aliases.
+pub fn names() -> Vec<(String, Keycode)> {
+    let mut names = Vec::new();
+    for (evdev_name, code) in EVDEV_NAMES.iter() {
+        names.push((evdev_name.clone(), Keycode(*code)));
+        let short = &evdev_name[4..];
+        if Keycode::from_str(short).ok() == Some(Keycode(*code)) {
+            names.push((short.to_string(), Keycode(*code)));
+        }
+        names.extend(
+            ALIASES
+                .iter()
+                .filter(|(_, alias_code)| alias_code == code)
+                .map(|(alias, _)| (alias.to_string(), Keycode(*code))),
+        );
+    }
+
+    names
+}
+
+/// Name of `code` as defined by evdev, if any
+fn evdev_name(code: KeyCode) -> Option<String> {
+    let name = format!("{code:?}");
+    (name.starts_with("KEY_") || name.starts_with("BTN_")).then_some(name)
+}
+
+/// Get the valid names closest to `name`, best first
+fn suggestions(name: &str) -> Vec<String> {
+    let max_distance = (name.len() / 3).max(2);
+    let mut candidates = ALIASES
+        .iter()
+        .map(|(alias, _)| (alias.to_string(), levenshtein(name, alias)))
+        .chain(EVDEV_NAMES.iter().map(|(evdev_name, _)| {
+            let short = &evdev_name[4..];
+            let distance = levenshtein(name, evdev_name).min(levenshtein(name, short));
+            (evdev_name.clone(), distance)
+        }))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .collect::<Vec<_>>();
+    // stable sort keeps the alias and code order between equally distant names
+    candidates.sort_by_key(|(_, distance)| *distance);
+
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Edit distance between `a` and `b`
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_aliases() {
+        for (name, code) in [
+            ("a", KeyCode::KEY_A),
+            ("1", KeyCode::KEY_1),
+            ("Escape", KeyCode::KEY_ESC),
+            ("LCTRL", KeyCode::KEY_LEFTCTRL),
+            ("/", KeyCode::KEY_SLASH),
+            ("NUMPAD_ENTER", KeyCode::KEY_KPENTER),
+            ("BTN_A", KeyCode::BTN_SOUTH),
+        ] {
+            assert_eq!(Keycode::from_str(name).unwrap().keycode(), code, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_should_parse_evdev_names() {
+        for (name, code) in [
+            ("KEY_VOLUMEUP", KeyCode::KEY_VOLUMEUP),
+            ("volumeup", KeyCode::KEY_VOLUMEUP),
+            ("KEY_POWER", KeyCode::KEY_POWER),
+            ("BTN_SOUTH", KeyCode::BTN_SOUTH),
+            ("south", KeyCode::BTN_SOUTH),
+            ("BTN_START", KeyCode::BTN_START),
+            ("playpause", KeyCode::KEY_PLAYPAUSE),
+            ("KEY_FULL_SCREEN", KeyCode::KEY_ZOOM),
+            // KEY_ prefix wins over BTN_
+            ("SELECT", KeyCode::KEY_SELECT),
+        ] {
+            assert_eq!(Keycode::from_str(name).unwrap().keycode(), code, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_should_parse_every_evdev_name() {
+        assert!(EVDEV_NAMES.len() > 500);
+        for (name, code) in EVDEV_NAMES.iter() {
+            assert_eq!(Keycode::from_str(name).unwrap().keycode(), *code, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_should_parse_raw_codes() {
+        assert_eq!(
+            Keycode::from_str("0x110").unwrap().keycode(),
+            KeyCode::BTN_LEFT
+        );
+        assert_eq!(
+            Keycode::from_str("272").unwrap().keycode(),
+            KeyCode::BTN_LEFT
+        );
+        assert!(Keycode::from_str("0x300").is_err());
+        assert!(Keycode::from_str("0xZZ").is_err());
+        assert!(Keycode::from_str("70000").is_err());
+    }
+
+    #[test]
+    fn test_should_deserialize_keycode() {
+        #[derive(serde::Deserialize)]
+        struct Key {
+            keycode: Keycode,
+        }
+
+        let key: Key = toml::from_str("keycode = \"KEY_MUTE\"").unwrap();
+        assert_eq!(key.keycode.keycode(), KeyCode::KEY_MUTE);
+        let key: Key = toml::from_str("keycode = 272").unwrap();
+        assert_eq!(key.keycode.keycode(), KeyCode::BTN_LEFT);
+        let key: Key = toml::from_str("keycode = 0x110").unwrap();
+        assert_eq!(key.keycode.keycode(), KeyCode::BTN_LEFT);
+        assert!(toml::from_str::<Key>("keycode = -1").is_err());
+        assert!(toml::from_str::<Key>("keycode = 1024").is_err());
+    }
+
+    #[test]
+    fn test_should_suggest_closest_names() {
+        let err = Keycode::from_str("VOLUMUP").unwrap_err();
+        assert!(
+            err.starts_with("Unsupported keycode \"VOLUMUP\"; did you mean KEY_VOLUMEUP"),
+            "{err}"
+        );
+
+        let err = Keycode::from_str("LCTLR").unwrap_err();
+        assert!(err.contains("LCTRL"), "{err}");
+
+        let err = Keycode::from_str("THIS_IS_NOT_A_KEY_AT_ALL").unwrap_err();
+        assert_eq!(err, "Unsupported keycode \"THIS_IS_NOT_A_KEY_AT_ALL\"");
+    }
+
+    #[test]
+    fn test_should_display_keycode() {
+        assert_eq!(Keycode(KeyCode::KEY_A).to_string(), "KEY_A");
+        assert_eq!(Keycode(KeyCode::BTN_SOUTH).to_string(), "BTN_SOUTH");
+        assert_eq!(Keycode(KeyCode::new(0x2ff)).to_string(), "0x2ff");
+    }
+
+    #[test]
+    fn test_should_list_every_name() {
+        let names = names();
+        for (name, keycode) in &names {
+            assert_eq!(Keycode::from_str(name).as_ref(), Ok(keycode), "{name}");
+        }
+        for (alias, code) in ALIASES {
+            assert!(
+                names.contains(&(alias.to_string(), Keycode(*code))),
+                "{alias}"
+            );
+        }
+
+        let escape = names
+            .iter()
+            .filter(|(_, keycode)| keycode.keycode() == KeyCode::KEY_ESC)
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(escape, vec!["KEY_ESC", "ESC", "ESCAPE"]);
+
+        // SELECT resolves to KEY_SELECT
+        assert!(names.contains(&("BTN_SELECT".to_string(), Keycode(KeyCode::BTN_SELECT))));
+        assert!(!names.contains(&("SELECT".to_string(), Keycode(KeyCode::BTN_SELECT))));
+    }
+
+    #[test]
+    fn test_should_categorize_keys() {
+        for (code, category) in [
+            (KeyCode::KEY_A, Category::Letters),
+            (KeyCode::KEY_M, Category::Letters),
+            (KeyCode::KEY_0, Category::Numbers),
+            (KeyCode::KEY_F12, Category::Function),
+            (KeyCode::KEY_LEFTCTRL, Category::Modifiers),
+            (KeyCode::KEY_UP, Category::Navigation),
+            (KeyCode::KEY_ESC, Category::Navigation),
+            (KeyCode::KEY_SLASH, Category::Punctuation),
+            (KeyCode::KEY_KPPLUS, Category::Numpad),
+            (KeyCode::KEY_VOLUMEUP, Category::Media),
+            (KeyCode::KEY_PLAYPAUSE, Category::Media),
+            (KeyCode::BTN_LEFT, Category::Mouse),
+            (KeyCode::BTN_SOUTH, Category::Gamepad),
+            (KeyCode::BTN_DPAD_UP, Category::Gamepad),
+            (KeyCode::KEY_POWER, Category::Other),
+        ] {
+            assert_eq!(Keycode(code).category(), category, "{code:?}");
+        }
+        assert_eq!(Category::Gamepad.to_string(), "gamepad");
+    }
+
+    #[test]
+    fn test_should_tell_keyboard_keys() {
+        for code in [
+            KeyCode::KEY_ESC,
+            KeyCode::KEY_A,
+            KeyCode::KEY_UP,
+            KeyCode::KEY_VOLUMEUP,
+            KeyCode::KEY_OK,
+            KeyCode::KEY_ALS_TOGGLE,
+        ] {
+            assert!(Keycode(code).is_keyboard_key(), "{code:?}");
+        }
+        for code in [
+            KeyCode::KEY_RESERVED,
+            KeyCode::BTN_LEFT,
+            KeyCode::BTN_SOUTH,
+            KeyCode::BTN_THUMBR,
+            KeyCode::BTN_DPAD_UP,
+            KeyCode::BTN_DPAD_RIGHT,
+            KeyCode::BTN_TRIGGER_HAPPY1,
+        ] {
+            assert!(!Keycode(code).is_keyboard_key(), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn test_should_compute_levenshtein_distance() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("abc", ""), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("LCTLR", "LCTRL"), 2);
     }
 }
@@ -0,0 +1,354 @@
+use evdev::KeyCode;
+use serde::Deserialize;
+
+use crate::config::{KeyCombo, Keycode};
+
+/// Keys typing characters, by row, from the number row down to the bottom letter row.
+///
+/// The key at the end of the home row is the one left of Enter on ISO keyboards, and the first one of
+/// the bottom row is the one left of Z, which is missing on ANSI keyboards.
+const ROWS: [&[KeyCode]; 4] = [
+    &[
+        KeyCode::KEY_GRAVE,
+        KeyCode::KEY_1,
+        KeyCode::KEY_2,
+        KeyCode::KEY_3,
+        KeyCode::KEY_4,
+        KeyCode::KEY_5,
+        KeyCode::KEY_6,
+        KeyCode::KEY_7,
+        KeyCode::KEY_8,
+        KeyCode::KEY_9,
+        KeyCode::KEY_0,
+        KeyCode::KEY_MINUS,
+        KeyCode::KEY_EQUAL,
+    ],
+    &[
+        KeyCode::KEY_Q,
+        KeyCode::KEY_W,
+        KeyCode::KEY_E,
+        KeyCode::KEY_R,
+        KeyCode::KEY_T,
+        KeyCode::KEY_Y,
+        KeyCode::KEY_U,
+        KeyCode::KEY_I,
+        KeyCode::KEY_O,
+        KeyCode::KEY_P,
+        KeyCode::KEY_LEFTBRACE,
+        KeyCode::KEY_RIGHTBRACE,
+    ],
+    &[
+        KeyCode::KEY_A,
+        KeyCode::KEY_S,
+        KeyCode::KEY_D,
+        KeyCode::KEY_F,
+        KeyCode::KEY_G,
+        KeyCode::KEY_H,
+        KeyCode::KEY_J,
+        KeyCode::KEY_K,
+        KeyCode::KEY_L,
+        KeyCode::KEY_SEMICOLON,
+        KeyCode::KEY_APOSTROPHE,
+        KeyCode::KEY_BACKSLASH,
+    ],
+    &[
+        KeyCode::KEY_102ND,
+        KeyCode::KEY_Z,
+        KeyCode::KEY_X,
+        KeyCode::KEY_C,
+        KeyCode::KEY_V,
+        KeyCode::KEY_B,
+        KeyCode::KEY_N,
+        KeyCode::KEY_M,
+        KeyCode::KEY_COMMA,
+        KeyCode::KEY_DOT,
+        KeyCode::KEY_SLASH,
+    ],
+];
+
+/// Characters typed by the keys of [`ROWS`] with a set of modifiers, one string per row.
+///
+/// A space stands for a key typing nothing, or a dead key; the space itself is typed by the space bar.
+type Level = [&'static str; 4];
+
+/// Characters typed by the keys of a layout without modifiers, with Shift and with AltGr
+struct Levels {
+    base: Level,
+    shift: Level,
+    altgr: Level,
+}
+
+const NO_CHARACTERS: Level = [
+    "             ",
+    "            ",
+    "            ",
+    "           ",
+];
+
+const US: Levels = Levels {
+    base: [
+        "`1234567890-=",
+        "qwertyuiop[]",
+        "asdfghjkl;'\\",
+        " zxcvbnm,./",
+    ],
+    shift: [
+        "~!@#$%^&*()_+",
+        "QWERTYUIOP{}",
+        "ASDFGHJKL:\"|",
+        " ZXCVBNM<>?",
+    ],
+    altgr: NO_CHARACTERS,
+};
+
+const UK: Levels = Levels {
+    base: [
+        "`1234567890-=",
+        "qwertyuiop[]",
+        "asdfghjkl;'#",
+        "\\zxcvbnm,./",
+    ],
+    shift: [
+        "¬!\"£$%^&*()_+",
+        "QWERTYUIOP{}",
+        "ASDFGHJKL:@~",
+        "|ZXCVBNM<>?",
+    ],
+    altgr: [
+        "    €        ",
+        "            ",
+        "            ",
+        "           ",
+    ],
+};
+
+const DE: Levels = Levels {
+    base: [
+        " 1234567890ß ",
+        "qwertzuiopü+",
+        "asdfghjklöä#",
+        "<yxcvbnm,.-",
+    ],
+    shift: [
+        "°!\"§$%&/()=? ",
+        "QWERTZUIOPÜ*",
+        "ASDFGHJKLÖÄ'",
+        ">YXCVBNM;:_",
+    ],
+    altgr: [
+        "  ²³   {[]}\\ ",
+        "@ €        ~",
+        "            ",
+        "|      µ   ",
+    ],
+};
+
+const FR: Levels = Levels {
+    base: [
+        "²&é\"'(-è_çà)=",
+        "azertyuiop $",
+        "qsdfghjklmù*",
+        "<wxcvbn,;:!",
+    ],
+    shift: [
+        " 1234567890°+",
+        "AZERTYUIOP £",
+        "QSDFGHJKLM%µ",
+        ">WXCVBN?./§",
+    ],
+    altgr: [
+        "   #{[| \\ @]}",
+        "  €         ",
+        "            ",
+        "           ",
+    ],
+};
+
+const IT: Levels = Levels {
+    base: [
+        "\\1234567890'ì",
+        "qwertyuiopè+",
+        "asdfghjklòàù",
+        "<zxcvbnm,.-",
+    ],
+    shift: [
+        "|!\"£$%&/()=?^",
+        "QWERTYUIOPé*",
+        "ASDFGHJKLç°§",
+        ">ZXCVBNM;:_",
+    ],
+    altgr: [
+        "             ",
+        "  €       []",
+        "         @# ",
+        "           ",
+    ],
+};
+
+const ES: Levels = Levels {
+    base: [
+        "º1234567890'¡",
+        "qwertyuiop +",
+        "asdfghjklñ ç",
+        "<zxcvbnm,.-",
+    ],
+    shift: [
+        "ª!\"·$%&/()=?¿",
+        "QWERTYUIOP *",
+        "ASDFGHJKLÑ Ç",
+        ">ZXCVBNM;:_",
+    ],
+    altgr: [
+        "\\|@#         ",
+        "  €       []",
+        "          {}",
+        "           ",
+    ],
+};
+
+/// Keyboard layout the host uses to translate the keys back into characters.
+///
+/// Text is typed by pressing the keys producing its characters with this layout.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyboardLayout {
+    /// US English (QWERTY)
+    #[default]
+    Us,
+    /// UK English (QWERTY)
+    Uk,
+    /// German (QWERTZ)
+    De,
+    /// French (AZERTY)
+    Fr,
+    /// Italian (QWERTY)
+    It,
+    /// Spanish (QWERTY)
+    Es,
+}
+
+impl KeyboardLayout {
+    /// Keys to press together to type `c`, with the modifiers first, if `c` can be typed
+    pub fn keys(self, c: char) -> Option<KeyCombo> {
+        match c {
+            ' ' => return Some(key(KeyCode::KEY_SPACE)),
+            '\n' => return Some(key(KeyCode::KEY_ENTER)),
+            '\t' => return Some(key(KeyCode::KEY_TAB)),
+            _ => {}
+        }
+        let levels = self.levels();
+        [
+            (levels.base, None),
+            (levels.shift, Some(KeyCode::KEY_LEFTSHIFT)),
+            (levels.altgr, Some(KeyCode::KEY_RIGHTALT)),
+        ]
+        .into_iter()
+        .find_map(|(level, modifier)| {
+            let code = find(&level, c)?;
+            let keycodes = modifier
+                .into_iter()
+                .chain([code])
+                .map(Keycode::from)
+                .collect();
+            KeyCombo::new(keycodes).ok()
+        })
+    }
+
+    /// Keys to press to type `text`, one combination per character.
+    ///
+    /// Returns the first character which can't be typed with this layout, if any.
+    pub fn type_text(self, text: &str) -> Result<Vec<KeyCombo>, char> {
+        text.chars().map(|c| self.keys(c).ok_or(c)).collect()
+    }
+
+    fn levels(self) -> &'static Levels {
+        match self {
+            Self::Us => &US,
+            Self::Uk => &UK,
+            Self::De => &DE,
+            Self::Fr => &FR,
+            Self::It => &IT,
+            Self::Es => &ES,
+        }
+    }
+}
+
+/// Key typing `c` in `level`, if any
+fn find(level: &Level, c: char) -> Option<KeyCode> {
+    ROWS.iter()
+        .zip(level)
+        .find_map(|(keys, chars)| chars.chars().position(|ch| ch == c).map(|pos| keys[pos]))
+}
+
+fn key(code: KeyCode) -> KeyCombo {
+    KeyCombo::from(Keycode::from(code))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_have_a_character_for_every_key() {
+        for layout in [
+            KeyboardLayout::Us,
+            KeyboardLayout::Uk,
+            KeyboardLayout::De,
+            KeyboardLayout::Fr,
+            KeyboardLayout::It,
+            KeyboardLayout::Es,
+        ] {
+            let levels = layout.levels();
+            for level in [levels.base, levels.shift, levels.altgr] {
+                for (keys, chars) in ROWS.iter().zip(level) {
+                    assert_eq!(chars.chars().count(), keys.len(), "{layout:?} {chars:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_should_type_characters() {
+        assert_eq!(keys(KeyboardLayout::Us, 'a'), "KEY_A");
+        assert_eq!(keys(KeyboardLayout::Us, 'A'), "KEY_LEFTSHIFT+KEY_A");
+        assert_eq!(keys(KeyboardLayout::Us, '@'), "KEY_LEFTSHIFT+KEY_2");
+        assert_eq!(keys(KeyboardLayout::Us, ' '), "KEY_SPACE");
+        assert_eq!(keys(KeyboardLayout::Us, '\n'), "KEY_ENTER");
+        assert_eq!(
+            keys(KeyboardLayout::Uk, '@'),
+            "KEY_LEFTSHIFT+KEY_APOSTROPHE"
+        );
+        assert_eq!(keys(KeyboardLayout::Uk, '€'), "KEY_RIGHTALT+KEY_4");
+        assert_eq!(keys(KeyboardLayout::De, 'z'), "KEY_Y");
+        assert_eq!(keys(KeyboardLayout::De, '@'), "KEY_RIGHTALT+KEY_Q");
+        assert_eq!(keys(KeyboardLayout::De, 'ß'), "KEY_MINUS");
+        assert_eq!(keys(KeyboardLayout::Fr, 'a'), "KEY_Q");
+        assert_eq!(keys(KeyboardLayout::Fr, '1'), "KEY_LEFTSHIFT+KEY_1");
+        assert_eq!(keys(KeyboardLayout::Fr, 'm'), "KEY_SEMICOLON");
+        assert_eq!(keys(KeyboardLayout::It, '@'), "KEY_RIGHTALT+KEY_SEMICOLON");
+        assert_eq!(keys(KeyboardLayout::Es, 'ñ'), "KEY_SEMICOLON");
+        assert_eq!(keys(KeyboardLayout::Es, '@'), "KEY_RIGHTALT+KEY_2");
+    }
+
+    #[test]
+    fn test_should_reject_characters_which_cant_be_typed() {
+        assert_eq!(KeyboardLayout::Us.keys('é'), None);
+        // dead keys
+        assert_eq!(KeyboardLayout::De.keys('^'), None);
+        assert_eq!(KeyboardLayout::Us.type_text("naïve"), Err('ï'));
+        assert_eq!(
+            KeyboardLayout::Us
+                .type_text("ok")
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["KEY_O", "KEY_K"]
+        );
+    }
+
+    fn keys(layout: KeyboardLayout, c: char) -> String {
+        layout.keys(c).unwrap().to_string()
+    }
+}
@@ -0,0 +1,413 @@
+use crate::config::{
+    ChordConfig, Config, DEFAULT_REPEAT_DELAY, DEFAULT_REPEAT_RATE, DeviceConfig, KeyConfig,
+    KeyMode, MAX_DEVICE_NAME_LEN, MAX_TURBO_HZ, MacroConfig, Output, PowerSwitchConfig,
+};
+
+/// A rule broken by a configuration: the field it's about, and the problem.
+///
+/// Nested fields are written with a dot, such as `mouse.speed`.
+pub type Violation = (&'static str, String);
+
+/// Rules broken by the global settings
+pub fn global_violations(config: &Config) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    if config.poll_interval_ms == 0 {
+        violations.push((
+            "poll_interval_ms",
+            "`poll_interval_ms` must be greater than 0".to_string(),
+        ));
+    }
+    if config
+        .phys
+        .as_deref()
+        .is_some_and(|phys| phys.contains('\0'))
+    {
+        violations.push(("phys", "`phys` contains a NUL character".to_string()));
+    }
+    let mouse = &config.mouse;
+    if mouse.speed == 0 {
+        violations.push((
+            "mouse.speed",
+            "`mouse.speed` must be greater than 0".to_string(),
+        ));
+    }
+    if mouse.max_speed < mouse.speed {
+        violations.push((
+            "mouse.max_speed",
+            "`mouse.max_speed` must be at least `mouse.speed`".to_string(),
+        ));
+    }
+    if !(mouse.acceleration_curve.is_finite() && mouse.acceleration_curve > 0.0) {
+        violations.push((
+            "mouse.acceleration_curve",
+            "`mouse.acceleration_curve` must be a positive number".to_string(),
+        ));
+    }
+
+    violations
+}
+
+/// Rules broken by `device`, regardless of the other devices
+pub fn device_violations(device: &DeviceConfig) -> Vec<Violation> {
+    let name = &device.name;
+    let mut violations = Vec::new();
+    if name.len() > MAX_DEVICE_NAME_LEN {
+        violations.push((
+            "name",
+            format!("device name {name:?} is longer than {MAX_DEVICE_NAME_LEN} bytes"),
+        ));
+    }
+    if device
+        .phys
+        .as_deref()
+        .is_some_and(|phys| phys.contains('\0'))
+    {
+        violations.push((
+            "phys",
+            format!("device {name:?}: `phys` contains a NUL character"),
+        ));
+    }
+
+    violations
+}
+
+/// Rules broken by `key` with the global settings and devices of `config`, regardless of the
+/// other keys
+pub fn key_violations(config: &Config, key: &KeyConfig) -> Vec<Violation> {
+    let gpio = key.gpio;
+    let mut violations = Vec::new();
+
+    // macro, text and exec keys emit nothing else
+    let actions = [
+        ("macro", key.macro_name.is_some()),
+        ("text", key.text.is_some()),
+        ("exec", key.exec.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(action, set)| set.then_some(action))
+    .collect::<Vec<_>>();
+    let action = actions.first().copied();
+    if key.keycode.is_none() && action.is_none() {
+        violations.push((
+            "keycode",
+            format!("key GPIO {gpio}: `keycode` is required without `macro`, `text` or `exec`"),
+        ));
+    }
+    for other in actions.iter().skip(1) {
+        violations.push((
+            other,
+            format!(
+                "key GPIO {gpio}: `{other}` can't be used with `{}`",
+                actions[0]
+            ),
+        ));
+    }
+    if let Some(exec) = &key.exec {
+        if exec.on_press.is_none() && exec.on_release.is_none() && exec.on_hold.is_none() {
+            violations.push((
+                "exec",
+                format!("key GPIO {gpio}: `exec` requires `on_press`, `on_release` or `on_hold`"),
+            ));
+        }
+        if exec.timeout_ms == Some(0) {
+            violations.push((
+                "exec",
+                format!("key GPIO {gpio}: `exec.timeout_ms` must be greater than 0"),
+            ));
+        }
+    }
+    if let Some(action) = action {
+        violations.extend(conflicts(
+            key,
+            &[
+                "hold_keycode",
+                "double_tap_keycode",
+                "turbo_hz",
+                "layer",
+                "layers",
+            ],
+            &format!("`{action}`"),
+        ));
+        if key.mode != KeyMode::Momentary {
+            violations.push((
+                "mode",
+                format!("key GPIO {gpio}: `mode` can't be used with `{action}`"),
+            ));
+        }
+    }
+    if let Some(text) = &key.text {
+        if text.is_empty() {
+            violations.push(("text", format!("key GPIO {gpio}: `text` is empty")));
+        } else if let Err(c) = config.keyboard_layout.type_text(text) {
+            violations.push((
+                "text",
+                format!(
+                    "key GPIO {gpio}: `text` has character {c:?} which can't be typed with the {:?} keyboard layout",
+                    config.keyboard_layout
+                ),
+            ));
+        }
+    }
+    if key.double_tap_keycode.is_none() {
+        if key.triple_tap_keycode.is_some() {
+            violations.push((
+                "triple_tap_keycode",
+                format!("key GPIO {gpio}: `triple_tap_keycode` requires `double_tap_keycode`"),
+            ));
+        }
+    } else {
+        violations.extend(conflicts(key, &["hold_keycode"], "`double_tap_keycode`"));
+    }
+    if let Some(hz) = key.turbo_hz {
+        if !(hz > 0.0 && hz <= MAX_TURBO_HZ) {
+            violations.push((
+                "turbo_hz",
+                format!(
+                    "key GPIO {gpio}: `turbo_hz` must be greater than 0 and at most {MAX_TURBO_HZ}"
+                ),
+            ));
+        }
+        violations.extend(conflicts(
+            key,
+            &["hold_keycode", "double_tap_keycode", "layer"],
+            "`turbo_hz`",
+        ));
+        if key.mode == KeyMode::Toggle {
+            violations.push((
+                "mode",
+                format!("key GPIO {gpio}: `mode = \"toggle\"` can't be used with `turbo_hz`"),
+            ));
+        }
+    }
+    if key.turbo_duty.is_some_and(|duty| !(1..=99).contains(&duty)) {
+        violations.push((
+            "turbo_duty",
+            format!("key GPIO {gpio}: `turbo_duty` must be between 1 and 99"),
+        ));
+    }
+    if key.mode == KeyMode::Toggle {
+        violations.extend(conflicts(
+            key,
+            &["hold_keycode", "double_tap_keycode", "layer"],
+            "`mode = \"toggle\"`",
+        ));
+    }
+    if key.mode == KeyMode::TapHold && key.hold_keycode.is_none() {
+        violations.push((
+            "mode",
+            format!("key GPIO {gpio}: `mode = \"tap-hold\"` requires `hold_keycode`"),
+        ));
+    }
+    if key.layer.is_some() {
+        violations.extend(conflicts(
+            key,
+            &[
+                "hold_keycode",
+                "double_tap_keycode",
+                "triple_tap_keycode",
+                "layers",
+            ],
+            "`layer`",
+        ));
+    }
+    if key.repeat {
+        if key.repeat_rate().unwrap_or(DEFAULT_REPEAT_RATE).is_zero() {
+            violations.push((
+                "repeat_rate_ms",
+                format!(
+                    "key GPIO {gpio}: `repeat_rate_ms` must be greater than 0 when `repeat` is enabled"
+                ),
+            ));
+        }
+        let delay = key.repeat_delay().unwrap_or(DEFAULT_REPEAT_DELAY);
+        let debounce = key.debounce().unwrap_or_else(|| config.default_debounce());
+        if debounce > delay {
+            violations.push((
+                "debounce_ms",
+                format!(
+                    "key GPIO {gpio}: debounce ({} ms) is longer than the repeat delay ({} ms)",
+                    debounce.as_millis(),
+                    delay.as_millis()
+                ),
+            ));
+        }
+    }
+    if output(config, key) == Output::Gamepad {
+        violations.extend(gamepad_violations(key));
+    }
+
+    violations
+}
+
+/// A GPIO claimed by a key, power switch or turbo switch, which is already claimed
+pub struct GpioReuse {
+    /// Index of the claiming key, or of the power switch after the keys
+    pub index: usize,
+    /// Index of the key or power switch which claimed the GPIO first
+    pub first: usize,
+    pub violation: Violation,
+}
+
+/// GPIOs claimed more than once by `keys`, `power_switches` and turbo switches
+pub fn gpio_reuses(keys: &[&KeyConfig], power_switches: &[&PowerSwitchConfig]) -> Vec<GpioReuse> {
+    let gpios = keys
+        .iter()
+        .map(|key| key.gpio)
+        .chain(power_switches.iter().map(|ps| ps.gpio))
+        .collect::<Vec<_>>();
+    let mut reuses = Vec::new();
+    for (index, gpio) in gpios.iter().enumerate() {
+        if let Some(first) = gpios[..index].iter().position(|other| other == gpio) {
+            reuses.push(GpioReuse {
+                index,
+                first,
+                violation: ("gpio", format!("GPIO {gpio} is already used")),
+            });
+        }
+    }
+    for (index, key) in keys.iter().enumerate() {
+        let Some(gpio) = key.turbo_gpio else {
+            continue;
+        };
+        if let Some(first) = gpios.iter().position(|other| *other == gpio) {
+            reuses.push(GpioReuse {
+                index,
+                first,
+                violation: (
+                    "turbo_gpio",
+                    format!(
+                        "key GPIO {}: turbo switch GPIO {gpio} is already used",
+                        key.gpio
+                    ),
+                ),
+            });
+        }
+    }
+
+    reuses
+}
+
+/// Rules broken by `chord`, regardless of the other tables
+pub fn chord_violations(chord: &ChordConfig) -> Vec<Violation> {
+    let name = &chord.keycode;
+    let mut violations = Vec::new();
+    if chord.gpios.len() < 2 {
+        violations.push((
+            "gpios",
+            format!("chord {name}: `gpios` must have at least two keys"),
+        ));
+    }
+    for (i, gpio) in chord.gpios.iter().enumerate() {
+        if chord.gpios[..i].contains(gpio) {
+            violations.push((
+                "gpios",
+                format!("chord {name}: GPIO {gpio} is used more than once"),
+            ));
+        }
+    }
+
+    violations
+}
+
+/// Rule broken by `key` being part of `chord`, if any
+pub fn chord_key_violation(chord: &ChordConfig, key: &KeyConfig) -> Option<Violation> {
+    let tap_only = key.hold_keycode.is_none()
+        && key.double_tap_keycode.is_none()
+        && key.layer.is_none()
+        && key.turbo_hz.is_none();
+
+    (!tap_only).then(|| {
+        (
+            "gpios",
+            format!(
+                "chord {}: key GPIO {} can't have `hold_keycode`, multi-tap keycodes, `layer` or `turbo_hz`",
+                chord.keycode, key.gpio
+            ),
+        )
+    })
+}
+
+/// Rules broken by `macro_config`, regardless of the other macros
+pub fn macro_violations(macro_config: &MacroConfig) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    if macro_config.steps.is_empty() {
+        violations.push((
+            "steps",
+            format!("macro {:?} has no steps", macro_config.name),
+        ));
+    }
+
+    violations
+}
+
+/// Output of the device `key` emits on
+fn output(config: &Config, key: &KeyConfig) -> Output {
+    config
+        .device_index(key.device.as_deref())
+        .and_then(|index| config.devices.get(index))
+        .and_then(|device| device.kind)
+        .unwrap_or(config.output)
+}
+
+/// Keyboard keys emitted by a gamepad `key`, which would make the gamepad look like a keyboard
+fn gamepad_violations(key: &KeyConfig) -> Vec<Violation> {
+    let combos = [
+        ("keycode", key.keycode.as_ref()),
+        ("hold_keycode", key.hold_keycode.as_ref()),
+        ("double_tap_keycode", key.double_tap_keycode.as_ref()),
+        ("triple_tap_keycode", key.triple_tap_keycode.as_ref()),
+    ];
+    let layers = key.layers.values().map(|combo| ("layers", Some(combo)));
+    combos
+        .into_iter()
+        .chain(layers)
+        .filter_map(|(field, combo)| {
+            let keycode = combo
+                .into_iter()
+                .flat_map(|combo| combo.keycodes())
+                // the arrow keys drive the d-pad
+                .filter(|k| {
+                    !matches!(
+                        k.keycode(),
+                        evdev::KeyCode::KEY_UP
+                            | evdev::KeyCode::KEY_DOWN
+                            | evdev::KeyCode::KEY_LEFT
+                            | evdev::KeyCode::KEY_RIGHT
+                    )
+                })
+                .find(|k| k.is_keyboard_key())?;
+
+            Some((
+                field,
+                format!(
+                    "key GPIO {}: {keycode} is a keyboard key, which would make the gamepad look like a keyboard",
+                    key.gpio
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Rules broken by setting any of `fields` of `key` along with `with`
+fn conflicts<'a>(
+    key: &'a KeyConfig,
+    fields: &'a [&'static str],
+    with: &'a str,
+) -> impl Iterator<Item = Violation> + 'a {
+    [
+        ("hold_keycode", key.hold_keycode.is_some()),
+        ("double_tap_keycode", key.double_tap_keycode.is_some()),
+        ("triple_tap_keycode", key.triple_tap_keycode.is_some()),
+        ("turbo_hz", key.turbo_hz.is_some()),
+        ("layer", key.layer.is_some()),
+        ("layers", !key.layers.is_empty()),
+    ]
+    .into_iter()
+    .filter(|(field, set)| *set && fields.contains(field))
+    .map(move |(field, _)| {
+        (
+            field,
+            format!("key GPIO {}: `{field}` can't be used with {with}", key.gpio),
+        )
+    })
+}
@@ -3,11 +3,14 @@ mod linux;
 mod mock;
 mod raspberry;
 
-pub use self::linux::LinuxGpio;
+use std::os::fd::RawFd;
+
+pub use self::linux::LinuxBackend;
 #[cfg(test)]
 #[allow(unused)]
-pub use self::mock::MockGpio;
-pub use self::raspberry::RaspberryGpio;
+pub use self::mock::{MockBackend, MockGpio};
+pub use self::raspberry::RaspberryBackend;
+use crate::config::{Bias, Config, Pull};
 
 /// GPIO value representation.
 ///
@@ -28,4 +31,47 @@ pub enum GpioValue {
 pub trait Gpio {
     /// Read the current GPIO value
     fn read(&mut self) -> anyhow::Result<GpioValue>;
+
+    /// File descriptor which becomes readable when an edge event is pending on the GPIO.
+    ///
+    /// Returns `None` if the GPIO doesn't support edge events; in that case it must be polled.
+    fn event_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    /// Consume one pending edge event.
+    ///
+    /// Must be called only once [`Gpio::event_fd`] is readable, otherwise it may block.
+    fn consume_event(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Describes how a GPIO line is requested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineConfig {
+    /// GPIO pin number
+    pub gpio: u8,
+    /// Whether the line is active low
+    pub active_low: bool,
+    /// Line bias on generic Linux systems
+    pub bias: Bias,
+    /// Pull resistor on Raspberry Pi
+    pub pull: Pull,
+    /// Whether edge detection must be enabled on the line
+    pub edge_events: bool,
+}
+
+/// GPIO backend abstraction, which opens lines on a specific platform
+pub trait GpioBackend {
+    type Gpio: Gpio;
+
+    /// Open the GPIO line with the given configuration
+    fn open(&self, line: &LineConfig) -> anyhow::Result<Self::Gpio>;
+
+    /// Whether the GPIOs opened by this backend support edge events
+    fn supports_edge_events(&self) -> bool;
+
+    /// Warn about configuration options which are not supported by this backend
+    fn check_config(&self, config: &Config);
 }
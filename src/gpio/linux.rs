@@ -1,16 +1,104 @@
-use std::path::Path;
+mod v2;
 
-use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
 
-use crate::gpio::{Gpio, GpioValue};
+use gpio_cdev::{Chip, EventRequestFlags, LineEventHandle, LineHandle, LineRequestFlags};
+
+use self::v2::LineRequest;
+use crate::config::{Bias, Config};
+use crate::gpio::{Gpio, GpioBackend, GpioValue, LineConfig};
+
+/// [`GpioBackend`] for generic Linux systems, based on the GPIO character device
+#[derive(Debug)]
+pub struct LinuxBackend {
+    device: PathBuf,
+}
+
+impl LinuxBackend {
+    /// Create a new [`LinuxBackend`] for the GPIO chip at `device`
+    pub fn new(device: PathBuf) -> Self {
+        LinuxBackend { device }
+    }
+}
+
+impl GpioBackend for LinuxBackend {
+    type Gpio = LinuxGpio;
+
+    fn open(&self, line: &LineConfig) -> anyhow::Result<Self::Gpio> {
+        LinuxGpio::try_new(
+            &self.device,
+            line.gpio,
+            line.active_low,
+            line.bias,
+            line.edge_events,
+        )
+    }
+
+    fn supports_edge_events(&self) -> bool {
+        true
+    }
+
+    fn check_config(&self, config: &Config) {
+        if config.default_pull.is_some()
+            || config.keys.iter().any(|k| k.pull.is_some())
+            || config.power_switches.iter().any(|ps| ps.pull.is_some())
+        {
+            warn!("`pull` is only supported on Raspberry Pi; use `bias` instead");
+        }
+    }
+}
 
 pub struct LinuxGpio {
-    handle: LineHandle,
+    handle: Handle,
+}
+
+/// Line handle requested from the chip
+enum Handle {
+    /// Line requested for value reads only
+    Line(LineHandle),
+    /// Line requested with edge detection on both edges
+    Events(LineEventHandle),
+    /// Line requested through the uAPI v2, required to configure the bias
+    V2 {
+        request: LineRequest,
+        edge_events: bool,
+    },
 }
 
 impl LinuxGpio {
-    /// Create a new [`LinuxGpio`] instance for the specified GPIO pin
-    pub fn try_new(device: &Path, gpio: u8, active_low: bool) -> anyhow::Result<Self> {
+    /// Create a new [`LinuxGpio`] instance for the specified GPIO pin.
+    ///
+    /// If `edge_events` is set, the line is requested with edge detection on both edges,
+    /// so it can be waited on through [`Gpio::event_fd`].
+    ///
+    /// Unless `bias` is [`Bias::AsIs`], the line is requested through the GPIO uAPI v2,
+    /// which requires Linux 5.10 or later.
+    pub fn try_new(
+        device: &Path,
+        gpio: u8,
+        active_low: bool,
+        bias: Bias,
+        edge_events: bool,
+    ) -> anyhow::Result<Self> {
+        if bias != Bias::AsIs {
+            debug!("Requesting line for GPIO {gpio} with bias {bias:?} on {device:?}");
+            let request = LineRequest::try_new(
+                device,
+                gpio as u32,
+                "gpio2key",
+                active_low,
+                bias,
+                edge_events,
+            )?;
+            return Ok(LinuxGpio {
+                handle: Handle::V2 {
+                    request,
+                    edge_events,
+                },
+            });
+        }
+
         debug!("Opening chip at {:?}", device);
         let mut chip = Chip::new(device)
             .map_err(|e| anyhow::anyhow!("Failed to open GPIO chip {:?}: {}", device, e))?;
@@ -25,8 +113,13 @@ impl LinuxGpio {
         }
 
         // request handle
-        debug!("Requesting line handle for GPIO {gpio}");
-        let handle = line.request(flags, 0, "gpio2key")?;
+        let handle = if edge_events {
+            debug!("Requesting line event handle for GPIO {gpio}");
+            Handle::Events(line.events(flags, EventRequestFlags::BOTH_EDGES, "gpio2key")?)
+        } else {
+            debug!("Requesting line handle for GPIO {gpio}");
+            Handle::Line(line.request(flags, 0, "gpio2key")?)
+        };
 
         Ok(LinuxGpio { handle })
     }
@@ -34,15 +127,47 @@ impl LinuxGpio {
 
 impl Gpio for LinuxGpio {
     fn read(&mut self) -> anyhow::Result<GpioValue> {
-        let value = self.handle.get_value()?;
-        trace!(
-            "Read GPIO {gpio} value: {value}",
-            gpio = self.handle.line().offset()
-        );
+        let (value, gpio) = match &self.handle {
+            Handle::Line(handle) => (handle.get_value()?, handle.line().offset()),
+            Handle::Events(handle) => (handle.get_value()?, handle.line().offset()),
+            Handle::V2 { request, .. } => (request.get_value()?, request.offset()),
+        };
+        trace!("Read GPIO {gpio} value: {value}");
         match value {
             0 => Ok(GpioValue::Disabled),
             1 => Ok(GpioValue::Enabled),
             v => Err(anyhow::anyhow!("Unexpected GPIO value: {}", v)),
         }
     }
+
+    fn event_fd(&self) -> Option<RawFd> {
+        match &self.handle {
+            Handle::Line(_) => None,
+            Handle::Events(handle) => Some(handle.as_raw_fd()),
+            Handle::V2 {
+                request,
+                edge_events,
+            } => edge_events.then(|| request.as_raw_fd()),
+        }
+    }
+
+    fn consume_event(&mut self) -> anyhow::Result<()> {
+        match &mut self.handle {
+            Handle::Line(_) => {}
+            Handle::Events(handle) => {
+                let event = handle.get_event()?;
+                trace!(
+                    "GPIO {gpio} edge event: {event:?}",
+                    gpio = handle.line().offset()
+                );
+            }
+            Handle::V2 {
+                request,
+                edge_events: true,
+            } => request.read_event()?,
+            Handle::V2 { .. } => {}
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,248 @@
+//! Minimal implementation of the GPIO character device uAPI v2 (`linux/gpio.h`).
+//!
+//! `gpio-cdev` only implements the v1 uAPI, which can't configure the line bias,
+//! so lines with a bias are requested through the v2 ioctls directly.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::Path;
+
+use crate::config::Bias;
+
+const GPIO_V2_LINES_MAX: usize = 64;
+const GPIO_V2_LINE_NUM_ATTRS_MAX: usize = 10;
+const GPIO_MAX_NAME_SIZE: usize = 32;
+
+const GPIO_V2_LINE_FLAG_ACTIVE_LOW: u64 = 1 << 1;
+const GPIO_V2_LINE_FLAG_INPUT: u64 = 1 << 2;
+const GPIO_V2_LINE_FLAG_EDGE_RISING: u64 = 1 << 4;
+const GPIO_V2_LINE_FLAG_EDGE_FALLING: u64 = 1 << 5;
+const GPIO_V2_LINE_FLAG_BIAS_PULL_UP: u64 = 1 << 8;
+const GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN: u64 = 1 << 9;
+const GPIO_V2_LINE_FLAG_BIAS_DISABLED: u64 = 1 << 10;
+
+const GPIO_V2_GET_LINE_IOCTL: u32 = iowr(0x07, std::mem::size_of::<GpioV2LineRequest>());
+const GPIO_V2_LINE_GET_VALUES_IOCTL: u32 = iowr(0x0E, std::mem::size_of::<GpioV2LineValues>());
+
+/// Build an `_IOWR(0xB4, nr, size)` ioctl request number
+const fn iowr(nr: u32, size: usize) -> u32 {
+    const IOC_READ_WRITE: u32 = 3;
+    const GPIO_IOCTL_TYPE: u32 = 0xB4;
+
+    (IOC_READ_WRITE << 30) | ((size as u32) << 16) | (GPIO_IOCTL_TYPE << 8) | nr
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpioV2LineAttribute {
+    id: u32,
+    padding: u32,
+    value: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpioV2LineConfigAttribute {
+    attr: GpioV2LineAttribute,
+    mask: u64,
+}
+
+#[repr(C)]
+struct GpioV2LineConfig {
+    flags: u64,
+    num_attrs: u32,
+    padding: [u32; 5],
+    attrs: [GpioV2LineConfigAttribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+}
+
+#[repr(C)]
+struct GpioV2LineRequest {
+    offsets: [u32; GPIO_V2_LINES_MAX],
+    consumer: [u8; GPIO_MAX_NAME_SIZE],
+    config: GpioV2LineConfig,
+    num_lines: u32,
+    event_buffer_size: u32,
+    padding: [u32; 5],
+    fd: i32,
+}
+
+#[repr(C)]
+struct GpioV2LineValues {
+    bits: u64,
+    mask: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct GpioV2LineEvent {
+    timestamp_ns: u64,
+    id: u32,
+    offset: u32,
+    seqno: u32,
+    line_seqno: u32,
+    padding: [u32; 6],
+}
+
+/// A single input line requested through the v2 uAPI
+pub struct LineRequest {
+    fd: OwnedFd,
+    offset: u32,
+}
+
+impl LineRequest {
+    /// Request `offset` on the chip at `device` as input with the given flags
+    pub fn try_new(
+        device: &Path,
+        offset: u32,
+        consumer: &str,
+        active_low: bool,
+        bias: Bias,
+        edge_events: bool,
+    ) -> anyhow::Result<Self> {
+        let chip = File::open(device)
+            .map_err(|e| anyhow::anyhow!("Failed to open GPIO chip {:?}: {}", device, e))?;
+
+        let mut flags = GPIO_V2_LINE_FLAG_INPUT;
+        if active_low {
+            flags |= GPIO_V2_LINE_FLAG_ACTIVE_LOW;
+        }
+        if edge_events {
+            flags |= GPIO_V2_LINE_FLAG_EDGE_RISING | GPIO_V2_LINE_FLAG_EDGE_FALLING;
+        }
+        flags |= match bias {
+            Bias::AsIs => 0,
+            Bias::PullUp => GPIO_V2_LINE_FLAG_BIAS_PULL_UP,
+            Bias::PullDown => GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN,
+            Bias::Disabled => GPIO_V2_LINE_FLAG_BIAS_DISABLED,
+        };
+
+        let mut request = GpioV2LineRequest {
+            offsets: [0; GPIO_V2_LINES_MAX],
+            consumer: [0; GPIO_MAX_NAME_SIZE],
+            config: GpioV2LineConfig {
+                flags,
+                num_attrs: 0,
+                padding: [0; 5],
+                attrs: [GpioV2LineConfigAttribute {
+                    attr: GpioV2LineAttribute {
+                        id: 0,
+                        padding: 0,
+                        value: 0,
+                    },
+                    mask: 0,
+                }; GPIO_V2_LINE_NUM_ATTRS_MAX],
+            },
+            num_lines: 1,
+            event_buffer_size: 0,
+            padding: [0; 5],
+            fd: -1,
+        };
+        request.offsets[0] = offset;
+        // keep the trailing nul byte
+        let consumer_len = consumer.len().min(GPIO_MAX_NAME_SIZE - 1);
+        request.consumer[..consumer_len].copy_from_slice(&consumer.as_bytes()[..consumer_len]);
+
+        if unsafe {
+            libc::ioctl(
+                chip.as_raw_fd(),
+                GPIO_V2_GET_LINE_IOCTL as libc::Ioctl,
+                &mut request,
+            )
+        } < 0
+        {
+            anyhow::bail!(
+                "Failed to request GPIO line {offset} on {device:?}: {}",
+                io::Error::last_os_error()
+            );
+        }
+
+        Ok(LineRequest {
+            fd: unsafe { OwnedFd::from_raw_fd(request.fd) },
+            offset,
+        })
+    }
+
+    /// Line offset on the chip
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Get the logical value of the line
+    pub fn get_value(&self) -> anyhow::Result<u8> {
+        let mut values = GpioV2LineValues { bits: 0, mask: 1 };
+        if unsafe {
+            libc::ioctl(
+                self.fd.as_raw_fd(),
+                GPIO_V2_LINE_GET_VALUES_IOCTL as libc::Ioctl,
+                &mut values,
+            )
+        } < 0
+        {
+            anyhow::bail!(
+                "Failed to read GPIO line {}: {}",
+                self.offset,
+                io::Error::last_os_error()
+            );
+        }
+
+        Ok((values.bits & 1) as u8)
+    }
+
+    /// Read one edge event from the line; blocks if no event is pending
+    pub fn read_event(&self) -> anyhow::Result<()> {
+        let mut event = GpioV2LineEvent::default();
+        let size = std::mem::size_of::<GpioV2LineEvent>();
+        let res = unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                &mut event as *mut GpioV2LineEvent as *mut libc::c_void,
+                size,
+            )
+        };
+        if res < 0 {
+            anyhow::bail!(
+                "Failed to read event for GPIO line {}: {}",
+                self.offset,
+                io::Error::last_os_error()
+            );
+        }
+        if res as usize != size {
+            anyhow::bail!(
+                "Short read for event of GPIO line {}: {res} bytes",
+                self.offset
+            );
+        }
+        trace!("GPIO {} edge event: {:?}", self.offset, event);
+
+        Ok(())
+    }
+}
+
+impl AsRawFd for LineRequest {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_match_kernel_abi_sizes() {
+        assert_eq!(std::mem::size_of::<GpioV2LineAttribute>(), 16);
+        assert_eq!(std::mem::size_of::<GpioV2LineConfigAttribute>(), 24);
+        assert_eq!(std::mem::size_of::<GpioV2LineConfig>(), 272);
+        assert_eq!(std::mem::size_of::<GpioV2LineRequest>(), 592);
+        assert_eq!(std::mem::size_of::<GpioV2LineValues>(), 16);
+        assert_eq!(std::mem::size_of::<GpioV2LineEvent>(), 48);
+    }
+
+    #[test]
+    fn test_should_build_ioctl_numbers() {
+        assert_eq!(GPIO_V2_GET_LINE_IOCTL, 0xC250_B407);
+        assert_eq!(GPIO_V2_LINE_GET_VALUES_IOCTL, 0xC010_B40E);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::gpio::{Gpio, GpioValue};
+use crate::config::Config;
+use crate::gpio::{Gpio, GpioBackend, GpioValue, LineConfig};
 
 /// Mock GPIO implementation for testing
 pub struct MockGpio {
@@ -13,6 +14,11 @@ impl MockGpio {
             value: initial_value,
         }
     }
+
+    /// Set the raw value of the line
+    pub fn set(&mut self, value: bool) {
+        self.value = value;
+    }
 }
 
 impl Gpio for MockGpio {
@@ -26,6 +32,24 @@ impl Gpio for MockGpio {
     }
 }
 
+/// Mock GPIO backend for testing, opening released lines
+#[derive(Debug, Default)]
+pub struct MockBackend;
+
+impl GpioBackend for MockBackend {
+    type Gpio = MockGpio;
+
+    fn open(&self, line: &LineConfig) -> anyhow::Result<Self::Gpio> {
+        Ok(MockGpio::new(line.active_low, line.active_low))
+    }
+
+    fn supports_edge_events(&self) -> bool {
+        false
+    }
+
+    fn check_config(&self, _config: &Config) {}
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
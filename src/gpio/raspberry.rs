@@ -1,6 +1,31 @@
 use rppal::gpio::{Gpio as RrppalGpio, InputPin};
 
-use crate::gpio::{Gpio, GpioValue};
+use crate::config::{Bias, Config, Pull};
+use crate::gpio::{Gpio, GpioBackend, GpioValue, LineConfig};
+
+/// [`GpioBackend`] for Raspberry Pi boards, based on `rppal`
+#[derive(Debug, Default)]
+pub struct RaspberryBackend;
+
+impl GpioBackend for RaspberryBackend {
+    type Gpio = RaspberryGpio;
+
+    fn open(&self, line: &LineConfig) -> anyhow::Result<Self::Gpio> {
+        RaspberryGpio::try_new(line.gpio, line.active_low, line.pull)
+    }
+
+    fn supports_edge_events(&self) -> bool {
+        false
+    }
+
+    fn check_config(&self, config: &Config) {
+        if config.keys.iter().any(|k| k.bias != Bias::AsIs)
+            || config.power_switches.iter().any(|ps| ps.bias != Bias::AsIs)
+        {
+            warn!("`bias` is ignored on Raspberry Pi; use `pull` instead");
+        }
+    }
+}
 
 pub struct RaspberryGpio {
     active_low: bool,
@@ -8,13 +33,19 @@ pub struct RaspberryGpio {
 }
 
 impl RaspberryGpio {
-    /// Create a new [`RaspberryGpio`] instance for the specified GPIO pin
-    pub fn try_new(gpio: u8, active_low: bool) -> anyhow::Result<Self> {
+    /// Create a new [`RaspberryGpio`] instance for the specified GPIO pin,
+    /// configured as input with the given [`Pull`] resistor
+    pub fn try_new(gpio: u8, active_low: bool, pull: Pull) -> anyhow::Result<Self> {
         let pin = RrppalGpio::new()
             .map_err(|e| anyhow::anyhow!("Failed to access GPIO: {}", e))?
             .get(gpio)
-            .map_err(|e| anyhow::anyhow!("Failed to get GPIO pin {}: {}", gpio, e))?
-            .into_input_pullup();
+            .map_err(|e| anyhow::anyhow!("Failed to get GPIO pin {}: {}", gpio, e))?;
+        debug!("Configuring GPIO {gpio} as input with pull {pull:?}");
+        let pin = match pull {
+            Pull::Up => pin.into_input_pullup(),
+            Pull::Down => pin.into_input_pulldown(),
+            Pull::None => pin.into_input(),
+        };
 
         Ok(RaspberryGpio { active_low, pin })
     }
@@ -1,115 +1,1668 @@
+mod chords;
 mod config;
+mod epoll;
+mod exec;
+mod layers;
+mod macros;
+mod multi_tap;
 mod state;
+mod tap_hold;
+mod waker;
 
+use std::os::fd::AsRawFd;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-pub use self::config::{InputListenerConfig, KeyConfig, PowerSwitch, RepeatConfig};
-use self::state::KeyState;
-use crate::gpio::{Gpio, GpioValue};
+use self::chords::{ChordEvent, Chords};
+pub use self::config::{
+    ChordConfig, ExecConfig, HoldConfig, InputListenerConfig, InputsConfig, KeyConfig,
+    MultiTapConfig, PowerSwitch, RepeatConfig, TurboConfig, TurboSwitch,
+};
+use self::epoll::Epoll;
+use self::exec::Exec;
+use self::layers::Layers;
+use self::macros::Macros;
+use self::multi_tap::MultiTap;
+use self::state::{KeyState, PowerSwitchState, State, TurboSwitchState};
+use self::tap_hold::HeldBack;
+pub use self::waker::Waker;
+use crate::config::{InputMode, KeyCombo, KeyMode};
+use crate::gpio::{Gpio, GpioValue, LineConfig};
 use crate::input_listener::state::OutEvent;
 use crate::keyboard::Keyboard;
 
 /// Input listener.
 ///
-/// The input listener monitors GPIOs and emits key events via the keyboard interface.
+/// The input listener monitors GPIOs and emits key events via the keyboard interface,
+/// on the output device of each key.
+///
+/// GPIOs are either polled every `poll_interval` or, in [`InputMode::Event`],
+/// waited on through their edge event file descriptors.
 pub struct InputListener<K, GPIO>
 where
     K: Keyboard,
     GPIO: Gpio,
 {
     exit: Arc<AtomicBool>,
-    keyboard: K,
+    reload: Arc<AtomicBool>,
+    waker: Arc<Waker>,
+    keyboards: Vec<K>,
     keys: Vec<KeyState<GPIO>>,
-    power_switches: Vec<PowerSwitch<GPIO>>,
+    /// Layers activated by the layer modifier keys held down
+    layers: Layers,
+    chords: Chords,
+    /// Events held back while a tap-hold key is undecided
+    held_back: HeldBack,
+    /// Macros being played back
+    macros: Macros,
+    turbo_switches: Vec<TurboSwitchState<GPIO>>,
+    power_switches: Vec<PowerSwitchState<GPIO>>,
+    mode: InputMode,
     poll_interval: Duration,
 }
 
+/// Epoll token of the [`Waker`]
+const WAKER_TOKEN: u64 = u64::MAX;
+
 impl<K, G> InputListener<K, G>
 where
     K: Keyboard,
     G: Gpio,
 {
-    /// Create a new input listener with the given configuration
-    pub fn new(config: InputListenerConfig<K, G>) -> Self {
-        InputListener {
+    /// Create a new input listener with the given configuration.
+    ///
+    /// GPIO lines are opened with `open`.
+    pub fn try_new<F>(config: InputListenerConfig<K>, open: F) -> anyhow::Result<Self>
+    where
+        F: FnMut(&LineConfig) -> anyhow::Result<G>,
+    {
+        let mut listener = InputListener {
             exit: config.exit,
-            keyboard: config.keyboard,
-            keys: config.keys.into_iter().map(KeyState::from).collect(),
-            power_switches: config.power_switches,
-            poll_interval: config.poll_interval,
+            reload: config.reload,
+            waker: config.waker,
+            keyboards: config.keyboards,
+            keys: Vec::new(),
+            layers: Layers::default(),
+            chords: Chords::default(),
+            held_back: HeldBack::default(),
+            macros: Macros::default(),
+            turbo_switches: Vec::new(),
+            power_switches: Vec::new(),
+            mode: config.inputs.mode,
+            poll_interval: config.inputs.poll_interval,
+        };
+        listener.reload(config.inputs, open)?;
+
+        Ok(listener)
+    }
+
+    /// Run the input listener until exit or reload is requested.
+    ///
+    /// Keys which are still held down are released when exiting.
+    pub fn run(&mut self) {
+        match self.mode {
+            InputMode::Poll => self.run_polling(),
+            InputMode::Event => {
+                if let Err(e) = self.run_event_driven() {
+                    error!("Event driven input listener failed: {e}; falling back to polling");
+                    self.run_polling();
+                }
+            }
+        }
+        if self.exit.load(std::sync::atomic::Ordering::SeqCst) {
+            self.release_all();
         }
     }
 
-    /// Run the input listener
-    pub fn run(mut self) {
-        while !self.exit.load(std::sync::atomic::Ordering::SeqCst) {
-            for key in &mut self.keys {
-                Self::handle_key_poll(key, &mut self.keyboard);
+    /// Apply a new inputs configuration.
+    ///
+    /// Keys and switches whose line configuration is unchanged keep their GPIO and state;
+    /// lines which are no longer used are closed and new lines are opened with `open`.
+    /// Held keys which are removed or whose settings change are released,
+    /// and so are the pressed chords.
+    ///
+    /// If some lines fail to open, the remaining configuration is still applied and an error is returned.
+    pub fn reload<F>(&mut self, inputs: InputsConfig, mut open: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&LineConfig) -> anyhow::Result<G>,
+    {
+        self.reset_held_back();
+        self.macros.reset();
+        let mut released = Vec::new();
+        self.reset_chords(&mut released);
+        // close unused lines first, so they can be requested again with a different configuration
+        let (mut old_keys, removed_keys): (Vec<_>, Vec<_>) = std::mem::take(&mut self.keys)
+            .into_iter()
+            .partition(|key| inputs.keys.iter().any(|k| k.line == key.line));
+        for key in removed_keys {
+            info!("GPIO {}: key {} removed", key.line.gpio, key.name);
+            let device = key.device;
+            if Self::release_key(&mut self.keyboards, key) {
+                released.push(device);
             }
-            for switch in &mut self.power_switches {
-                Self::handle_power_switch_poll(switch, &self.exit);
+        }
+        let mut old_turbo_switches = std::mem::take(&mut self.turbo_switches);
+        old_turbo_switches.retain(|switch| {
+            let keep = inputs
+                .turbo_switches
+                .iter()
+                .any(|ts| ts.line == switch.line);
+            if !keep {
+                info!("GPIO {}: turbo switch removed", switch.line.gpio);
+            }
+            keep
+        });
+        let mut old_switches = std::mem::take(&mut self.power_switches);
+        old_switches.retain(|switch| {
+            let keep = inputs
+                .power_switches
+                .iter()
+                .any(|ps| ps.line == switch.line);
+            if !keep {
+                info!("GPIO {}: power switch removed", switch.line.gpio);
+            }
+            keep
+        });
+
+        let mut errors = Vec::new();
+        for config in inputs.keys {
+            let gpio = config.line.gpio;
+            if let Some(pos) = old_keys.iter().position(|key| key.line == config.line) {
+                let mut key = old_keys.swap_remove(pos);
+                let changed = [
+                    ("keycode", key.keycode != config.keycode),
+                    ("hold", key.hold != config.hold),
+                    (
+                        "multi-tap",
+                        key.multi_tap.as_ref().map(|multi_tap| &multi_tap.config)
+                            != config.multi_tap.as_ref(),
+                    ),
+                    ("layer", key.layer != config.layer),
+                    ("layers", key.layer_keycodes != config.layer_keycodes),
+                    ("mode", key.mode != config.mode),
+                    ("turbo", key.turbo != config.turbo),
+                    ("repeat", key.repeat != config.repeat),
+                    ("device", key.device != config.device),
+                    ("macro", key.key_macro != config.key_macro),
+                    (
+                        "exec",
+                        key.exec.as_ref().map(|exec| &exec.config) != config.exec.as_ref(),
+                    ),
+                ]
+                .into_iter()
+                .filter_map(|(setting, changed)| changed.then_some(setting))
+                .collect::<Vec<_>>();
+                if !changed.is_empty() {
+                    info!(
+                        "GPIO {gpio}: key {} settings changed: {}",
+                        key.name,
+                        changed.join(", ")
+                    );
+                    if let Some(held) = key.held_keycode().cloned() {
+                        key.release();
+                        if Self::key_up(&mut self.keyboards, key.device, &held) {
+                            released.push(key.device);
+                        }
+                    }
+                }
+                key.keycode = config.keycode;
+                key.name = config.name;
+                key.device = config.device;
+                key.debounce = config.debounce;
+                key.mode = config.mode;
+                key.repeat = config.repeat;
+                key.hold = config.hold;
+                key.turbo = config.turbo;
+                // keep the taps in progress, unless the taps changed
+                if key.multi_tap.as_ref().map(|multi_tap| &multi_tap.config)
+                    != config.multi_tap.as_ref()
+                {
+                    key.multi_tap = config.multi_tap.map(MultiTap::new);
+                }
+                key.layer = config.layer;
+                key.layer_keycodes = config.layer_keycodes;
+                key.key_macro = config.key_macro;
+                // keep the commands running, unless they changed
+                if key.exec.as_ref().map(|exec| &exec.config) != config.exec.as_ref() {
+                    key.exec = config.exec.map(|exec| Exec::new(gpio, exec));
+                }
+                self.keys.push(key);
+                continue;
+            }
+
+            info!("GPIO {gpio}: opening line for key {}", config.name);
+            match open(&config.line) {
+                Ok(gpio) => self.keys.push(KeyState::new(config, gpio)),
+                Err(e) => errors.push(format!("GPIO {gpio}: {e}")),
             }
-            trace!("tick");
+        }
+        for config in inputs.turbo_switches {
+            if let Some(pos) = old_turbo_switches
+                .iter()
+                .position(|switch| switch.line == config.line)
+            {
+                let mut switch = old_turbo_switches.swap_remove(pos);
+                switch.debounce = config.debounce;
+                self.turbo_switches.push(switch);
+                continue;
+            }
+
+            info!("GPIO {}: opening line for turbo switch", config.line.gpio);
+            match open(&config.line) {
+                Ok(gpio) => self.turbo_switches.push(TurboSwitchState {
+                    gpio,
+                    line: config.line,
+                    debounce: config.debounce,
+                    enabled: false,
+                    state: State::Unknown,
+                }),
+                Err(e) => errors.push(format!("GPIO {}: {e}", config.line.gpio)),
+            }
+        }
+        self.sync_turbo();
+        for config in inputs.power_switches {
+            if let Some(pos) = old_switches
+                .iter()
+                .position(|switch| switch.line == config.line)
+            {
+                self.power_switches.push(old_switches.swap_remove(pos));
+                continue;
+            }
+
+            info!("GPIO {}: opening line for power switch", config.line.gpio);
+            match open(&config.line) {
+                Ok(gpio) => self.power_switches.push(PowerSwitchState {
+                    gpio,
+                    line: config.line,
+                }),
+                Err(e) => errors.push(format!("GPIO {}: {e}", config.line.gpio)),
+            }
+        }
+        Self::sync(&mut self.keyboards, &released);
+        self.chords = Chords::new(inputs.chords);
+        // layers stay active only while their modifier is still held
+        let keys = &self.keys;
+        self.layers.retain(|name| {
+            keys.iter()
+                .any(|key| key.layer.as_deref() == Some(name) && key.is_pressed())
+        });
+
+        self.mode = inputs.mode;
+        self.poll_interval = inputs.poll_interval;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("Failed to open GPIO lines: {}", errors.join("; "))
+        }
+    }
+
+    /// Replace the output devices, releasing all the held keys on the current ones
+    pub fn replace_keyboards(&mut self, keyboards: Vec<K>) {
+        self.release_all();
+        self.keyboards = keyboards;
+    }
+
+    /// Run the input listener reading all the GPIOs every `poll_interval`
+    fn run_polling(&mut self) {
+        debug!("Polling GPIOs every {:?}", self.poll_interval);
+        while !self.should_stop() {
+            self.tick();
             std::thread::sleep(self.poll_interval);
         }
     }
 
-    /// Handle polling of a single key
-    fn handle_key_poll(key: &mut KeyState<G>, keyboard: &mut K) {
+    /// Run the input listener waiting for GPIO edge events.
+    ///
+    /// The listener sleeps until an edge occurs, the [`Waker`] is woken up,
+    /// or a debounce/repeat deadline of any key elapses.
+    fn run_event_driven(&mut self) -> anyhow::Result<()> {
+        let epoll = Epoll::try_new()?;
+        epoll.add(self.waker.as_raw_fd(), WAKER_TOKEN)?;
+        let gpios = self
+            .keys
+            .iter()
+            .map(|key| &key.gpio)
+            .chain(self.power_switches.iter().map(|switch| &switch.gpio))
+            .chain(self.turbo_switches.iter().map(|switch| &switch.gpio));
+        for (token, gpio) in gpios.enumerate() {
+            let Some(fd) = gpio.event_fd() else {
+                anyhow::bail!("GPIO doesn't support edge events");
+            };
+            epoll.add(fd, token as u64)?;
+        }
+        debug!("Waiting for GPIO edge events");
+
+        while !self.should_stop() {
+            // deadlines elapsed while ticking are kept, so they're handled right away
+            let now = self.tick();
+
+            let timeout = self
+                .keys
+                .iter()
+                .filter_map(|key| key.next_deadline(now))
+                .chain(self.chords.next_deadline())
+                .chain(self.macros.next_deadline())
+                .chain(self.keyboards.iter().filter_map(Keyboard::next_deadline))
+                .min()
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            trace!("waiting for events with timeout {timeout:?}");
+            for token in epoll.wait(timeout)? {
+                self.consume_event(token);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Consume the event notified for the epoll `token`
+    fn consume_event(&mut self, token: u64) {
+        if token == WAKER_TOKEN {
+            trace!("woken up");
+            self.waker.reset();
+            return;
+        }
+
+        let token = token as usize;
+        let switches = self.keys.len() + self.power_switches.len();
+        let gpio = if token < self.keys.len() {
+            &mut self.keys[token].gpio
+        } else if token < switches {
+            &mut self.power_switches[token - self.keys.len()].gpio
+        } else {
+            &mut self.turbo_switches[token - switches].gpio
+        };
+        if let Err(e) = gpio.consume_event() {
+            error!("Failed to read GPIO edge event: {e}");
+        }
+    }
+
+    /// Read all the GPIOs and handle their values, returning the instant the tick ran at
+    fn tick(&mut self) -> Instant {
+        let mut turbo_toggled = false;
+        for switch in &mut self.turbo_switches {
+            turbo_toggled |= Self::handle_turbo_switch_poll(switch);
+        }
+        if turbo_toggled {
+            self.sync_turbo();
+        }
+        let now = Instant::now();
+        for index in 0..self.keys.len() {
+            let key = &mut self.keys[index];
+            let event = Self::poll_key(key);
+            if event == OutEvent::None {
+                continue;
+            }
+            let gpio = key.line.gpio;
+            for event in self.chords.handle(gpio, event, now) {
+                self.emit(event);
+            }
+        }
+        for event in self.chords.expire(now) {
+            self.emit(event);
+        }
+        self.play_macros(now);
+        for exec in self.keys.iter_mut().filter_map(|key| key.exec.as_mut()) {
+            exec.poll(now);
+        }
+        for keyboard in &mut self.keyboards {
+            if let Err(e) = keyboard.tick(now) {
+                error!("Failed to emit pointer motion: {e}");
+            }
+        }
+        if self
+            .power_switches
+            .iter_mut()
+            .any(Self::handle_power_switch_poll)
+        {
+            self.power_off();
+        }
+        trace!("tick");
+
+        now
+    }
+
+    /// Emit a key up event for every key which is currently held down and flush them.
+    fn release_all(&mut self) {
+        self.reset_held_back();
+        self.macros.reset();
+        let mut released = Vec::new();
+        self.reset_chords(&mut released);
+        for key in &mut self.keys {
+            let Some(held) = key.held_keycode().cloned() else {
+                continue;
+            };
+            info!("Releasing held key {held}");
+            key.release();
+            if Self::key_up(&mut self.keyboards, key.device, &held) {
+                released.push(key.device);
+            }
+        }
+        Self::sync(&mut self.keyboards, &released);
+    }
+
+    /// Abort the chords in progress, emitting the key up events of the pressed ones.
+    ///
+    /// The keys whose events are held back or dropped by the chords are forced into the released state,
+    /// since nothing has been emitted for them.
+    fn reset_chords(&mut self, released: &mut Vec<usize>) {
+        for key in &mut self.keys {
+            if self.chords.is_captured(key.line.gpio) {
+                key.release();
+            }
+        }
+        for index in self.chords.reset() {
+            let chord = self.chords.config(index);
+            info!("Releasing held chord {}", chord.keycode);
+            if Self::key_up(&mut self.keyboards, chord.device, &chord.keycode) {
+                released.push(chord.device);
+            }
+        }
+    }
+
+    /// Emit a key up event for `key` if it is held down, consuming it.
+    ///
+    /// Returns whether a key up event has been emitted.
+    fn release_key(keyboards: &mut [K], key: KeyState<G>) -> bool {
+        key.held_keycode()
+            .is_some_and(|held| Self::key_up(keyboards, key.device, held))
+    }
+
+    /// Emit the key up events for `keycode` on `device`, logging failures.
+    ///
+    /// Returns whether the events have been emitted.
+    fn key_up(keyboards: &mut [K], device: usize, keycode: &KeyCombo) -> bool {
+        let Some(keyboard) = keyboards.get_mut(device) else {
+            error!("Failed to release key {keycode}: no output device {device}");
+            return false;
+        };
+        match Self::release_combo(keyboard, keycode) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Failed to release key {keycode}: {e}");
+                false
+            }
+        }
+    }
+
+    /// Press the keys of `combo` in order
+    fn press_combo(keyboard: &mut K, combo: &KeyCombo) -> anyhow::Result<()> {
+        combo
+            .keycodes()
+            .iter()
+            .try_for_each(|keycode| keyboard.key_down(keycode.keycode()))
+    }
+
+    /// Release the keys of `combo` in reverse order.
+    ///
+    /// Every key is released even if some fail, so no modifier is left held; the first error is returned.
+    fn release_combo(keyboard: &mut K, combo: &KeyCombo) -> anyhow::Result<()> {
+        let mut res = Ok(());
+        for keycode in combo.keycodes().iter().rev() {
+            let up = keyboard.key_up(keycode.keycode());
+            res = res.and(up);
+        }
+
+        res
+    }
+
+    /// Flush the events of the output `devices`
+    fn sync(keyboards: &mut [K], devices: &[usize]) {
+        for (_, keyboard) in keyboards
+            .iter_mut()
+            .enumerate()
+            .filter(|(device, _)| devices.contains(device))
+        {
+            if let Err(e) = keyboard.sync() {
+                error!("Failed to flush key events: {e}");
+            }
+        }
+    }
+
+    /// Whether the listener must stop, because exit or reload has been requested
+    fn should_stop(&self) -> bool {
+        self.exit.load(std::sync::atomic::Ordering::SeqCst)
+            || self.reload.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Handle polling of a single key, returning the event to emit
+    fn poll_key(key: &mut KeyState<G>) -> OutEvent {
         // read value
-        trace!("Polling key {:?}", key.keycode);
+        trace!("Polling key {}", key.name);
         let Ok(value) = key.gpio.read() else {
-            error!("Failed to read GPIO for key {:?}", key.keycode);
-            return;
+            error!("Failed to read GPIO for key {}", key.name);
+            return OutEvent::None;
         };
-        trace!("Read GPIO value {:?} for key {:?}", value, key.keycode);
+        trace!("Read GPIO value {:?} for key {}", value, key.name);
         // handle value
-        let res = match key.handle_gpio_value(value) {
-            OutEvent::None => Ok(()),
-            OutEvent::Press => {
-                info!("Key {:?} pressed", key.keycode);
-                keyboard.key_down(key.keycode.keycode())
+        key.handle_gpio_value(value)
+    }
+
+    /// Emit an event which went through chord detection
+    fn emit(&mut self, event: ChordEvent) {
+        match event {
+            ChordEvent::Key { gpio, event } => {
+                if self.keys.iter().any(KeyState::is_undecided) {
+                    if self.held_back.push(gpio, event) {
+                        self.resolve_permissive_holds();
+                    }
+                } else {
+                    self.handle_key(gpio, event);
+                }
+                self.flush_held_back();
             }
-            OutEvent::Release => {
-                info!("Key {:?} released", key.keycode);
-                keyboard.key_up(key.keycode.keycode())
+            ChordEvent::Press(index) => {
+                let chord = self.chords.config(index);
+                info!("Chord {} pressed", chord.keycode);
+                let Some(keyboard) = self.keyboards.get_mut(chord.device) else {
+                    error!(
+                        "No output device {} for chord {}",
+                        chord.device, chord.keycode
+                    );
+                    return;
+                };
+                if let Err(e) = Self::press_combo(keyboard, &chord.keycode) {
+                    error!(
+                        "Failed to send key event for chord {}: {}",
+                        chord.keycode, e
+                    );
+                }
+            }
+            ChordEvent::Release(index) => {
+                let chord = self.chords.config(index);
+                info!("Chord {} released", chord.keycode);
+                Self::key_up(&mut self.keyboards, chord.device, &chord.keycode);
+            }
+        }
+    }
+
+    /// Emit `event` for the key on `gpio`
+    fn handle_key(&mut self, gpio: u8, event: OutEvent) {
+        let Some(key) = self.keys.iter_mut().find(|key| key.line.gpio == gpio) else {
+            return;
+        };
+        if let Some(exec) = &mut key.exec {
+            // exec keys run their commands on press and release, and emit nothing
+            match event {
+                OutEvent::Press => exec.press(Instant::now()),
+                OutEvent::Release => exec.release(Instant::now()),
+                _ => {}
+            }
+            return;
+        }
+        match &key.key_macro {
+            // macro keys start or cancel their macro on press, and emit nothing else
+            Some(key_macro) if event == OutEvent::Press => {
+                if self
+                    .macros
+                    .toggle(gpio, key.device, key_macro, Instant::now())
+                {
+                    info!("Macro {} started", key_macro.name);
+                } else {
+                    info!("Macro {} cancelled", key_macro.name);
+                }
+            }
+            Some(_) => {}
+            None => Self::handle_key_event(key, event, &mut self.keyboards, &mut self.layers),
+        }
+    }
+
+    /// Tap the keys of the macro steps due at `now`
+    fn play_macros(&mut self, now: Instant) {
+        for (device, keycode) in self.macros.advance(now) {
+            let Some(keyboard) = self.keyboards.get_mut(device) else {
+                error!("No output device {device} for macro key {keycode}");
+                continue;
+            };
+            info!("Macro key {keycode} tapped");
+            if let Err(e) = Self::press_combo(keyboard, &keycode)
+                .and_then(|()| Self::release_combo(keyboard, &keycode))
+            {
+                error!("Failed to send key event for macro key {keycode}: {e}");
+            }
+        }
+    }
+
+    /// Resolve the undecided tap-hold keys with permissive hold as held, since another key has been
+    /// pressed and released while they were down
+    fn resolve_permissive_holds(&mut self) {
+        for key in &mut self.keys {
+            if key.is_undecided() && key.hold.as_ref().is_some_and(|hold| hold.permissive) {
+                let event = key.resolve_hold();
+                Self::handle_key_event(key, event, &mut self.keyboards, &mut self.layers);
+            }
+        }
+    }
+
+    /// Emit the events held back by the tap-hold keys, once none of them is undecided
+    fn flush_held_back(&mut self) {
+        if self.held_back.is_empty() || self.keys.iter().any(KeyState::is_undecided) {
+            return;
+        }
+        for (gpio, event) in self.held_back.take() {
+            self.handle_key(gpio, event);
+        }
+    }
+
+    /// Force the undecided tap-hold keys into the released state, emitting the events they held back.
+    ///
+    /// Nothing has been emitted for the undecided keys, so they don't need any key up event.
+    fn reset_held_back(&mut self) {
+        for key in &mut self.keys {
+            if key.is_undecided() {
+                key.release();
+            }
+        }
+        self.flush_held_back();
+    }
+
+    /// Emit the keys of `event` for a single key
+    fn handle_key_event(
+        key: &mut KeyState<G>,
+        event: OutEvent,
+        keyboards: &mut [K],
+        layers: &mut Layers,
+    ) {
+        let Some(keyboard) = keyboards.get_mut(key.device) else {
+            error!("No output device {} for key {}", key.device, key.name);
+            return;
+        };
+        if let Some(layer) = key.layer.clone() {
+            if let Err(e) = Self::handle_layer_modifier(key, event, &layer, keyboard, layers) {
+                error!("Failed to send key event for key {}: {}", key.name, e);
+            }
+            return;
+        }
+        if key.mode == KeyMode::Toggle {
+            if let Err(e) = Self::handle_toggle(key, event, keyboard, layers) {
+                error!("Failed to send key event for key {}: {}", key.name, e);
+            }
+            return;
+        }
+        match event {
+            OutEvent::Press | OutEvent::Tap => {
+                key.resolved = layers.resolve(&key.layer_keycodes).cloned();
+            }
+            OutEvent::HoldPress => {
+                layers.resolve(&[]);
+            }
+            _ => {}
+        }
+
+        let res = match (event, key.out_keycode()) {
+            (OutEvent::None, _) => Ok(()),
+            (OutEvent::Press, Some(keycode)) => {
+                info!("Key {keycode} pressed");
+                Self::press_combo(keyboard, keycode)
+            }
+            (OutEvent::Release, Some(keycode)) => {
+                info!("Key {keycode} released");
+                Self::release_combo(keyboard, keycode)
+            }
+            (OutEvent::Repeat, Some(keycode)) => {
+                // only the main key repeats, while the modifiers stay held
+                info!("Key {keycode} repeat");
+                keyboard.key_repeat(keycode.key().keycode())
+            }
+            (OutEvent::Tap, Some(keycode)) => {
+                info!("Key {keycode} tapped");
+                Self::press_combo(keyboard, keycode)
+                    .and_then(|()| Self::release_combo(keyboard, keycode))
             }
-            OutEvent::Repeat => {
-                info!("Key {:?} repeat", key.keycode);
-                keyboard.key_repeat(key.keycode.keycode())
+            // keys without keycode run their action instead
+            (OutEvent::Press | OutEvent::Release | OutEvent::Repeat | OutEvent::Tap, None) => {
+                Ok(())
             }
+            (OutEvent::HoldPress, _) => match key.held_keycode() {
+                Some(held) => {
+                    info!("Key {} held: {held} pressed", key.name);
+                    Self::press_combo(keyboard, held)
+                }
+                None => Ok(()),
+            },
+            (OutEvent::HoldRelease, _) => match &key.hold {
+                Some(hold) => {
+                    info!("Key {} held: {} released", key.name, hold.keycode);
+                    Self::release_combo(keyboard, &hold.keycode)
+                }
+                None => Ok(()),
+            },
         };
         if let Err(e) = res {
-            error!("Failed to send key event for key {:?}: {}", key.keycode, e);
+            error!("Failed to send key event for key {}: {}", key.name, e);
+        }
+        if matches!(event, OutEvent::Release | OutEvent::Tap) {
+            key.resolved = None;
         }
     }
 
-    /// Handle polling of a single power switch
-    fn handle_power_switch_poll(switch: &mut PowerSwitch<G>, exit: &Arc<AtomicBool>) {
+    /// Handle the `event` of a modifier of `layer`.
+    ///
+    /// The layer is active while the key is held; the key is tapped only if no other key
+    /// has been pressed in the meantime.
+    fn handle_layer_modifier(
+        key: &KeyState<G>,
+        event: OutEvent,
+        layer: &str,
+        keyboard: &mut K,
+        layers: &mut Layers,
+    ) -> anyhow::Result<()> {
+        match event {
+            OutEvent::Press => {
+                info!("Layer {layer} activated");
+                layers.activate(layer);
+                Ok(())
+            }
+            OutEvent::Release => {
+                info!("Layer {layer} deactivated");
+                if layers.deactivate(layer) {
+                    return Ok(());
+                }
+                let Some(keycode) = &key.keycode else {
+                    return Ok(());
+                };
+                info!("Key {keycode} tapped");
+                Self::press_combo(keyboard, keycode)
+                    .and_then(|()| Self::release_combo(keyboard, keycode))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Handle the `event` of a toggle key: every press flips its keys between held down and released
+    fn handle_toggle(
+        key: &mut KeyState<G>,
+        event: OutEvent,
+        keyboard: &mut K,
+        layers: &mut Layers,
+    ) -> anyhow::Result<()> {
+        if event != OutEvent::Press {
+            return Ok(());
+        }
+        if key.latched {
+            key.latched = false;
+            let res = key.out_keycode().map_or(Ok(()), |keycode| {
+                info!("Key {keycode} unlatched");
+                Self::release_combo(keyboard, keycode)
+            });
+            key.resolved = None;
+            res
+        } else {
+            key.resolved = layers.resolve(&key.layer_keycodes).cloned();
+            key.latched = true;
+            key.out_keycode().map_or(Ok(()), |keycode| {
+                info!("Key {keycode} latched");
+                Self::press_combo(keyboard, keycode)
+            })
+        }
+    }
+
+    /// Handle polling of a single turbo switch.
+    ///
+    /// Returns whether turbo has been switched on or off.
+    fn handle_turbo_switch_poll(switch: &mut TurboSwitchState<G>) -> bool {
         let value = match switch.gpio.read() {
-            Ok(v) => v,
+            Ok(value) => value,
             Err(e) => {
-                error!("Failed to read GPIO for power switch: {}", e);
-                return;
+                error!("Failed to read GPIO for turbo switch: {}", e);
+                return false;
             }
         };
-        if value == GpioValue::Enabled {
-            warn!("Power switch activated, shutting down system");
-            #[cfg(target_os = "linux")]
-            unsafe {
-                if libc::reboot(libc::LINUX_REBOOT_CMD_POWER_OFF) != 0 {
-                    error!(
-                        "Failed to shut down system: {}",
-                        std::io::Error::last_os_error()
-                    );
-                } else {
-                    info!("System shutdown initiated");
-                    // set exit
-                    exit.store(true, std::sync::atomic::Ordering::SeqCst);
-                }
+        if !switch.handle_gpio_value(value) {
+            return false;
+        }
+        info!(
+            "GPIO {}: turbo switched {}",
+            switch.line.gpio,
+            if switch.enabled { "on" } else { "off" }
+        );
+
+        true
+    }
+
+    /// Switch turbo on or off for the keys, according to their turbo switch
+    fn sync_turbo(&mut self) {
+        for key in &mut self.keys {
+            key.turbo_enabled = match key.turbo.and_then(|turbo| turbo.switch) {
+                None => true,
+                Some(gpio) => self
+                    .turbo_switches
+                    .iter()
+                    .any(|switch| switch.line.gpio == gpio && switch.enabled),
+            };
+        }
+    }
+
+    /// Handle polling of a single power switch.
+    ///
+    /// Returns whether the power switch is activated.
+    fn handle_power_switch_poll(switch: &mut PowerSwitchState<G>) -> bool {
+        match switch.gpio.read() {
+            Ok(value) => value == GpioValue::Enabled,
+            Err(e) => {
+                error!("Failed to read GPIO for power switch: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Release all the keys and shut down the system
+    fn power_off(&mut self) {
+        warn!("Power switch activated, shutting down system");
+        self.release_all();
+        #[cfg(target_os = "linux")]
+        unsafe {
+            if libc::reboot(libc::LINUX_REBOOT_CMD_POWER_OFF) != 0 {
+                error!(
+                    "Failed to shut down system: {}",
+                    std::io::Error::last_os_error()
+                );
+            } else {
+                info!("System shutdown initiated");
+                // set exit
+                self.exit.store(true, std::sync::atomic::Ordering::SeqCst);
             }
         }
     }
 }
+
+impl<K, G> Drop for InputListener<K, G>
+where
+    K: Keyboard,
+    G: Gpio,
+{
+    fn drop(&mut self) {
+        // make sure no key is left pressed, even if the listener is unwinding
+        self.release_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::{Bias, Concurrency, KeyboardLayout, MacroConfig, MacroStep, Pull};
+    use crate::gpio::MockGpio;
+    use crate::keyboard::MockKeyboard;
+
+    #[test]
+    fn test_should_release_held_keys() {
+        let mut listener = listener(vec![key(17, "A"), key(18, "B")]);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_A, 1)]
+        );
+
+        listener.release_all();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_A, 1), (evdev::KeyCode::KEY_A, 0)]
+        );
+        assert_eq!(listener.keyboards[0].syncs, 1);
+
+        // nothing is left to release
+        listener.release_all();
+        assert_eq!(listener.keyboards[0].events.len(), 2);
+        assert_eq!(listener.keyboards[0].syncs, 1);
+    }
+
+    #[test]
+    fn test_should_release_repeating_keys() {
+        let mut key = key(17, "A");
+        key.repeat = Some(RepeatConfig {
+            delay: Duration::ZERO,
+            rate: Duration::ZERO,
+        });
+        let mut listener = listener(vec![key]);
+        listener.tick();
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_A, 1), (evdev::KeyCode::KEY_A, 2)]
+        );
+
+        listener.release_all();
+        assert_eq!(
+            listener.keyboards[0].events.last(),
+            Some(&(evdev::KeyCode::KEY_A, 0))
+        );
+        assert_eq!(listener.keyboards[0].syncs, 1);
+    }
+
+    #[test]
+    fn test_should_keep_deadlines_elapsed_after_tick() {
+        let mut key = key(17, "A");
+        key.repeat = Some(RepeatConfig {
+            delay: Duration::from_millis(20),
+            rate: Duration::from_millis(20),
+        });
+        let mut listener = listener(vec![key]);
+        let now = listener.tick();
+        std::thread::sleep(Duration::from_millis(30));
+
+        // the repeat delay elapsed after the tick, which must not make the listener wait for an edge
+        assert!(listener.keys[0].next_deadline(now).is_some());
+    }
+
+    #[test]
+    fn test_should_not_flush_without_held_keys() {
+        let mut listener = listener(vec![key(18, "A")]);
+        listener.tick();
+        listener.release_all();
+        assert!(listener.keyboards[0].events.is_empty());
+        assert_eq!(listener.keyboards[0].syncs, 0);
+    }
+
+    #[test]
+    fn test_should_keep_unchanged_keys_on_reload() {
+        let mut listener = listener(vec![key(17, "A"), key(18, "B")]);
+        listener.tick();
+
+        let mut opened = Vec::new();
+        listener
+            .reload(inputs(vec![key(17, "A"), key(19, "C")]), |line| {
+                opened.push(line.gpio);
+                open(line)
+            })
+            .unwrap();
+        assert_eq!(opened, vec![19]);
+        assert_eq!(listener.keys.len(), 2);
+        // key A is still held down and didn't emit anything
+        assert!(listener.keys[0].held_keycode().is_some());
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_A, 1)]
+        );
+    }
+
+    #[test]
+    fn test_should_release_removed_keys_on_reload() {
+        let mut listener = listener(vec![key(17, "A")]);
+        listener.tick();
+
+        listener.reload(inputs(vec![key(18, "B")]), open).unwrap();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_A, 1), (evdev::KeyCode::KEY_A, 0)]
+        );
+        assert_eq!(listener.keyboards[0].syncs, 1);
+        assert_eq!(listener.keys.len(), 1);
+        assert_eq!(listener.keys[0].line.gpio, 18);
+    }
+
+    #[test]
+    fn test_should_release_held_key_on_keycode_change() {
+        let mut listener = listener(vec![key(17, "A")]);
+        listener.tick();
+
+        listener
+            .reload(inputs(vec![key(17, "B")]), |_| panic!("line reopened"))
+            .unwrap();
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::KEY_A, 1),
+                (evdev::KeyCode::KEY_A, 0),
+                (evdev::KeyCode::KEY_B, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_release_repeating_key_when_repeat_is_removed() {
+        let mut repeating = key(17, "A");
+        repeating.repeat = Some(RepeatConfig {
+            delay: Duration::ZERO,
+            rate: Duration::from_secs(3600),
+        });
+        let mut listener = listener(vec![repeating]);
+        listener.tick();
+        listener.tick();
+
+        listener
+            .reload(inputs(vec![key(17, "A")]), |_| panic!("line reopened"))
+            .unwrap();
+        listener.tick();
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::KEY_A, 1),
+                (evdev::KeyCode::KEY_A, 2),
+                (evdev::KeyCode::KEY_A, 0),
+                (evdev::KeyCode::KEY_A, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_keep_held_multi_tap_on_reload() {
+        let mut multi_tap = key(17, "A");
+        multi_tap.multi_tap = Some(MultiTapConfig {
+            keycodes: vec!["A".parse().unwrap(), "B".parse().unwrap()],
+            window: Duration::ZERO,
+        });
+        let mut listener = listener(vec![multi_tap.clone(), key(18, "C")]);
+        listener.tick();
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_A, 1)]
+        );
+
+        let mut changed = key(18, "D");
+        changed.debounce = Duration::from_millis(10);
+        listener
+            .reload(inputs(vec![multi_tap, changed]), |_| {
+                panic!("line reopened")
+            })
+            .unwrap();
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        listener.release_all();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_A, 1), (evdev::KeyCode::KEY_A, 0)]
+        );
+    }
+
+    #[test]
+    fn test_should_reopen_changed_lines_on_reload() {
+        let mut listener = listener(vec![key(17, "A")]);
+        let mut changed = key(17, "A");
+        changed.line.bias = Bias::PullUp;
+
+        let mut opened = Vec::new();
+        listener
+            .reload(inputs(vec![changed]), |line| {
+                opened.push(*line);
+                open(line)
+            })
+            .unwrap();
+        assert_eq!(opened.len(), 1);
+        assert_eq!(opened[0].bias, Bias::PullUp);
+    }
+
+    #[test]
+    fn test_should_report_lines_failing_to_open() {
+        let mut listener = listener(vec![key(17, "A")]);
+        assert!(
+            listener
+                .reload(inputs(vec![key(17, "A"), key(18, "B")]), |_| {
+                    anyhow::bail!("busy")
+                })
+                .is_err()
+        );
+        assert_eq!(listener.keys.len(), 1);
+    }
+
+    #[test]
+    fn test_should_route_keys_to_their_device() {
+        let mut player2 = key(17, "BTN_SOUTH");
+        player2.device = 1;
+        let mut listener = listener(vec![key(18, "A"), player2]);
+        listener.keyboards.push(MockKeyboard::default());
+        listener.tick();
+        assert!(listener.keyboards[0].events.is_empty());
+        assert_eq!(
+            listener.keyboards[1].events,
+            vec![(evdev::KeyCode::BTN_SOUTH, 1)]
+        );
+
+        listener.release_all();
+        assert_eq!(listener.keyboards[0].syncs, 0);
+        assert_eq!(listener.keyboards[1].syncs, 1);
+    }
+
+    #[test]
+    fn test_should_release_held_key_on_device_change() {
+        let mut listener = listener(vec![key(17, "A")]);
+        listener.keyboards.push(MockKeyboard::default());
+        listener.tick();
+
+        let mut moved = key(17, "A");
+        moved.device = 1;
+        listener
+            .reload(inputs(vec![moved]), |_| panic!("line reopened"))
+            .unwrap();
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_A, 1), (evdev::KeyCode::KEY_A, 0)]
+        );
+        assert_eq!(
+            listener.keyboards[1].events,
+            vec![(evdev::KeyCode::KEY_A, 1)]
+        );
+    }
+
+    #[test]
+    fn test_should_emit_combos_as_a_unit() {
+        let mut combo = key(17, "LCTRL+LALT+T");
+        combo.repeat = Some(RepeatConfig {
+            delay: Duration::ZERO,
+            rate: Duration::ZERO,
+        });
+        let mut listener = listener(vec![combo]);
+        listener.tick();
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::KEY_LEFTCTRL, 1),
+                (evdev::KeyCode::KEY_LEFTALT, 1),
+                (evdev::KeyCode::KEY_T, 1),
+                (evdev::KeyCode::KEY_T, 2),
+            ]
+        );
+
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events[4..],
+            [
+                (evdev::KeyCode::KEY_T, 0),
+                (evdev::KeyCode::KEY_LEFTALT, 0),
+                (evdev::KeyCode::KEY_LEFTCTRL, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_tap_or_hold() {
+        let mut tap_hold = key(17, "ENTER");
+        tap_hold.hold = Some(HoldConfig {
+            keycode: "ESC".parse().unwrap(),
+            time: Duration::from_millis(50),
+            permissive: false,
+        });
+        let mut listener = listener(vec![tap_hold]);
+
+        // short press
+        listener.tick();
+        assert!(listener.keyboards[0].events.is_empty());
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::KEY_ENTER, 1),
+                (evdev::KeyCode::KEY_ENTER, 0)
+            ]
+        );
+
+        // long press
+        listener.keyboards[0].events.clear();
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        std::thread::sleep(Duration::from_millis(60));
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_ESC, 1)]
+        );
+        assert!(listener.keys[0].held_keycode().is_some());
+
+        listener.release_all();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_ESC, 1), (evdev::KeyCode::KEY_ESC, 0)]
+        );
+    }
+
+    #[test]
+    fn test_should_resolve_keys_against_layers() {
+        let mut hotkey = key(4, "BTN_MODE");
+        hotkey.layer = Some("hotkey".to_string());
+        let mut start = key(18, "BTN_START");
+        start.layer_keycodes = vec![("hotkey".to_string(), "ESC".parse().unwrap())];
+        let mut listener = listener(vec![hotkey, start]);
+        listener.tick();
+        assert!(listener.keyboards[0].events.is_empty());
+
+        // hotkey + start
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.keys[1].gpio.set(false);
+        listener.tick();
+        // releasing the modifier first still releases the resolved key
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        listener.keys[1].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_ESC, 1), (evdev::KeyCode::KEY_ESC, 0)]
+        );
+
+        // start alone
+        listener.keyboards[0].events.clear();
+        listener.keys[1].gpio.set(false);
+        listener.tick();
+        listener.keys[1].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::BTN_START, 1),
+                (evdev::KeyCode::BTN_START, 0)
+            ]
+        );
+
+        // hotkey tapped alone
+        listener.keyboards[0].events.clear();
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        assert!(listener.keyboards[0].events.is_empty());
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::BTN_MODE, 1), (evdev::KeyCode::BTN_MODE, 0)]
+        );
+    }
+
+    #[test]
+    fn test_should_press_chords() {
+        let keys = vec![key(4, "BTN_SELECT"), key(18, "BTN_START")];
+        let mut listener = listener(keys.clone());
+        let mut config = inputs(keys);
+        config.chords = vec![ChordConfig {
+            gpios: vec![4, 18],
+            keycode: "ESC".parse().unwrap(),
+            device: 0,
+            window: Duration::from_secs(60),
+        }];
+        listener.reload(config.clone(), open).unwrap();
+
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        assert!(listener.keyboards[0].events.is_empty());
+        listener.keys[1].gpio.set(false);
+        listener.tick();
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        listener.keys[1].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_ESC, 1), (evdev::KeyCode::KEY_ESC, 0)]
+        );
+
+        // chords are released on exit, while their keys emit nothing
+        listener.keyboards[0].events.clear();
+        listener.keys[0].gpio.set(false);
+        listener.keys[1].gpio.set(false);
+        listener.tick();
+        listener.release_all();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_ESC, 1), (evdev::KeyCode::KEY_ESC, 0)]
+        );
+        listener.keys[0].gpio.set(true);
+        listener.keys[1].gpio.set(true);
+        listener.tick();
+
+        // fall back to the single key once the window closes
+        config.chords[0].window = Duration::ZERO;
+        listener.reload(config, open).unwrap();
+        listener.keyboards[0].events.clear();
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::BTN_SELECT, 1),
+                (evdev::KeyCode::BTN_SELECT, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_latch_toggle_keys() {
+        let mut mute = key(18, "MUTE");
+        mute.mode = KeyMode::Toggle;
+        let mut listener = listener(vec![mute]);
+
+        for _ in 0..2 {
+            listener.keys[0].gpio.set(false);
+            listener.tick();
+            listener.tick();
+            listener.keys[0].gpio.set(true);
+            listener.tick();
+        }
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_MUTE, 1), (evdev::KeyCode::KEY_MUTE, 0)]
+        );
+
+        // latched keys are released on exit
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert!(listener.keys[0].held_keycode().is_some());
+        listener.release_all();
+        assert_eq!(
+            listener.keyboards[0].events[2..],
+            [(evdev::KeyCode::KEY_MUTE, 1), (evdev::KeyCode::KEY_MUTE, 0)]
+        );
+        assert!(listener.keys[0].held_keycode().is_none());
+    }
+
+    #[test]
+    fn test_should_pulse_turbo_keys_when_switched_on() {
+        let mut fire = key(18, "A");
+        fire.turbo = Some(TurboConfig {
+            down: Duration::ZERO,
+            up: Duration::ZERO,
+            switch: Some(5),
+        });
+        let mut listener = listener(vec![fire.clone()]);
+        let mut config = inputs(vec![fire]);
+        config.turbo_switches = vec![TurboSwitch {
+            line: key(5, "B").line,
+            debounce: Duration::ZERO,
+        }];
+        listener.reload(config, open).unwrap();
+
+        // turbo is off
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.tick();
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_A, 1), (evdev::KeyCode::KEY_A, 0)]
+        );
+
+        // switch turbo on
+        listener.turbo_switches[0].gpio.set(false);
+        listener.tick();
+        listener.turbo_switches[0].gpio.set(true);
+        listener.tick();
+        assert!(listener.keys[0].turbo_enabled);
+
+        listener.keyboards[0].events.clear();
+        listener.keys[0].gpio.set(false);
+        for _ in 0..3 {
+            listener.tick();
+        }
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::KEY_A, 1),
+                (evdev::KeyCode::KEY_A, 0),
+                (evdev::KeyCode::KEY_A, 1),
+                (evdev::KeyCode::KEY_A, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_hold_back_keys_while_tap_hold_key_is_undecided() {
+        let mut listener = listener(vec![tap_hold_key(false), key(22, "A")]);
+        listener.tick();
+
+        // the other key is pressed and released while the tap-hold key is down: it's a tap
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.keys[1].gpio.set(false);
+        listener.tick();
+        listener.keys[1].gpio.set(true);
+        listener.tick();
+        assert!(listener.keyboards[0].events.is_empty());
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::KEY_SPACE, 1),
+                (evdev::KeyCode::KEY_SPACE, 0),
+                (evdev::KeyCode::KEY_A, 1),
+                (evdev::KeyCode::KEY_A, 0)
+            ]
+        );
+
+        // the tap-hold key is released while the other key is down
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.keys[1].gpio.set(false);
+        listener.tick();
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events[4..],
+            [
+                (evdev::KeyCode::KEY_SPACE, 1),
+                (evdev::KeyCode::KEY_SPACE, 0),
+                (evdev::KeyCode::KEY_A, 1)
+            ]
+        );
+        listener.keys[1].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events[7..],
+            [(evdev::KeyCode::KEY_A, 0)]
+        );
+    }
+
+    #[test]
+    fn test_should_hold_tap_hold_keys_with_permissive_hold() {
+        let mut listener = listener(vec![tap_hold_key(true), key(22, "A")]);
+        listener.tick();
+
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.keys[1].gpio.set(false);
+        listener.tick();
+        assert!(listener.keyboards[0].events.is_empty());
+        listener.keys[1].gpio.set(true);
+        listener.tick();
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::KEY_LEFTSHIFT, 1),
+                (evdev::KeyCode::KEY_A, 1),
+                (evdev::KeyCode::KEY_A, 0),
+                (evdev::KeyCode::KEY_LEFTSHIFT, 0)
+            ]
+        );
+
+        // the held back events are emitted on exit
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.keys[1].gpio.set(false);
+        listener.tick();
+        listener.release_all();
+        assert_eq!(
+            listener.keyboards[0].events[4..],
+            [(evdev::KeyCode::KEY_A, 1), (evdev::KeyCode::KEY_A, 0)]
+        );
+        assert!(!listener.keys[0].is_undecided());
+    }
+
+    #[test]
+    fn test_should_play_back_macros() {
+        let mut menu = key(18, "M");
+        menu.key_macro = Some(MacroConfig {
+            name: "menu".to_string(),
+            steps: vec![
+                MacroStep::Keys("ESC".parse().unwrap()),
+                MacroStep::Delay {
+                    delay_ms: 3_600_000,
+                },
+                MacroStep::Keys("ENTER".parse().unwrap()),
+            ],
+        });
+        let mut listener = listener(vec![menu]);
+        listener.tick();
+
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![(evdev::KeyCode::KEY_ESC, 1), (evdev::KeyCode::KEY_ESC, 0)]
+        );
+        assert!(listener.macros.next_deadline().is_some());
+
+        // pressed again while playing back, the macro is cancelled
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        assert!(listener.macros.next_deadline().is_none());
+
+        // macro keys don't hold anything down
+        listener.release_all();
+        assert_eq!(listener.keyboards[0].events.len(), 2);
+    }
+
+    #[test]
+    fn test_should_type_text() {
+        let text_key: crate::config::KeyConfig =
+            toml::from_str("gpio = 18\nkeycode = \"L\"\ntext = \"Hi@z\"").unwrap();
+        let mut login = key(18, "L");
+        login.key_macro = text_key.text_macro(KeyboardLayout::De);
+        let mut listener = listener(vec![login]);
+        listener.tick();
+
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.tick();
+        assert_eq!(
+            listener.keyboards[0].events,
+            vec![
+                (evdev::KeyCode::KEY_LEFTSHIFT, 1),
+                (evdev::KeyCode::KEY_H, 1),
+                (evdev::KeyCode::KEY_H, 0),
+                (evdev::KeyCode::KEY_LEFTSHIFT, 0),
+                (evdev::KeyCode::KEY_I, 1),
+                (evdev::KeyCode::KEY_I, 0),
+                (evdev::KeyCode::KEY_RIGHTALT, 1),
+                (evdev::KeyCode::KEY_Q, 1),
+                (evdev::KeyCode::KEY_Q, 0),
+                (evdev::KeyCode::KEY_RIGHTALT, 0),
+                (evdev::KeyCode::KEY_Y, 1),
+                (evdev::KeyCode::KEY_Y, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_run_commands_without_emitting_keys() {
+        let output = tempfile::NamedTempFile::new().unwrap();
+        let command = format!(
+            "echo \"$GPIO2KEY_GPIO $GPIO2KEY_EVENT\" >> '{}'",
+            output.path().display()
+        );
+        let mut screenshot = key(18, "SYSRQ");
+        screenshot.exec = Some(ExecConfig {
+            on_press: Some(command.clone()),
+            on_release: Some(command),
+            on_hold: None,
+            hold: Duration::from_secs(3600),
+            concurrency: Concurrency::Queue,
+            timeout: None,
+        });
+        let mut listener = listener(vec![screenshot]);
+        listener.tick();
+
+        listener.keys[0].gpio.set(false);
+        listener.tick();
+        listener.tick();
+        listener.keys[0].gpio.set(true);
+        listener.tick();
+        assert!(listener.keys[0].held_keycode().is_none());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while listener.keys[0].next_deadline(Instant::now()).is_some() {
+            assert!(Instant::now() < deadline, "commands still running");
+            std::thread::sleep(Duration::from_millis(10));
+            listener.tick();
+        }
+        assert!(listener.keyboards[0].events.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(output.path()).unwrap(),
+            "18 press\n18 release\n"
+        );
+    }
+
+    /// Tap-hold key on GPIO 18, emitting SPACE when tapped and LEFTSHIFT when held
+    fn tap_hold_key(permissive: bool) -> KeyConfig {
+        let mut space = key(18, "SPACE");
+        space.mode = KeyMode::TapHold;
+        space.hold = Some(HoldConfig {
+            keycode: "LEFTSHIFT".parse().unwrap(),
+            time: Duration::from_secs(3600),
+            permissive,
+        });
+
+        space
+    }
+
+    /// Open a mock GPIO; GPIO 17 is enabled, any other is disabled
+    fn open(line: &LineConfig) -> anyhow::Result<MockGpio> {
+        Ok(MockGpio::new(line.gpio != 17, true))
+    }
+
+    fn key(gpio: u8, keycode: &str) -> KeyConfig {
+        let keycode = keycode.parse::<KeyCombo>().unwrap();
+        KeyConfig {
+            line: LineConfig {
+                gpio,
+                active_low: true,
+                bias: Bias::AsIs,
+                pull: Pull::Up,
+                edge_events: false,
+            },
+            name: keycode.to_string(),
+            keycode: Some(keycode),
+            device: 0,
+            debounce: Duration::ZERO,
+            mode: KeyMode::Momentary,
+            repeat: None,
+            hold: None,
+            turbo: None,
+            multi_tap: None,
+            layer: None,
+            layer_keycodes: vec![],
+            key_macro: None,
+            exec: None,
+        }
+    }
+
+    fn inputs(keys: Vec<KeyConfig>) -> InputsConfig {
+        InputsConfig {
+            keys,
+            chords: vec![],
+            turbo_switches: vec![],
+            power_switches: vec![],
+            mode: InputMode::Poll,
+            poll_interval: Duration::from_millis(5),
+        }
+    }
+
+    fn listener(keys: Vec<KeyConfig>) -> InputListener<MockKeyboard, MockGpio> {
+        InputListener::try_new(
+            InputListenerConfig {
+                exit: Arc::new(AtomicBool::default()),
+                reload: Arc::new(AtomicBool::default()),
+                waker: Arc::new(Waker::try_new().unwrap()),
+                keyboards: vec![MockKeyboard::default()],
+                inputs: inputs(keys),
+            },
+            open,
+        )
+        .unwrap()
+    }
+}
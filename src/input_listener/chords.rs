@@ -0,0 +1,314 @@
+use std::time::Instant;
+
+use crate::input_listener::ChordConfig;
+use crate::input_listener::state::OutEvent;
+
+/// Event to emit once the key events went through chord detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChordEvent {
+    /// Emit `event` for the key on `gpio`
+    Key { gpio: u8, event: OutEvent },
+    /// Press the keys of the chord with the given index
+    Press(usize),
+    /// Release the keys of the chord with the given index
+    Release(usize),
+}
+
+/// Chord detection, layered over the [`OutEvent`]s of the keys.
+///
+/// The events of the keys of a chord are held back when the first of them goes down:
+/// if all of them go down within the chord window, the chord is pressed instead, until any of them
+/// is released; otherwise the held back events are emitted for the individual keys.
+#[derive(Debug, Default)]
+pub struct Chords {
+    chords: Vec<Chord>,
+}
+
+#[derive(Debug)]
+struct Chord {
+    config: ChordConfig,
+    state: ChordState,
+}
+
+/// State of a [`Chord`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ChordState {
+    /// None of the keys is held back
+    Idle,
+    /// The `down` keys went down, and their `events` are held back until the window closes
+    Pending {
+        since: Instant,
+        down: Vec<u8>,
+        events: Vec<(u8, OutEvent)>,
+    },
+    /// All the keys went down and the chord is pressed; `down` are the keys still down
+    Pressed { down: Vec<u8> },
+    /// The chord has been released; its events are dropped until the `down` keys are released
+    Released { down: Vec<u8> },
+}
+
+impl Chords {
+    pub fn new(configs: Vec<ChordConfig>) -> Self {
+        Chords {
+            chords: configs
+                .into_iter()
+                .map(|config| Chord {
+                    config,
+                    state: ChordState::Idle,
+                })
+                .collect(),
+        }
+    }
+
+    /// Configuration of the chord with index `chord`
+    pub fn config(&self, chord: usize) -> &ChordConfig {
+        &self.chords[chord].config
+    }
+
+    /// Handle the `event` of the key on `gpio` at `now`, returning the events to emit
+    pub fn handle(&mut self, gpio: u8, event: OutEvent, now: Instant) -> Vec<ChordEvent> {
+        let Some(index) = self
+            .chords
+            .iter()
+            .position(|chord| chord.config.gpios.contains(&gpio))
+        else {
+            return vec![ChordEvent::Key { gpio, event }];
+        };
+        let chord = &mut self.chords[index];
+
+        match (&mut chord.state, event) {
+            (ChordState::Idle, OutEvent::Press) => {
+                chord.state = ChordState::Pending {
+                    since: now,
+                    down: vec![gpio],
+                    events: vec![(gpio, event)],
+                };
+                Vec::new()
+            }
+            // keys released after the window closed
+            (ChordState::Idle, _) => vec![ChordEvent::Key { gpio, event }],
+            (ChordState::Pending { down, events, .. }, OutEvent::Press) => {
+                down.push(gpio);
+                if chord.config.gpios.iter().all(|gpio| down.contains(gpio)) {
+                    chord.state = ChordState::Pressed {
+                        down: std::mem::take(down),
+                    };
+                    vec![ChordEvent::Press(index)]
+                } else {
+                    // the event is kept, to be emitted if the chord isn't completed
+                    events.push((gpio, event));
+                    Vec::new()
+                }
+            }
+            (ChordState::Pending { events, .. }, OutEvent::Release) => {
+                // a key released before the chord is completed: it's not a chord
+                let mut events = std::mem::take(events);
+                events.push((gpio, event));
+                chord.state = ChordState::Idle;
+                events
+                    .into_iter()
+                    .map(|(gpio, event)| ChordEvent::Key { gpio, event })
+                    .collect()
+            }
+            (ChordState::Pending { events, .. }, _) => {
+                events.push((gpio, event));
+                Vec::new()
+            }
+            (ChordState::Pressed { down }, OutEvent::Release) => {
+                down.retain(|key| *key != gpio);
+                chord.state = ChordState::Released {
+                    down: std::mem::take(down),
+                };
+                chord.settle();
+                vec![ChordEvent::Release(index)]
+            }
+            (ChordState::Released { down }, OutEvent::Release) => {
+                down.retain(|key| *key != gpio);
+                chord.settle();
+                Vec::new()
+            }
+            (ChordState::Released { down }, OutEvent::Press) => {
+                down.push(gpio);
+                Vec::new()
+            }
+            (ChordState::Pressed { .. } | ChordState::Released { .. }, _) => Vec::new(),
+        }
+    }
+
+    /// Close the windows elapsed at `now`, returning the held back events to emit
+    pub fn expire(&mut self, now: Instant) -> Vec<ChordEvent> {
+        let mut expired = Vec::new();
+        for chord in &mut self.chords {
+            if let ChordState::Pending { since, events, .. } = &mut chord.state {
+                if now.duration_since(*since) >= chord.config.window {
+                    expired.extend(
+                        events
+                            .drain(..)
+                            .map(|(gpio, event)| ChordEvent::Key { gpio, event }),
+                    );
+                    chord.state = ChordState::Idle;
+                }
+            }
+        }
+
+        expired
+    }
+
+    /// Whether the events of the key on `gpio` are held back or dropped by a chord
+    pub fn is_captured(&self, gpio: u8) -> bool {
+        self.chords.iter().any(|chord| match &chord.state {
+            ChordState::Idle => false,
+            ChordState::Pending { down, .. }
+            | ChordState::Pressed { down }
+            | ChordState::Released { down } => down.contains(&gpio),
+        })
+    }
+
+    /// Abort the chords in progress, returning the indexes of the ones which were pressed
+    pub fn reset(&mut self) -> Vec<usize> {
+        let mut pressed = Vec::new();
+        for (index, chord) in self.chords.iter_mut().enumerate() {
+            if matches!(chord.state, ChordState::Pressed { .. }) {
+                pressed.push(index);
+            }
+            chord.state = ChordState::Idle;
+        }
+
+        pressed
+    }
+
+    /// Next instant at which a chord window closes, if any
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.chords
+            .iter()
+            .filter_map(|chord| match &chord.state {
+                ChordState::Pending { since, .. } => Some(*since + chord.config.window),
+                _ => None,
+            })
+            .min()
+    }
+}
+
+impl Chord {
+    /// Go back to idle once all the keys are released
+    fn settle(&mut self) {
+        if matches!(&self.state, ChordState::Released { down } if down.is_empty()) {
+            self.state = ChordState::Idle;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_utils::at;
+
+    const WINDOW: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn test_should_press_chord() {
+        let start = Instant::now();
+        let mut chords = chords();
+
+        assert!(chords.handle(4, OutEvent::Press, start).is_empty());
+        assert!(chords.is_captured(4));
+        assert_eq!(
+            chords.handle(17, OutEvent::Press, at(start, 30)),
+            vec![ChordEvent::Press(0)]
+        );
+        assert!(chords.expire(at(start, 100)).is_empty());
+        assert_eq!(
+            chords.handle(4, OutEvent::Release, at(start, 200)),
+            vec![ChordEvent::Release(0)]
+        );
+        // the events of the keys still down are dropped
+        assert!(
+            chords
+                .handle(17, OutEvent::Repeat, at(start, 300))
+                .is_empty()
+        );
+        assert!(
+            chords
+                .handle(17, OutEvent::Release, at(start, 400))
+                .is_empty()
+        );
+        assert!(!chords.is_captured(17));
+    }
+
+    #[test]
+    fn test_should_fall_back_to_keys_when_window_closes() {
+        let start = Instant::now();
+        let mut chords = chords();
+
+        assert!(chords.handle(4, OutEvent::Press, start).is_empty());
+        assert_eq!(chords.next_deadline(), Some(start + WINDOW));
+        assert!(chords.expire(at(start, 49)).is_empty());
+        assert_eq!(chords.expire(at(start, 50)), vec![key(4, OutEvent::Press)]);
+        assert!(!chords.is_captured(4));
+        assert_eq!(chords.next_deadline(), None);
+
+        // the other key starts a new chord
+        assert!(chords.handle(17, OutEvent::Press, at(start, 60)).is_empty());
+        assert_eq!(
+            chords.expire(at(start, 110)),
+            vec![key(17, OutEvent::Press)]
+        );
+        assert_eq!(
+            chords.handle(4, OutEvent::Release, at(start, 200)),
+            vec![key(4, OutEvent::Release)]
+        );
+    }
+
+    #[test]
+    fn test_should_fall_back_to_keys_on_release() {
+        let start = Instant::now();
+        let mut chords = chords();
+
+        chords.handle(4, OutEvent::Press, start);
+        assert_eq!(
+            chords.handle(4, OutEvent::Release, at(start, 20)),
+            vec![key(4, OutEvent::Press), key(4, OutEvent::Release)]
+        );
+        assert!(chords.expire(at(start, 100)).is_empty());
+    }
+
+    #[test]
+    fn test_should_pass_through_other_keys() {
+        let start = Instant::now();
+        let mut chords = chords();
+
+        assert_eq!(
+            chords.handle(22, OutEvent::Press, start),
+            vec![key(22, OutEvent::Press)]
+        );
+        assert!(!chords.is_captured(22));
+    }
+
+    #[test]
+    fn test_should_reset_chords() {
+        let start = Instant::now();
+        let mut chords = chords();
+
+        chords.handle(4, OutEvent::Press, start);
+        chords.handle(17, OutEvent::Press, start);
+        assert_eq!(chords.reset(), vec![0]);
+        assert!(!chords.is_captured(4));
+        assert!(chords.reset().is_empty());
+    }
+
+    fn chords() -> Chords {
+        Chords::new(vec![ChordConfig {
+            gpios: vec![4, 17],
+            keycode: "ESC".parse().unwrap(),
+            device: 0,
+            window: WINDOW,
+        }])
+    }
+
+    fn key(gpio: u8, event: OutEvent) -> ChordEvent {
+        ChordEvent::Key { gpio, event }
+    }
+}
@@ -2,44 +2,137 @@ use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use std::time::Duration;
 
-use crate::config::Keycode;
-use crate::gpio::Gpio;
+use crate::config::{Concurrency, InputMode, KeyCombo, KeyMode, MacroConfig};
+use crate::gpio::LineConfig;
+use crate::input_listener::Waker;
 use crate::keyboard::Keyboard;
 
 /// Configuration for an individual key binding
-pub struct KeyConfig<GPIO>
-where
-    GPIO: Gpio,
-{
-    pub gpio: GPIO,
-    pub keycode: Keycode,
+#[derive(Debug, Clone)]
+pub struct KeyConfig {
+    pub line: LineConfig,
+    /// Keys emitted by the key; macro, text and exec keys may have none
+    pub keycode: Option<KeyCombo>,
+    /// Name of the key in the logs
+    pub name: String,
+    /// Index of the output device the key emits on
+    pub device: usize,
     pub debounce: Duration,
+    pub mode: KeyMode,
     pub repeat: Option<RepeatConfig>,
+    pub hold: Option<HoldConfig>,
+    pub turbo: Option<TurboConfig>,
+    pub multi_tap: Option<MultiTapConfig>,
+    /// Layer activated while the key is held, making it a layer modifier
+    pub layer: Option<String>,
+    /// Keys emitted instead of `keycode` while a layer is active, by layer name
+    pub layer_keycodes: Vec<(String, KeyCombo)>,
+    /// Macro played back when the key is pressed, instead of emitting `keycode`
+    pub key_macro: Option<MacroConfig>,
+    /// Commands run by the key, instead of emitting `keycode`
+    pub exec: Option<ExecConfig>,
 }
 
 /// Configuration for key auto-repeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RepeatConfig {
     pub delay: Duration,
     pub rate: Duration,
 }
 
+/// Configuration for the action of a key held down
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoldConfig {
+    /// Keys emitted instead of the key ones when it's held
+    pub keycode: KeyCombo,
+    /// Time the key must be held
+    pub time: Duration,
+    /// Whether a tap-hold key is held as soon as another key is pressed and released while it's down
+    pub permissive: bool,
+}
+
+/// Configuration for turbo, pressing and releasing a key repeatedly while it's held
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurboConfig {
+    /// Time the key is held down in each pulse
+    pub down: Duration,
+    /// Time the key is released in each pulse
+    pub up: Duration,
+    /// GPIO of the switch turning turbo on and off, if any
+    pub switch: Option<u8>,
+}
+
+/// Configuration for multi-tap detection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiTapConfig {
+    /// Keys emitted for one, two, three... taps
+    pub keycodes: Vec<KeyCombo>,
+    /// Time after a release within which the next tap must start
+    pub window: Duration,
+}
+
+/// Configuration for the commands run by a key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecConfig {
+    pub on_press: Option<String>,
+    pub on_release: Option<String>,
+    pub on_hold: Option<String>,
+    /// Time the key must be held to run `on_hold`
+    pub hold: Duration,
+    pub concurrency: Concurrency,
+    /// Time after which a running command is killed, if any
+    pub timeout: Option<Duration>,
+}
+
+/// Configuration for a chord: keys pressed together to emit a different key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChordConfig {
+    /// GPIOs of the keys of the chord
+    pub gpios: Vec<u8>,
+    pub keycode: KeyCombo,
+    /// Index of the output device the chord emits on
+    pub device: usize,
+    /// Time within which all the keys must be pressed
+    pub window: Duration,
+}
+
 /// Configuration for an individual power switch
-pub struct PowerSwitch<GPIO>
-where
-    GPIO: Gpio,
-{
-    pub gpio: GPIO,
+#[derive(Debug, Clone)]
+pub struct PowerSwitch {
+    pub line: LineConfig,
+}
+
+/// Configuration for a turbo switch, turning turbo on and off at every press
+#[derive(Debug, Clone)]
+pub struct TurboSwitch {
+    pub line: LineConfig,
+    pub debounce: Duration,
+}
+
+/// Configuration of the inputs handled by the listener.
+///
+/// It can be replaced at runtime with [`super::InputListener::reload`].
+#[derive(Debug, Clone)]
+pub struct InputsConfig {
+    pub keys: Vec<KeyConfig>,
+    pub chords: Vec<ChordConfig>,
+    pub turbo_switches: Vec<TurboSwitch>,
+    pub power_switches: Vec<PowerSwitch>,
+    pub mode: InputMode,
+    pub poll_interval: Duration,
 }
 
 /// Configuration for the input listener
-pub struct InputListenerConfig<K, GPIO>
+pub struct InputListenerConfig<K>
 where
     K: Keyboard,
-    GPIO: Gpio,
 {
+    /// Set to stop the listener
     pub exit: Arc<AtomicBool>,
-    pub keyboard: K,
-    pub keys: Vec<KeyConfig<GPIO>>,
-    pub power_switches: Vec<PowerSwitch<GPIO>>,
-    pub poll_interval: Duration,
+    /// Set to make [`super::InputListener::run`] return, so the configuration can be reloaded
+    pub reload: Arc<AtomicBool>,
+    pub waker: Arc<Waker>,
+    /// Output devices, indexed by [`KeyConfig::device`]
+    pub keyboards: Vec<K>,
+    pub inputs: InputsConfig,
 }
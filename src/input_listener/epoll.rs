@@ -0,0 +1,100 @@
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+
+/// Maximum amount of events returned by a single [`Epoll::wait`]
+const MAX_EVENTS: usize = 32;
+
+/// Minimal safe wrapper around a level-triggered epoll instance
+pub struct Epoll {
+    fd: OwnedFd,
+}
+
+impl Epoll {
+    /// Create a new [`Epoll`] instance
+    pub fn try_new() -> anyhow::Result<Self> {
+        let fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if fd < 0 {
+            anyhow::bail!("Failed to create epoll: {}", io::Error::last_os_error());
+        }
+
+        Ok(Epoll {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Register `fd` for readability; `token` is returned by [`Epoll::wait`] when it's ready.
+    pub fn add(&self, fd: RawFd, token: u64) -> anyhow::Result<()> {
+        let mut event = libc::epoll_event {
+            events: libc::EPOLLIN as u32,
+            u64: token,
+        };
+        if unsafe { libc::epoll_ctl(self.fd.as_raw_fd(), libc::EPOLL_CTL_ADD, fd, &mut event) } < 0
+        {
+            anyhow::bail!(
+                "Failed to register fd {fd} in epoll: {}",
+                io::Error::last_os_error()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Wait until any of the registered fds is readable or `timeout` elapses.
+    ///
+    /// If `timeout` is `None`, waits indefinitely.
+    /// Returns the tokens of the ready fds; an interrupted wait returns no tokens.
+    pub fn wait(&self, timeout: Option<Duration>) -> anyhow::Result<Vec<u64>> {
+        let timeout_ms = match timeout {
+            // round up, so we never wake up before the deadline
+            Some(timeout) => timeout
+                .as_micros()
+                .div_ceil(1000)
+                .min(libc::c_int::MAX as u128) as libc::c_int,
+            None => -1,
+        };
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; MAX_EVENTS];
+        let n = unsafe {
+            libc::epoll_wait(
+                self.fd.as_raw_fd(),
+                events.as_mut_ptr(),
+                MAX_EVENTS as libc::c_int,
+                timeout_ms,
+            )
+        };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            anyhow::bail!("Failed to wait on epoll: {err}");
+        }
+
+        Ok(events[..n as usize].iter().map(|event| event.u64).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::input_listener::waker::Waker;
+
+    #[test]
+    fn test_should_wait_for_events() {
+        let epoll = Epoll::try_new().unwrap();
+        let waker = Waker::try_new().unwrap();
+        epoll.add(waker.as_raw_fd(), 42).unwrap();
+
+        assert!(epoll.wait(Some(Duration::ZERO)).unwrap().is_empty());
+        waker.wake();
+        assert_eq!(epoll.wait(Some(Duration::ZERO)).unwrap(), vec![42]);
+        waker.reset();
+        assert!(
+            epoll
+                .wait(Some(Duration::from_millis(1)))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
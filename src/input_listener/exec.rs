@@ -0,0 +1,464 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::Concurrency;
+use crate::input_listener::ExecConfig;
+
+/// Interval at which a running command is checked for completion and timeout
+const REAP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Key event running a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Press,
+    Release,
+    Hold,
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Press => write!(f, "press"),
+            Self::Release => write!(f, "release"),
+            Self::Hold => write!(f, "hold"),
+        }
+    }
+}
+
+/// Commands run by a key.
+///
+/// Commands are spawned in their own process group and reaped at every tick, so they never block
+/// the listener; their output is logged line by line by a thread per stream.
+#[derive(Debug)]
+pub struct Exec {
+    pub config: ExecConfig,
+    /// GPIO of the key, exposed to the commands
+    gpio: u8,
+    running: Option<Running>,
+    /// Commands waiting for the running one to finish, with [`Concurrency::Queue`]; each trigger is
+    /// queued at most once
+    queue: VecDeque<Trigger>,
+    /// Instant the key was pressed, until its hold command is run
+    pressed: Option<Instant>,
+}
+
+/// A command being run
+#[derive(Debug)]
+struct Running {
+    child: Child,
+    trigger: Trigger,
+    started: Instant,
+}
+
+impl Exec {
+    pub fn new(gpio: u8, config: ExecConfig) -> Self {
+        Exec {
+            config,
+            gpio,
+            running: None,
+            queue: VecDeque::new(),
+            pressed: None,
+        }
+    }
+
+    /// Run the press command, and start timing the hold
+    pub fn press(&mut self, now: Instant) {
+        if self.config.on_hold.is_some() {
+            self.pressed = Some(now);
+        }
+        self.trigger(Trigger::Press, now);
+    }
+
+    /// Run the release command, cancelling the hold if it hasn't been run yet
+    pub fn release(&mut self, now: Instant) {
+        self.pressed = None;
+        self.trigger(Trigger::Release, now);
+    }
+
+    /// Forget the key is pressed, without running any command
+    pub fn reset(&mut self) {
+        self.pressed = None;
+    }
+
+    /// Reap the running command, killing it if it timed out, then run the queued commands and the
+    /// hold command, if due at `now`.
+    ///
+    /// It must be called on every tick.
+    pub fn poll(&mut self, now: Instant) {
+        if let Some(running) = &mut self.running {
+            match running.child.try_wait() {
+                Ok(Some(status)) if status.success() => {
+                    info!("GPIO {}: {} command finished", self.gpio, running.trigger);
+                    self.running = None;
+                }
+                Ok(Some(status)) => {
+                    warn!(
+                        "GPIO {}: {} command failed: {status}",
+                        self.gpio, running.trigger
+                    );
+                    self.running = None;
+                }
+                Ok(None) => {
+                    if let Some(timeout) = self
+                        .config
+                        .timeout
+                        .filter(|timeout| now.duration_since(running.started) >= *timeout)
+                    {
+                        warn!(
+                            "GPIO {}: {} command timed out after {timeout:?}",
+                            self.gpio, running.trigger
+                        );
+                        self.kill();
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        "GPIO {}: failed to wait for {} command: {e}",
+                        self.gpio, running.trigger
+                    );
+                    self.running = None;
+                }
+            }
+        }
+        while self.running.is_none() {
+            let Some(trigger) = self.queue.pop_front() else {
+                break;
+            };
+            self.spawn(trigger, now);
+        }
+        if self
+            .pressed
+            .is_some_and(|pressed| now.duration_since(pressed) >= self.config.hold)
+        {
+            self.pressed = None;
+            self.trigger(Trigger::Hold, now);
+        }
+    }
+
+    /// Next instant at which the commands must be polled, if any
+    pub fn next_deadline(&self, now: Instant) -> Option<Instant> {
+        let reap = self.is_busy().then(|| now + REAP_INTERVAL);
+        let hold = self.pressed.map(|pressed| pressed + self.config.hold);
+
+        reap.into_iter().chain(hold).min()
+    }
+
+    /// Run the command for `trigger`, if any, according to the concurrency policy
+    fn trigger(&mut self, trigger: Trigger, now: Instant) {
+        if self.command(trigger).is_none() {
+            return;
+        }
+        if let Some(running) = &self.running {
+            match self.config.concurrency {
+                Concurrency::Ignore => {
+                    info!(
+                        "GPIO {}: {trigger} command ignored, {} command still running",
+                        self.gpio, running.trigger
+                    );
+                    return;
+                }
+                Concurrency::Queue if self.queue.contains(&trigger) => {
+                    info!(
+                        "GPIO {}: {trigger} command ignored, already queued",
+                        self.gpio
+                    );
+                    return;
+                }
+                Concurrency::Queue => {
+                    debug!("GPIO {}: {trigger} command queued", self.gpio);
+                    self.queue.push_back(trigger);
+                    return;
+                }
+                Concurrency::Kill => {
+                    info!(
+                        "GPIO {}: killing {} command to run {trigger} command",
+                        self.gpio, running.trigger
+                    );
+                    self.kill();
+                }
+            }
+        }
+        self.spawn(trigger, now);
+    }
+
+    /// Spawn the command for `trigger` with `sh`, logging its output
+    fn spawn(&mut self, trigger: Trigger, now: Instant) {
+        let Some(command) = self.command(trigger) else {
+            return;
+        };
+        info!("GPIO {}: running {trigger} command `{command}`", self.gpio);
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("GPIO2KEY_GPIO", self.gpio.to_string())
+            .env("GPIO2KEY_EVENT", trigger.to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // so it can be killed along with its children
+            .process_group(0)
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                error!("GPIO {}: failed to run {trigger} command: {e}", self.gpio);
+                return;
+            }
+        };
+        let label = format!("GPIO {}: {trigger} command", self.gpio);
+        if let Some(stdout) = child.stdout.take() {
+            log_output(stdout, label.clone(), log::Level::Info);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            log_output(stderr, label, log::Level::Warn);
+        }
+        self.running = Some(Running {
+            child,
+            trigger,
+            started: now,
+        });
+    }
+
+    /// Kill the process group of the running command, if any
+    fn kill(&mut self) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+        if unsafe { libc::kill(-(running.child.id() as libc::pid_t), libc::SIGKILL) } < 0 {
+            error!(
+                "GPIO {}: failed to kill {} command: {}",
+                self.gpio,
+                running.trigger,
+                std::io::Error::last_os_error()
+            );
+        }
+        reap(running.child);
+    }
+
+    fn command(&self, trigger: Trigger) -> Option<&str> {
+        match trigger {
+            Trigger::Press => self.config.on_press.as_deref(),
+            Trigger::Release => self.config.on_release.as_deref(),
+            Trigger::Hold => self.config.on_hold.as_deref(),
+        }
+    }
+
+    /// Whether a command is running or queued
+    fn is_busy(&self) -> bool {
+        self.running.is_some() || !self.queue.is_empty()
+    }
+}
+
+impl Drop for Exec {
+    fn drop(&mut self) {
+        // don't leave the command running once the key is gone, or its commands changed
+        self.kill();
+    }
+}
+
+/// Log every line of `output` with `level`, from a separate thread
+fn log_output<R>(output: R, label: String, level: log::Level)
+where
+    R: Read + Send + 'static,
+{
+    let result = std::thread::Builder::new()
+        .name("exec-output".to_string())
+        .spawn(move || {
+            for line in BufReader::new(output).lines().map_while(Result::ok) {
+                log!(level, "{label}: {line}");
+            }
+        });
+    if let Err(e) = result {
+        error!("Failed to spawn thread logging command output: {e}");
+    }
+}
+
+/// Wait for `child` to exit from a separate thread
+fn reap(mut child: Child) {
+    let result = std::thread::Builder::new()
+        .name("exec-reaper".to_string())
+        .spawn(move || child.wait());
+    if let Err(e) = result {
+        error!("Failed to spawn thread waiting for command: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::test_utils::at;
+
+    #[test]
+    fn test_should_run_commands_with_event_environment() {
+        let output = NamedTempFile::new().unwrap();
+        let mut exec = Exec::new(
+            17,
+            config(
+                &append(&output, "$GPIO2KEY_GPIO $GPIO2KEY_EVENT"),
+                Concurrency::Queue,
+            ),
+        );
+        let start = Instant::now();
+
+        exec.press(start);
+        exec.poll(at(start, 49));
+        exec.poll(at(start, 50));
+        exec.release(at(start, 60));
+        wait(&mut exec);
+        assert_eq!(
+            std::fs::read_to_string(output.path()).unwrap(),
+            "17 press\n17 hold\n17 release\n"
+        );
+    }
+
+    #[test]
+    fn test_should_not_run_hold_command_if_released_before_hold_time() {
+        let output = NamedTempFile::new().unwrap();
+        let mut exec = Exec::new(
+            17,
+            config(&append(&output, "$GPIO2KEY_EVENT"), Concurrency::Queue),
+        );
+        let start = Instant::now();
+
+        exec.press(start);
+        assert_eq!(exec.next_deadline(start), Some(at(start, 50)));
+        exec.release(at(start, 49));
+        wait(&mut exec);
+        exec.poll(at(start, 50));
+        wait(&mut exec);
+        assert_eq!(exec.next_deadline(start), None);
+        assert_eq!(
+            std::fs::read_to_string(output.path()).unwrap(),
+            "press\nrelease\n"
+        );
+    }
+
+    #[test]
+    fn test_should_ignore_commands_while_running() {
+        let output = NamedTempFile::new().unwrap();
+        let mut exec = Exec::new(
+            17,
+            config(
+                &format!("sleep 0.2; {}", append(&output, "$GPIO2KEY_EVENT")),
+                Concurrency::Ignore,
+            ),
+        );
+
+        exec.press(Instant::now());
+        exec.release(Instant::now());
+        wait(&mut exec);
+        assert_eq!(std::fs::read_to_string(output.path()).unwrap(), "press\n");
+    }
+
+    #[test]
+    fn test_should_kill_running_command() {
+        let output = NamedTempFile::new().unwrap();
+        let mut exec = Exec::new(
+            17,
+            config(
+                &format!(
+                    "[ $GPIO2KEY_EVENT = press ] && sleep 10; {}",
+                    append(&output, "$GPIO2KEY_EVENT")
+                ),
+                Concurrency::Kill,
+            ),
+        );
+        let start = Instant::now();
+
+        exec.press(start);
+        exec.release(Instant::now());
+        wait(&mut exec);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(std::fs::read_to_string(output.path()).unwrap(), "release\n");
+    }
+
+    #[test]
+    fn test_should_not_queue_the_same_command_twice() {
+        let output = NamedTempFile::new().unwrap();
+        let mut config = config(
+            &format!("sleep 0.2; {}", append(&output, "$GPIO2KEY_EVENT")),
+            Concurrency::Queue,
+        );
+        config.on_hold = None;
+        let mut exec = Exec::new(17, config);
+
+        exec.press(Instant::now());
+        for _ in 0..3 {
+            exec.release(Instant::now());
+            exec.press(Instant::now());
+        }
+        assert_eq!(exec.queue, vec![Trigger::Release, Trigger::Press]);
+        wait(&mut exec);
+        assert_eq!(
+            std::fs::read_to_string(output.path()).unwrap(),
+            "press\nrelease\npress\n"
+        );
+    }
+
+    #[test]
+    fn test_should_kill_running_command_on_drop() {
+        let output = NamedTempFile::new().unwrap();
+        let mut config = config(
+            &format!("sleep 0.2; {}", append(&output, "$GPIO2KEY_EVENT")),
+            Concurrency::Ignore,
+        );
+        config.on_release = None;
+        config.on_hold = None;
+        let mut exec = Exec::new(17, config);
+
+        exec.press(Instant::now());
+        drop(exec);
+        std::thread::sleep(Duration::from_millis(400));
+        assert_eq!(std::fs::read_to_string(output.path()).unwrap(), "");
+    }
+
+    #[test]
+    fn test_should_kill_command_on_timeout() {
+        let mut config = config("sleep 10", Concurrency::Ignore);
+        config.on_release = None;
+        config.on_hold = None;
+        config.timeout = Some(Duration::from_millis(50));
+        let mut exec = Exec::new(17, config);
+        let start = Instant::now();
+
+        exec.press(start);
+        exec.poll(at(start, 49));
+        assert!(exec.is_busy());
+        exec.poll(at(start, 50));
+        assert!(!exec.is_busy());
+    }
+
+    /// Configuration running `command` on press, release and after 50ms of hold
+    fn config(command: &str, concurrency: Concurrency) -> ExecConfig {
+        ExecConfig {
+            on_press: Some(command.to_string()),
+            on_release: Some(command.to_string()),
+            on_hold: Some(command.to_string()),
+            hold: Duration::from_millis(50),
+            concurrency,
+            timeout: None,
+        }
+    }
+
+    /// Command appending `text` to `output`
+    fn append(output: &NamedTempFile, text: &str) -> String {
+        format!("echo \"{text}\" >> '{}'", output.path().display())
+    }
+
+    /// Poll `exec` until its commands are finished
+    fn wait(exec: &mut Exec) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while exec.is_busy() {
+            assert!(Instant::now() < deadline, "commands still running");
+            std::thread::sleep(Duration::from_millis(10));
+            exec.poll(Instant::now());
+        }
+    }
+}
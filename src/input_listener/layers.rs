@@ -0,0 +1,111 @@
+use crate::config::KeyCombo;
+
+/// Layers activated by the layer modifier keys which are held down
+#[derive(Debug, Default)]
+pub struct Layers {
+    /// Active layers, in activation order
+    active: Vec<ActiveLayer>,
+}
+
+#[derive(Debug)]
+struct ActiveLayer {
+    name: String,
+    /// Whether another key has been pressed while the layer was active
+    used: bool,
+}
+
+impl Layers {
+    /// Activate the layer `name`
+    pub fn activate(&mut self, name: &str) {
+        self.deactivate(name);
+        self.active.push(ActiveLayer {
+            name: name.to_string(),
+            used: false,
+        });
+    }
+
+    /// Deactivate the layer `name`.
+    ///
+    /// Returns whether another key has been pressed while the layer was active.
+    pub fn deactivate(&mut self, name: &str) -> bool {
+        match self.active.iter().position(|layer| layer.name == name) {
+            Some(pos) => self.active.remove(pos).used,
+            None => false,
+        }
+    }
+
+    /// Resolve the keys of a key being pressed against the active layers, marking them as used.
+    ///
+    /// Returns the keys mapped by `layer_keycodes` to the most recently activated layer, if any.
+    pub fn resolve<'a>(
+        &mut self,
+        layer_keycodes: &'a [(String, KeyCombo)],
+    ) -> Option<&'a KeyCombo> {
+        for layer in &mut self.active {
+            layer.used = true;
+        }
+
+        self.active.iter().rev().find_map(|layer| {
+            layer_keycodes
+                .iter()
+                .find(|(name, _)| *name == layer.name)
+                .map(|(_, keycode)| keycode)
+        })
+    }
+
+    /// Keep only the active layers for which `keep` returns true
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.active.retain(|layer| keep(&layer.name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_resolve_active_layers() {
+        let layer_keycodes = vec![
+            ("hotkey".to_string(), "ESC".parse::<KeyCombo>().unwrap()),
+            ("fn".to_string(), "F1".parse::<KeyCombo>().unwrap()),
+        ];
+        let mut layers = Layers::default();
+        assert_eq!(layers.resolve(&layer_keycodes), None);
+
+        layers.activate("hotkey");
+        assert_eq!(
+            layers.resolve(&layer_keycodes).map(ToString::to_string),
+            Some("KEY_ESC".to_string())
+        );
+        // the most recent layer wins
+        layers.activate("fn");
+        assert_eq!(
+            layers.resolve(&layer_keycodes).map(ToString::to_string),
+            Some("KEY_F1".to_string())
+        );
+        assert_eq!(layers.resolve(&[]), None);
+
+        assert!(layers.deactivate("fn"));
+        assert!(!layers.deactivate("fn"));
+        assert_eq!(
+            layers.resolve(&layer_keycodes).map(ToString::to_string),
+            Some("KEY_ESC".to_string())
+        );
+    }
+
+    #[test]
+    fn test_should_tell_whether_layer_was_used() {
+        let mut layers = Layers::default();
+        layers.activate("hotkey");
+        assert!(!layers.deactivate("hotkey"));
+
+        layers.activate("hotkey");
+        layers.resolve(&[]);
+        assert!(layers.deactivate("hotkey"));
+
+        layers.activate("hotkey");
+        layers.retain(|name| name != "hotkey");
+        assert!(!layers.deactivate("hotkey"));
+    }
+}
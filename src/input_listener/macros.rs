@@ -0,0 +1,151 @@
+use std::time::{Duration, Instant};
+
+use crate::config::{KeyCombo, MacroConfig, MacroStep};
+
+/// Macros being played back, advanced at every tick so they never block the listener
+#[derive(Debug, Default)]
+pub struct Macros {
+    playing: Vec<Playback>,
+}
+
+/// A macro being played back by a key
+#[derive(Debug)]
+struct Playback {
+    /// GPIO of the key playing back the macro
+    gpio: u8,
+    /// Index of the output device the macro emits on
+    device: usize,
+    config: MacroConfig,
+    /// Index of the next step to play back
+    next: usize,
+    /// Instant at which the next step is due
+    resume: Instant,
+}
+
+impl Macros {
+    /// Start playing back `config` for the key on `gpio` at `now`, or cancel it if the key is
+    /// already playing it back.
+    ///
+    /// Returns whether the macro has been started.
+    pub fn toggle(&mut self, gpio: u8, device: usize, config: &MacroConfig, now: Instant) -> bool {
+        if let Some(pos) = self.playing.iter().position(|p| p.gpio == gpio) {
+            self.playing.remove(pos);
+            return false;
+        }
+        self.playing.push(Playback {
+            gpio,
+            device,
+            config: config.clone(),
+            next: 0,
+            resume: now,
+        });
+
+        true
+    }
+
+    /// Play back the steps due at `now`, returning the keys to tap, in order, with their device.
+    ///
+    /// Delays start when they're reached, so the keys are never closer than the configured delays,
+    /// even if the listener is late.
+    pub fn advance(&mut self, now: Instant) -> Vec<(usize, KeyCombo)> {
+        let mut taps = Vec::new();
+        for playback in &mut self.playing {
+            while playback.resume <= now {
+                let Some(step) = playback.config.steps.get(playback.next) else {
+                    break;
+                };
+                playback.next += 1;
+                match step {
+                    MacroStep::Delay { delay_ms } => {
+                        playback.resume = now + Duration::from_millis(*delay_ms);
+                    }
+                    MacroStep::Keys(keycode) => taps.push((playback.device, keycode.clone())),
+                }
+            }
+        }
+        self.playing.retain(|playback| {
+            let done = playback.next == playback.config.steps.len();
+            if done {
+                info!("Macro {} finished", playback.config.name);
+            }
+            !done
+        });
+
+        taps
+    }
+
+    /// Cancel every macro being played back
+    pub fn reset(&mut self) {
+        for playback in self.playing.drain(..) {
+            info!("Macro {} cancelled", playback.config.name);
+        }
+    }
+
+    /// Next instant at which a step is due, if any
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.playing.iter().map(|playback| playback.resume).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::test_utils::at;
+
+    #[test]
+    fn test_should_play_back_macro() {
+        let start = Instant::now();
+        let mut macros = Macros::default();
+
+        assert!(macros.toggle(17, 1, &menu(), start));
+        assert_eq!(macros.advance(start), vec![(1, key("ESC"))]);
+        assert_eq!(
+            macros.next_deadline(),
+            Some(start + Duration::from_millis(100))
+        );
+        assert!(macros.advance(at(start, 99)).is_empty());
+        assert_eq!(
+            macros.advance(at(start, 100)),
+            vec![(1, key("DOWN")), (1, key("ENTER"))]
+        );
+        assert_eq!(macros.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_should_cancel_macro_on_toggle() {
+        let start = Instant::now();
+        let mut macros = Macros::default();
+
+        assert!(macros.toggle(17, 0, &menu(), start));
+        assert_eq!(macros.advance(start), vec![(0, key("ESC"))]);
+        // another key plays back its own macro
+        assert!(macros.toggle(18, 0, &menu(), start));
+        assert!(!macros.toggle(17, 0, &menu(), at(start, 50)));
+        assert_eq!(macros.advance(at(start, 50)), vec![(0, key("ESC"))]);
+        assert_eq!(
+            macros.advance(at(start, 150)),
+            vec![(0, key("DOWN")), (0, key("ENTER"))]
+        );
+
+        macros.toggle(17, 0, &menu(), at(start, 200));
+        macros.reset();
+        assert!(macros.advance(at(start, 200)).is_empty());
+    }
+
+    fn menu() -> MacroConfig {
+        MacroConfig {
+            name: "menu".to_string(),
+            steps: vec![
+                MacroStep::Keys(key("ESC")),
+                MacroStep::Delay { delay_ms: 100 },
+                MacroStep::Keys(key("DOWN")),
+                MacroStep::Keys(key("ENTER")),
+            ],
+        }
+    }
+
+    fn key(keycode: &str) -> KeyCombo {
+        keycode.parse().unwrap()
+    }
+}
@@ -0,0 +1,252 @@
+use std::time::Instant;
+
+use crate::config::KeyCombo;
+use crate::input_listener::MultiTapConfig;
+use crate::input_listener::state::OutEvent;
+
+/// Multi-tap detection, layered over the [`OutEvent`]s of a key.
+///
+/// Taps are buffered until the tap window closes after the last release, then the keys mapped
+/// to the number of taps are tapped. If the key is still pressed when the window closes,
+/// the mapped keys are pressed instead, and released with the key.
+#[derive(Debug)]
+pub struct MultiTap {
+    pub config: MultiTapConfig,
+    state: Taps,
+    /// Number of taps of the last emitted event
+    emitted: usize,
+}
+
+/// Taps buffered by [`MultiTap`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Taps {
+    /// No tap in progress
+    Idle,
+    /// The key is pressed for the `count`th time
+    Down { count: usize, since: Instant },
+    /// The key has been released after `count` taps
+    Up { count: usize, since: Instant },
+    /// The key has been held after `count` taps, and the mapped keys are pressed
+    Held { count: usize },
+}
+
+impl MultiTap {
+    pub fn new(config: MultiTapConfig) -> Self {
+        MultiTap {
+            config,
+            state: Taps::Idle,
+            emitted: 0,
+        }
+    }
+
+    /// Handle the `event` of the key at `now`, returning the [`OutEvent`] to emit
+    /// for the keys returned by [`MultiTap::keycode`].
+    ///
+    /// It must be called on every tick, even without events, so the tap window can close.
+    pub fn handle(&mut self, event: OutEvent, now: Instant) -> OutEvent {
+        let window = self.config.window;
+        let max_taps = self.config.keycodes.len();
+
+        let (state, out_event) = match (self.state, event) {
+            (Taps::Idle, OutEvent::Press) => (
+                Taps::Down {
+                    count: 1,
+                    since: now,
+                },
+                OutEvent::None,
+            ),
+            (Taps::Up { count, .. }, OutEvent::Press) => (
+                Taps::Down {
+                    count: count + 1,
+                    since: now,
+                },
+                OutEvent::None,
+            ),
+            // the last mapped tap doesn't need to wait for the window to close
+            (Taps::Down { count, .. }, OutEvent::Release) if count >= max_taps => {
+                (Taps::Idle, OutEvent::Tap)
+            }
+            (Taps::Down { count, .. }, OutEvent::Release) => {
+                (Taps::Up { count, since: now }, OutEvent::None)
+            }
+            (Taps::Down { count, since }, _) if now.duration_since(since) >= window => {
+                (Taps::Held { count }, OutEvent::Press)
+            }
+            (Taps::Up { since, .. }, _) if now.duration_since(since) >= window => {
+                (Taps::Idle, OutEvent::Tap)
+            }
+            (Taps::Held { .. }, OutEvent::Release) => (Taps::Idle, OutEvent::Release),
+            (state, _) => (state, OutEvent::None),
+        };
+
+        if out_event != OutEvent::None {
+            self.emitted = self.count();
+        }
+        self.state = state;
+
+        out_event
+    }
+
+    /// Keys mapped to the number of taps of the last emitted event
+    pub fn keycode(&self) -> &KeyCombo {
+        &self.config.keycodes[self.emitted.saturating_sub(1)]
+    }
+
+    /// Whether the mapped keys are pressed
+    pub fn is_held(&self) -> bool {
+        matches!(self.state, Taps::Held { .. })
+    }
+
+    /// Abort the taps in progress
+    pub fn reset(&mut self) {
+        self.state = Taps::Idle;
+    }
+
+    /// Next instant after `now` at which the tap window closes, if taps are in progress
+    pub fn next_deadline(&self, now: Instant) -> Option<Instant> {
+        match self.state {
+            Taps::Down { since, .. } | Taps::Up { since, .. } => {
+                Some(since + self.config.window).filter(|deadline| *deadline > now)
+            }
+            Taps::Idle | Taps::Held { .. } => None,
+        }
+    }
+
+    /// Number of taps in progress
+    fn count(&self) -> usize {
+        match self.state {
+            Taps::Idle => 0,
+            Taps::Down { count, .. } | Taps::Up { count, .. } | Taps::Held { count } => count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_utils::at;
+
+    const WINDOW: Duration = Duration::from_millis(250);
+
+    #[test]
+    fn test_should_emit_single_tap_when_window_closes() {
+        let start = Instant::now();
+        let mut multi_tap = multi_tap();
+
+        assert_eq!(multi_tap.handle(OutEvent::Press, start), OutEvent::None);
+        assert_eq!(
+            multi_tap.handle(OutEvent::Release, at(start, 50)),
+            OutEvent::None
+        );
+        assert_eq!(
+            multi_tap.handle(OutEvent::None, at(start, 299)),
+            OutEvent::None
+        );
+        assert_eq!(
+            multi_tap.handle(OutEvent::None, at(start, 300)),
+            OutEvent::Tap
+        );
+        assert_eq!(multi_tap.keycode().to_string(), "KEY_A");
+        assert_eq!(
+            multi_tap.handle(OutEvent::None, at(start, 1000)),
+            OutEvent::None
+        );
+    }
+
+    #[test]
+    fn test_should_emit_double_tap() {
+        let start = Instant::now();
+        let mut multi_tap = multi_tap();
+
+        multi_tap.handle(OutEvent::Press, start);
+        multi_tap.handle(OutEvent::Release, at(start, 50));
+        assert_eq!(
+            multi_tap.handle(OutEvent::Press, at(start, 200)),
+            OutEvent::None
+        );
+        assert_eq!(
+            multi_tap.handle(OutEvent::Release, at(start, 250)),
+            OutEvent::None
+        );
+        assert_eq!(
+            multi_tap.handle(OutEvent::None, at(start, 500)),
+            OutEvent::Tap
+        );
+        assert_eq!(multi_tap.keycode().to_string(), "KEY_B");
+    }
+
+    #[test]
+    fn test_should_emit_last_tap_on_release() {
+        let start = Instant::now();
+        let mut multi_tap = multi_tap();
+
+        for i in 0..2 {
+            multi_tap.handle(OutEvent::Press, at(start, i * 100));
+            multi_tap.handle(OutEvent::Release, at(start, i * 100 + 50));
+        }
+        multi_tap.handle(OutEvent::Press, at(start, 200));
+        assert_eq!(
+            multi_tap.handle(OutEvent::Release, at(start, 250)),
+            OutEvent::Tap
+        );
+        assert_eq!(multi_tap.keycode().to_string(), "KEY_C");
+        assert_eq!(multi_tap.next_deadline(at(start, 250)), None);
+    }
+
+    #[test]
+    fn test_should_press_mapped_keys_when_held() {
+        let start = Instant::now();
+        let mut multi_tap = multi_tap();
+
+        multi_tap.handle(OutEvent::Press, start);
+        multi_tap.handle(OutEvent::Release, at(start, 50));
+        multi_tap.handle(OutEvent::Press, at(start, 100));
+        assert_eq!(
+            multi_tap.handle(OutEvent::None, at(start, 350)),
+            OutEvent::Press
+        );
+        assert!(multi_tap.is_held());
+        assert_eq!(multi_tap.keycode().to_string(), "KEY_B");
+        assert_eq!(
+            multi_tap.handle(OutEvent::None, at(start, 1000)),
+            OutEvent::None
+        );
+        assert_eq!(
+            multi_tap.handle(OutEvent::Release, at(start, 1100)),
+            OutEvent::Release
+        );
+        assert!(!multi_tap.is_held());
+        assert_eq!(multi_tap.keycode().to_string(), "KEY_B");
+    }
+
+    #[test]
+    fn test_should_get_next_deadline() {
+        let start = Instant::now();
+        let mut multi_tap = multi_tap();
+        assert_eq!(multi_tap.next_deadline(start), None);
+
+        multi_tap.handle(OutEvent::Press, start);
+        assert_eq!(multi_tap.next_deadline(start), Some(start + WINDOW));
+        multi_tap.handle(OutEvent::Release, at(start, 50));
+        assert_eq!(
+            multi_tap.next_deadline(at(start, 50)),
+            Some(at(start, 50) + WINDOW)
+        );
+
+        multi_tap.reset();
+        assert_eq!(multi_tap.next_deadline(at(start, 50)), None);
+    }
+
+    fn multi_tap() -> MultiTap {
+        MultiTap::new(MultiTapConfig {
+            keycodes: ["A", "B", "C"]
+                .iter()
+                .map(|keycode| keycode.parse().unwrap())
+                .collect(),
+            window: WINDOW,
+        })
+    }
+}
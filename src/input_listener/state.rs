@@ -1,8 +1,10 @@
 use std::time::{Duration, Instant};
 
-use crate::config::Keycode;
-use crate::gpio::{Gpio, GpioValue};
-use crate::input_listener::{KeyConfig, RepeatConfig};
+use crate::config::{KeyCombo, KeyMode, MacroConfig};
+use crate::gpio::{Gpio, GpioValue, LineConfig};
+use crate::input_listener::exec::Exec;
+use crate::input_listener::multi_tap::MultiTap;
+use crate::input_listener::{HoldConfig, KeyConfig, RepeatConfig, TurboConfig};
 
 /// Configuration for an individual key binding
 pub struct KeyState<GPIO>
@@ -10,9 +12,33 @@ where
     GPIO: Gpio,
 {
     pub gpio: GPIO,
-    pub keycode: Keycode,
+    pub line: LineConfig,
+    pub keycode: Option<KeyCombo>,
+    /// Name of the key in the logs
+    pub name: String,
+    /// Index of the output device the key emits on
+    pub device: usize,
     pub debounce: Duration,
+    pub mode: KeyMode,
+    /// Whether a toggle key is latched, holding its keys down
+    pub latched: bool,
     pub repeat: Option<RepeatConfig>,
+    pub hold: Option<HoldConfig>,
+    pub turbo: Option<TurboConfig>,
+    /// Whether turbo is switched on; always true without a turbo switch
+    pub turbo_enabled: bool,
+    /// Multi-tap detection, layered over `state`
+    pub multi_tap: Option<MultiTap>,
+    /// Layer activated while the key is held, making it a layer modifier
+    pub layer: Option<String>,
+    /// Keys emitted instead of `keycode` while a layer is active, by layer name
+    pub layer_keycodes: Vec<(String, KeyCombo)>,
+    /// Keys resolved against the active layers when the key was pressed
+    pub resolved: Option<KeyCombo>,
+    /// Macro played back when the key is pressed, instead of emitting `keycode`
+    pub key_macro: Option<MacroConfig>,
+    /// Commands run by the key, instead of emitting `keycode`
+    pub exec: Option<Exec>,
     pub state: State,
 }
 
@@ -20,47 +46,216 @@ impl<G> KeyState<G>
 where
     G: Gpio,
 {
+    /// Create a new [`KeyState`] for the key described by `config`, reading from `gpio`
+    pub fn new(config: KeyConfig, gpio: G) -> Self {
+        let line = config.line.gpio;
+        KeyState {
+            gpio,
+            line: config.line,
+            keycode: config.keycode,
+            name: config.name,
+            device: config.device,
+            debounce: config.debounce,
+            mode: config.mode,
+            latched: false,
+            repeat: config.repeat,
+            hold: config.hold,
+            turbo: config.turbo,
+            turbo_enabled: true,
+            multi_tap: config.multi_tap.map(MultiTap::new),
+            layer: config.layer,
+            layer_keycodes: config.layer_keycodes,
+            resolved: None,
+            key_macro: config.key_macro,
+            exec: config.exec.map(|exec| Exec::new(line, exec)),
+            state: State::Unknown,
+        }
+    }
+
     /// Handle an event for the gpio and update the state accordingly.
     ///
     /// Returns the [`OutEvent`] generated by this state change.
     pub fn handle_gpio_value(&mut self, value: GpioValue) -> OutEvent {
-        let (new_state, out_event) =
-            self.state
-                .handle_gpio_value(value, self.repeat.as_ref(), self.debounce);
+        let (new_state, out_event) = self.state.handle_gpio_value(
+            value,
+            self.repeat.as_ref(),
+            self.hold_time(),
+            self.active_turbo(),
+            self.debounce,
+        );
         if out_event != OutEvent::None {
             debug!(
-                "Key {keycode:?} state change: {old_state:?} -> {new_state:?}, event: {out_event:?}",
-                keycode = self.keycode,
+                "Key {name} state change: {old_state:?} -> {new_state:?}, event: {out_event:?}",
+                name = &self.name,
                 old_state = self.state,
             );
         }
         self.state = new_state;
 
+        match &mut self.multi_tap {
+            Some(multi_tap) => multi_tap.handle(out_event, Instant::now()),
+            None => out_event,
+        }
+    }
+
+    /// Keys emitted by [`OutEvent::Press`], [`OutEvent::Release`], [`OutEvent::Repeat`] and [`OutEvent::Tap`],
+    /// if any
+    pub fn out_keycode(&self) -> Option<&KeyCombo> {
+        match (&self.resolved, &self.multi_tap) {
+            (Some(resolved), _) => Some(resolved),
+            (None, Some(multi_tap)) => Some(multi_tap.keycode()),
+            (None, None) => self.keycode.as_ref(),
+        }
+    }
+
+    /// Whether the key is physically pressed, regardless of what it emitted
+    pub fn is_pressed(&self) -> bool {
+        !matches!(self.state, State::Unknown | State::Released { .. })
+    }
+
+    /// Keys currently held down by this key, if any
+    pub fn held_keycode(&self) -> Option<&KeyCombo> {
+        if self.layer.is_some() || self.key_macro.is_some() || self.exec.is_some() {
+            // layer modifiers, macro and exec keys don't emit anything while held
+            return None;
+        }
+        if self.mode == KeyMode::Toggle {
+            return self.latched.then(|| self.out_keycode()).flatten();
+        }
+        if let Some(multi_tap) = &self.multi_tap {
+            return multi_tap.is_held().then(|| self.out_keycode()).flatten();
+        }
+        match self.state {
+            State::Pressed { .. } | State::Repeating { .. } => self.out_keycode(),
+            State::Held { .. } => self.hold.as_ref().map(|hold| &hold.keycode),
+            State::Turbo { down: true, .. } => self.out_keycode(),
+            State::Unknown
+            | State::Released { .. }
+            | State::Pending { .. }
+            | State::Turbo { down: false, .. } => None,
+        }
+    }
+
+    /// Whether the key is a tap-hold key which is down, but not yet resolved as tapped or held
+    pub fn is_undecided(&self) -> bool {
+        self.mode == KeyMode::TapHold && matches!(self.state, State::Pending { .. })
+    }
+
+    /// Resolve an undecided tap-hold key as held before the hold time elapses.
+    ///
+    /// Returns the [`OutEvent`] to emit.
+    pub fn resolve_hold(&mut self) -> OutEvent {
+        let (new_state, out_event) = self.state.resolve_hold();
+        debug!(
+            "Key {name} resolved as held: {old_state:?} -> {new_state:?}",
+            name = &self.name,
+            old_state = self.state,
+        );
+        self.state = new_state;
+
         out_event
     }
+
+    /// Force the key into the released state, regardless of the GPIO value
+    pub fn release(&mut self) {
+        self.state = State::Released {
+            since: Instant::now(),
+        };
+        if let Some(multi_tap) = &mut self.multi_tap {
+            multi_tap.reset();
+        }
+        if let Some(exec) = &mut self.exec {
+            exec.reset();
+        }
+        self.latched = false;
+        self.resolved = None;
+    }
+
+    /// Next instant after `now` at which this key may change state without any GPIO edge
+    pub fn next_deadline(&self, now: Instant) -> Option<Instant> {
+        let deadline = self.state.next_deadline(
+            self.repeat.as_ref(),
+            self.hold_time(),
+            self.active_turbo(),
+            self.debounce,
+            now,
+        );
+        let multi_tap_deadline = self
+            .multi_tap
+            .as_ref()
+            .and_then(|multi_tap| multi_tap.next_deadline(now));
+        let exec_deadline = self.exec.as_ref().and_then(|exec| exec.next_deadline(now));
+
+        deadline
+            .into_iter()
+            .chain(multi_tap_deadline)
+            .chain(exec_deadline)
+            .min()
+    }
+
+    /// Time the key must be held to emit the hold keycode, if configured
+    fn hold_time(&self) -> Option<Duration> {
+        self.hold.as_ref().map(|hold| hold.time)
+    }
+
+    /// Turbo configuration, if configured and switched on
+    fn active_turbo(&self) -> Option<&TurboConfig> {
+        self.turbo.as_ref().filter(|_| self.turbo_enabled)
+    }
 }
 
-impl<GPIO> From<KeyConfig<GPIO>> for KeyState<GPIO>
+/// An opened turbo switch, turning turbo on and off for the keys using it at every press
+pub struct TurboSwitchState<GPIO>
 where
     GPIO: Gpio,
 {
-    fn from(config: KeyConfig<GPIO>) -> Self {
-        KeyState {
-            gpio: config.gpio,
-            keycode: config.keycode,
-            debounce: config.debounce,
-            repeat: config.repeat,
-            state: State::Unknown,
+    pub gpio: GPIO,
+    pub line: LineConfig,
+    pub debounce: Duration,
+    pub enabled: bool,
+    pub state: State,
+}
+
+impl<G> TurboSwitchState<G>
+where
+    G: Gpio,
+{
+    /// Handle the value of the switch GPIO, returning whether turbo has been switched on or off
+    pub fn handle_gpio_value(&mut self, value: GpioValue) -> bool {
+        let (state, event) = self
+            .state
+            .handle_gpio_value(value, None, None, None, self.debounce);
+        self.state = state;
+        if event == OutEvent::Press {
+            self.enabled = !self.enabled;
+            return true;
         }
+
+        false
     }
 }
 
+/// An opened power switch
+pub struct PowerSwitchState<GPIO>
+where
+    GPIO: Gpio,
+{
+    pub gpio: GPIO,
+    pub line: LineConfig,
+}
+
 /// Output event generated by a key state change
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OutEvent {
     Press,
     Release,
     Repeat,
+    /// The key has been released before the hold time: press and release the key
+    Tap,
+    /// The key has been held for the hold time: press the hold keycode
+    HoldPress,
+    /// The key has been released after the hold time: release the hold keycode
+    HoldRelease,
     None,
 }
 
@@ -75,24 +270,70 @@ pub enum State {
     Pressed { since: Instant },
     /// The key is being repeated
     Repeating { last_repeat: Instant },
+    /// The key is pressed, but nothing is emitted until it is either released or held for the hold time
+    Pending { since: Instant },
+    /// The key has been held for the hold time, and the hold keycode is pressed
+    Held { since: Instant },
+    /// The key is pressed with turbo, and its keycode is pulsed; `pulse` is the start of the current
+    /// pulse phase, with the keycode held `down` or released
+    Turbo {
+        since: Instant,
+        pulse: Instant,
+        down: bool,
+    },
 }
 
 impl State {
     /// Handle an event and return the new state and the [`OutEvent`]
     ///
     /// This function works as a state machine transition handler.
+    /// If `hold` is set, the output of a press is deferred until the key is released
+    /// ([`OutEvent::Tap`]) or held for `hold` ([`OutEvent::HoldPress`]).
+    /// If `turbo` is set, the key is pressed and released repeatedly while held.
     pub fn handle_gpio_value(
         self,
         value: GpioValue,
         repeat: Option<&RepeatConfig>,
+        hold: Option<Duration>,
+        turbo: Option<&TurboConfig>,
         debounce: Duration,
     ) -> (Self, OutEvent) {
         match value {
-            GpioValue::Enabled => self.handle_gpio_enabled(repeat, debounce),
+            GpioValue::Enabled => self.handle_gpio_enabled(repeat, hold, turbo, debounce),
             GpioValue::Disabled => self.handle_gpio_disabled(debounce),
         }
     }
 
+    /// Resolve a pending key as held, regardless of the hold time
+    pub fn resolve_hold(self) -> (Self, OutEvent) {
+        match self {
+            Self::Pending { .. } => (
+                Self::Held {
+                    since: Instant::now(),
+                },
+                OutEvent::HoldPress,
+            ),
+            _ => (self, OutEvent::None),
+        }
+    }
+
+    /// State entered when the key is pressed, with the [`OutEvent`] of the press
+    fn press(hold: Option<Duration>, turbo: Option<&TurboConfig>) -> (Self, OutEvent) {
+        let since = Instant::now();
+        match (hold, turbo) {
+            (Some(_), _) => (Self::Pending { since }, OutEvent::None),
+            (None, Some(_)) => (
+                Self::Turbo {
+                    since,
+                    pulse: since,
+                    down: true,
+                },
+                OutEvent::Press,
+            ),
+            (None, None) => (Self::Pressed { since }, OutEvent::Press),
+        }
+    }
+
     /// Handle the GPIO being in the disabled state
     fn handle_gpio_disabled(self, debounce: Duration) -> (Self, OutEvent) {
         match self {
@@ -114,6 +355,33 @@ impl State {
                 },
                 OutEvent::Release,
             ),
+            // if pending and debounced, it's a tap
+            Self::Pending { .. } if self.is_debounced(debounce) => (
+                Self::Released {
+                    since: Instant::now(),
+                },
+                OutEvent::Tap,
+            ),
+            // if held and debounced, release the hold keycode
+            Self::Held { .. } if self.is_debounced(debounce) => (
+                Self::Released {
+                    since: Instant::now(),
+                },
+                OutEvent::HoldRelease,
+            ),
+            // if turbo and debounced, release the keycode if it's down
+            Self::Turbo { down, .. } if self.is_debounced(debounce) => (
+                Self::Released {
+                    since: Instant::now(),
+                },
+                if down {
+                    OutEvent::Release
+                } else {
+                    OutEvent::None
+                },
+            ),
+            // if pending, held or turbo, but not debounced, stay
+            Self::Pending { .. } | Self::Held { .. } | Self::Turbo { .. } => (self, OutEvent::None),
             // if unknown, go to released
             Self::Unknown => (
                 Self::Released {
@@ -128,39 +396,35 @@ impl State {
     fn handle_gpio_enabled(
         self,
         repeat: Option<&RepeatConfig>,
+        hold: Option<Duration>,
+        turbo: Option<&TurboConfig>,
         debounce: Duration,
     ) -> (Self, OutEvent) {
         // this matches ONLY with the enabled state. In any other case, we short-circuit to Released
         match self {
-            // if released, and debounced, go to pressed
-            Self::Released { .. } if self.is_debounced(debounce) => (
-                Self::Pressed {
-                    since: Instant::now(),
-                },
-                OutEvent::Press,
-            ),
+            // if released, and debounced, go to pressed (or pending, if hold is configured)
+            Self::Released { .. } if self.is_debounced(debounce) => Self::press(hold, turbo),
             // if released, but not debounced, stay released
             Self::Released { .. } => (self, OutEvent::None),
-            // if pressed, go to repeating or stay pressed
-            Self::Pressed { since } if repeat.is_some() => {
-                let repeat = repeat.expect("can't be empty");
-                // if enough time has passed, go to repeating
-                if since.elapsed() >= repeat.delay {
-                    (
-                        Self::Repeating {
-                            last_repeat: Instant::now(),
-                        },
-                        OutEvent::Repeat,
-                    )
-                } else {
-                    (self, OutEvent::None)
-                }
+            // if pressed, and enough time has passed, go to repeating
+            Self::Pressed { since }
+                if repeat.is_some_and(|repeat| since.elapsed() >= repeat.delay) =>
+            {
+                (
+                    Self::Repeating {
+                        last_repeat: Instant::now(),
+                    },
+                    OutEvent::Repeat,
+                )
             }
-            // if pressed, but no repeat configured, stay pressed
+            // if pressed, but no repeat configured or not yet due, stay pressed
             Self::Pressed { .. } => (self, OutEvent::None),
             // if repeating, check if it's time for another repeat
             Self::Repeating { last_repeat } => {
-                let repeat = repeat.expect("can't be empty");
+                let Some(repeat) = repeat else {
+                    // repeat has been turned off by a reload: keep the key pressed
+                    return (Self::Pressed { since: last_repeat }, OutEvent::None);
+                };
                 if last_repeat.elapsed() >= repeat.rate {
                     (
                         Self::Repeating {
@@ -172,20 +436,89 @@ impl State {
                     (self, OutEvent::None)
                 }
             }
-            // if unknown, go to pressed
-            Self::Unknown => (
-                Self::Pressed {
-                    since: Instant::now(),
-                },
-                OutEvent::Press,
-            ),
+            // if pending, go to held once the hold time elapses
+            Self::Pending { since } => match hold {
+                Some(hold) if since.elapsed() >= hold => (
+                    Self::Held {
+                        since: Instant::now(),
+                    },
+                    OutEvent::HoldPress,
+                ),
+                _ => (self, OutEvent::None),
+            },
+            // if held, stay held; the hold keycode doesn't repeat
+            Self::Held { .. } => (self, OutEvent::None),
+            // if turbo, flip the keycode at the end of each pulse phase
+            Self::Turbo { since, pulse, down } => {
+                let phase = match turbo {
+                    Some(turbo) if down => turbo.down,
+                    Some(turbo) => turbo.up,
+                    // turbo has been switched off: keep the keycode down
+                    None if down => return (self, OutEvent::None),
+                    None => Duration::ZERO,
+                };
+                if pulse.elapsed() < phase {
+                    return (self, OutEvent::None);
+                }
+                let state = Self::Turbo {
+                    since,
+                    pulse: Instant::now(),
+                    down: !down,
+                };
+                if down {
+                    (state, OutEvent::Release)
+                } else {
+                    (state, OutEvent::Press)
+                }
+            }
+            // if unknown, go to pressed (or pending, if hold is configured)
+            Self::Unknown => Self::press(hold, turbo),
         }
     }
 
+    /// Get the next instant after `now` at which the state may change even if the GPIO value doesn't,
+    /// because the debounce window, the hold time, a repeat interval or a turbo pulse phase elapses.
+    pub fn next_deadline(
+        &self,
+        repeat: Option<&RepeatConfig>,
+        hold: Option<Duration>,
+        turbo: Option<&TurboConfig>,
+        debounce: Duration,
+        now: Instant,
+    ) -> Option<Instant> {
+        let deadlines = match self {
+            Self::Unknown => [None, None],
+            Self::Released { since } => [Some(*since + debounce), None],
+            Self::Pressed { since } => [
+                Some(*since + debounce),
+                repeat.map(|repeat| *since + repeat.delay),
+            ],
+            Self::Repeating { last_repeat } => {
+                [repeat.map(|repeat| *last_repeat + repeat.rate), None]
+            }
+            Self::Pending { since } => [Some(*since + debounce), hold.map(|hold| *since + hold)],
+            Self::Held { since } => [Some(*since + debounce), None],
+            Self::Turbo { since, pulse, down } => [
+                Some(*since + debounce),
+                turbo.map(|turbo| *pulse + if *down { turbo.down } else { turbo.up }),
+            ],
+        };
+
+        deadlines
+            .into_iter()
+            .flatten()
+            .filter(|deadline| *deadline > now)
+            .min()
+    }
+
     /// Check if the current state has been stable for at least the debounce duration
     fn is_debounced(&self, debounce: Duration) -> bool {
         match self {
-            Self::Pressed { since } | Self::Released { since } => since.elapsed() >= debounce,
+            Self::Pressed { since }
+            | Self::Released { since }
+            | Self::Pending { since }
+            | Self::Held { since }
+            | Self::Turbo { since, .. } => since.elapsed() >= debounce,
             Self::Repeating { .. } => false, // handled within repeating logic
             Self::Unknown => false,
         }
@@ -204,8 +537,13 @@ mod tests {
         let state = State::Released {
             since: now - Duration::from_millis(200),
         };
-        let (new_state, out_event) =
-            state.handle_gpio_value(GpioValue::Enabled, None, Duration::from_millis(100));
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Enabled,
+            None,
+            None,
+            None,
+            Duration::from_millis(100),
+        );
         assert!(
             matches!(new_state, State::Pressed { .. }),
             "Expected state to be Pressed"
@@ -219,8 +557,13 @@ mod tests {
         let state = State::Pressed {
             since: now - Duration::from_millis(200),
         };
-        let (new_state, out_event) =
-            state.handle_gpio_value(GpioValue::Disabled, None, Duration::from_millis(100));
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Disabled,
+            None,
+            None,
+            None,
+            Duration::from_millis(100),
+        );
         assert!(
             matches!(new_state, State::Released { .. }),
             "Expected state to be Released"
@@ -235,8 +578,13 @@ mod tests {
         let state = State::Released {
             since: now - Duration::from_millis(50),
         };
-        let (new_state, out_event) =
-            state.handle_gpio_value(GpioValue::Enabled, None, Duration::from_millis(100));
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Enabled,
+            None,
+            None,
+            None,
+            Duration::from_millis(100),
+        );
         assert!(
             matches!(new_state, State::Released { .. }),
             "Expected state to remain Released"
@@ -250,8 +598,13 @@ mod tests {
         let state = State::Pressed {
             since: now - Duration::from_millis(50),
         };
-        let (new_state, out_event) =
-            state.handle_gpio_value(GpioValue::Disabled, None, Duration::from_millis(100));
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Disabled,
+            None,
+            None,
+            None,
+            Duration::from_millis(100),
+        );
         assert!(
             matches!(new_state, State::Pressed { .. }),
             "Expected state to remain Pressed"
@@ -273,6 +626,8 @@ mod tests {
         let (new_state, out_event) = state.handle_gpio_value(
             GpioValue::Enabled,
             Some(&repeat_config),
+            None,
+            None,
             Duration::from_millis(50),
         );
         assert!(
@@ -296,6 +651,8 @@ mod tests {
         let (new_state, out_event) = state.handle_gpio_value(
             GpioValue::Enabled,
             Some(&repeat_config),
+            None,
+            None,
             Duration::from_millis(50),
         );
         assert!(
@@ -305,6 +662,25 @@ mod tests {
         assert_eq!(out_event, OutEvent::Repeat);
     }
 
+    #[test]
+    fn test_should_stay_pressed_when_repeat_is_removed() {
+        let state = State::Repeating {
+            last_repeat: Instant::now() - Duration::from_millis(60),
+        };
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Enabled,
+            None,
+            None,
+            None,
+            Duration::from_millis(50),
+        );
+        assert!(
+            matches!(new_state, State::Pressed { .. }),
+            "Expected state to fall back to Pressed"
+        );
+        assert_eq!(out_event, OutEvent::None);
+    }
+
     #[test]
     fn test_should_not_handle_repeating_rate_too_soon() {
         let now = Instant::now();
@@ -319,6 +695,8 @@ mod tests {
         let (new_state, out_event) = state.handle_gpio_value(
             GpioValue::Enabled,
             Some(&repeat_config),
+            None,
+            None,
             Duration::from_millis(50),
         );
         assert!(
@@ -331,8 +709,13 @@ mod tests {
     #[test]
     fn test_should_handle_unknown_to_pressed() {
         let state = State::Unknown;
-        let (new_state, out_event) =
-            state.handle_gpio_value(GpioValue::Enabled, None, Duration::from_millis(100));
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Enabled,
+            None,
+            None,
+            None,
+            Duration::from_millis(100),
+        );
         assert!(
             matches!(new_state, State::Pressed { .. }),
             "Expected state to be Pressed"
@@ -343,8 +726,13 @@ mod tests {
     #[test]
     fn test_should_handle_unknown_to_released() {
         let state = State::Unknown;
-        let (new_state, out_event) =
-            state.handle_gpio_value(GpioValue::Disabled, None, Duration::from_millis(100));
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Disabled,
+            None,
+            None,
+            None,
+            Duration::from_millis(100),
+        );
         assert!(
             matches!(new_state, State::Released { .. }),
             "Expected state to be Released"
@@ -352,6 +740,291 @@ mod tests {
         assert_eq!(out_event, OutEvent::None);
     }
 
+    #[test]
+    fn test_should_defer_press_with_hold() {
+        let now = Instant::now();
+        let hold = Some(Duration::from_millis(1000));
+
+        let state = State::Released {
+            since: now - Duration::from_millis(200),
+        };
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Enabled,
+            None,
+            hold,
+            None,
+            Duration::from_millis(100),
+        );
+        assert!(
+            matches!(new_state, State::Pending { .. }),
+            "Expected state to be Pending"
+        );
+        assert_eq!(out_event, OutEvent::None);
+
+        let (new_state, out_event) = State::Unknown.handle_gpio_value(
+            GpioValue::Enabled,
+            None,
+            hold,
+            None,
+            Duration::from_millis(100),
+        );
+        assert!(
+            matches!(new_state, State::Pending { .. }),
+            "Expected state to be Pending"
+        );
+        assert_eq!(out_event, OutEvent::None);
+    }
+
+    #[test]
+    fn test_should_tap_when_released_before_hold() {
+        let now = Instant::now();
+        let hold = Some(Duration::from_millis(1000));
+
+        let state = State::Pending {
+            since: now - Duration::from_millis(500),
+        };
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Enabled,
+            None,
+            hold,
+            None,
+            Duration::from_millis(100),
+        );
+        assert_eq!(new_state, state, "Expected state to remain Pending");
+        assert_eq!(out_event, OutEvent::None);
+
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Disabled,
+            None,
+            hold,
+            None,
+            Duration::from_millis(100),
+        );
+        assert!(
+            matches!(new_state, State::Released { .. }),
+            "Expected state to be Released"
+        );
+        assert_eq!(out_event, OutEvent::Tap);
+    }
+
+    #[test]
+    fn test_should_debounce_tap() {
+        let now = Instant::now();
+        let state = State::Pending {
+            since: now - Duration::from_millis(50),
+        };
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Disabled,
+            None,
+            Some(Duration::from_millis(1000)),
+            None,
+            Duration::from_millis(100),
+        );
+        assert_eq!(new_state, state, "Expected state to remain Pending");
+        assert_eq!(out_event, OutEvent::None);
+    }
+
+    #[test]
+    fn test_should_hold_after_hold_time() {
+        let now = Instant::now();
+        let hold = Some(Duration::from_millis(1000));
+        let repeat_config = RepeatConfig {
+            delay: Duration::from_millis(100),
+            rate: Duration::from_millis(50),
+        };
+
+        let state = State::Pending {
+            since: now - Duration::from_millis(1200),
+        };
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Enabled,
+            Some(&repeat_config),
+            hold,
+            None,
+            Duration::from_millis(100),
+        );
+        assert!(
+            matches!(new_state, State::Held { .. }),
+            "Expected state to be Held"
+        );
+        assert_eq!(out_event, OutEvent::HoldPress);
+
+        // the hold keycode doesn't repeat
+        let state = State::Held {
+            since: now - Duration::from_millis(500),
+        };
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Enabled,
+            Some(&repeat_config),
+            hold,
+            None,
+            Duration::from_millis(100),
+        );
+        assert_eq!(new_state, state, "Expected state to remain Held");
+        assert_eq!(out_event, OutEvent::None);
+
+        let (new_state, out_event) = state.handle_gpio_value(
+            GpioValue::Disabled,
+            Some(&repeat_config),
+            hold,
+            None,
+            Duration::from_millis(100),
+        );
+        assert!(
+            matches!(new_state, State::Released { .. }),
+            "Expected state to be Released"
+        );
+        assert_eq!(out_event, OutEvent::HoldRelease);
+    }
+
+    #[test]
+    fn test_should_get_hold_deadline() {
+        let now = Instant::now();
+        let debounce = Duration::from_millis(20);
+        let hold = Duration::from_millis(1000);
+
+        let since = now - Duration::from_millis(5);
+        let state = State::Pending { since };
+        assert_eq!(
+            state.next_deadline(None, Some(hold), None, debounce, now),
+            Some(since + debounce)
+        );
+
+        let since = now - Duration::from_millis(50);
+        let state = State::Pending { since };
+        assert_eq!(
+            state.next_deadline(None, Some(hold), None, debounce, now),
+            Some(since + hold)
+        );
+
+        let state = State::Held { since };
+        assert_eq!(
+            state.next_deadline(None, Some(hold), None, debounce, now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_should_get_next_deadline() {
+        let now = Instant::now();
+        let debounce = Duration::from_millis(20);
+        let repeat_config = RepeatConfig {
+            delay: Duration::from_millis(100),
+            rate: Duration::from_millis(50),
+        };
+
+        assert_eq!(
+            State::Unknown.next_deadline(None, None, None, debounce, now),
+            None
+        );
+
+        let since = now - Duration::from_millis(5);
+        let state = State::Released { since };
+        assert_eq!(
+            state.next_deadline(None, None, None, debounce, now),
+            Some(since + debounce)
+        );
+
+        let state = State::Released {
+            since: now - Duration::from_millis(50),
+        };
+        assert_eq!(state.next_deadline(None, None, None, debounce, now), None);
+
+        let state = State::Pressed { since };
+        assert_eq!(
+            state.next_deadline(Some(&repeat_config), None, None, debounce, now),
+            Some(since + debounce)
+        );
+
+        let since = now - Duration::from_millis(50);
+        let state = State::Pressed { since };
+        assert_eq!(
+            state.next_deadline(Some(&repeat_config), None, None, debounce, now),
+            Some(since + repeat_config.delay)
+        );
+        assert_eq!(state.next_deadline(None, None, None, debounce, now), None);
+
+        let last_repeat = now - Duration::from_millis(10);
+        let state = State::Repeating { last_repeat };
+        assert_eq!(
+            state.next_deadline(Some(&repeat_config), None, None, debounce, now),
+            Some(last_repeat + repeat_config.rate)
+        );
+    }
+
+    #[test]
+    fn test_should_pulse_with_turbo() {
+        let now = Instant::now();
+        let debounce = Duration::from_millis(20);
+        let turbo = TurboConfig {
+            down: Duration::from_millis(30),
+            up: Duration::from_millis(70),
+            switch: None,
+        };
+
+        let (state, out_event) = State::Unknown.handle_gpio_value(
+            GpioValue::Enabled,
+            None,
+            None,
+            Some(&turbo),
+            debounce,
+        );
+        assert!(matches!(state, State::Turbo { down: true, .. }));
+        assert_eq!(out_event, OutEvent::Press);
+
+        let since = now - Duration::from_millis(500);
+        let state = State::Turbo {
+            since,
+            pulse: now - Duration::from_millis(40),
+            down: true,
+        };
+        let (state, out_event) =
+            state.handle_gpio_value(GpioValue::Enabled, None, None, Some(&turbo), debounce);
+        assert!(matches!(state, State::Turbo { down: false, .. }));
+        assert_eq!(out_event, OutEvent::Release);
+
+        let pulse = now - Duration::from_millis(10);
+        let state = State::Turbo {
+            since,
+            pulse,
+            down: false,
+        };
+        let (new_state, out_event) =
+            state.handle_gpio_value(GpioValue::Enabled, None, None, Some(&turbo), debounce);
+        assert_eq!(new_state, state);
+        assert_eq!(out_event, OutEvent::None);
+        assert_eq!(
+            state.next_deadline(None, None, Some(&turbo), debounce, now),
+            Some(pulse + turbo.up)
+        );
+
+        // the key is released only if it's down
+        let (new_state, out_event) =
+            state.handle_gpio_value(GpioValue::Disabled, None, None, Some(&turbo), debounce);
+        assert!(matches!(new_state, State::Released { .. }));
+        assert_eq!(out_event, OutEvent::None);
+        let state = State::Turbo {
+            since,
+            pulse,
+            down: true,
+        };
+        let (new_state, out_event) =
+            state.handle_gpio_value(GpioValue::Disabled, None, None, Some(&turbo), debounce);
+        assert!(matches!(new_state, State::Released { .. }));
+        assert_eq!(out_event, OutEvent::Release);
+
+        // switched off, the key stays down
+        let state = State::Turbo {
+            since,
+            pulse: since,
+            down: true,
+        };
+        let (new_state, out_event) =
+            state.handle_gpio_value(GpioValue::Enabled, None, None, None, debounce);
+        assert_eq!(new_state, state);
+        assert_eq!(out_event, OutEvent::None);
+    }
+
     #[test]
     fn test_should_tell_whether_is_debounced() {
         let now = Instant::now();
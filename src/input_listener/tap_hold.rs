@@ -0,0 +1,69 @@
+use crate::input_listener::state::OutEvent;
+
+/// Events of the other keys held back while a tap-hold key is undecided.
+///
+/// They're emitted once the tap-hold key is either tapped or held, so they're affected by
+/// its hold keycode only if it's held.
+#[derive(Debug, Default)]
+pub struct HeldBack {
+    events: Vec<(u8, OutEvent)>,
+}
+
+impl HeldBack {
+    /// Hold back `event` of the key on `gpio`.
+    ///
+    /// Returns whether the key has been both pressed and released while held back,
+    /// which resolves permissive hold keys as held.
+    pub fn push(&mut self, gpio: u8, event: OutEvent) -> bool {
+        let press = match event {
+            OutEvent::Tap => None,
+            OutEvent::Release => Some(OutEvent::Press),
+            OutEvent::HoldRelease => Some(OutEvent::HoldPress),
+            OutEvent::Press | OutEvent::Repeat | OutEvent::HoldPress | OutEvent::None => {
+                self.events.push((gpio, event));
+                return false;
+            }
+        };
+        let pressed = press.is_none_or(|press| self.events.contains(&(gpio, press)));
+        self.events.push((gpio, event));
+
+        pressed
+    }
+
+    /// Take the held back events, in order
+    pub fn take(&mut self) -> Vec<(u8, OutEvent)> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_tell_whether_key_is_pressed_and_released() {
+        let mut held_back = HeldBack::default();
+        assert!(held_back.is_empty());
+
+        assert!(!held_back.push(18, OutEvent::Press));
+        assert!(!held_back.push(22, OutEvent::Release));
+        assert!(held_back.push(18, OutEvent::Release));
+        assert!(held_back.push(23, OutEvent::Tap));
+
+        assert_eq!(
+            held_back.take(),
+            vec![
+                (18, OutEvent::Press),
+                (22, OutEvent::Release),
+                (18, OutEvent::Release),
+                (23, OutEvent::Tap)
+            ]
+        );
+        assert!(held_back.is_empty());
+    }
+}
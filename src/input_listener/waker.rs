@@ -0,0 +1,86 @@
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// Wakes up the input listener while it's waiting for GPIO events.
+///
+/// Backed by an `eventfd`, which is registered in the listener epoll set.
+#[derive(Debug)]
+pub struct Waker {
+    fd: OwnedFd,
+}
+
+impl Waker {
+    /// Create a new [`Waker`]
+    pub fn try_new() -> anyhow::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            anyhow::bail!("Failed to create eventfd: {}", io::Error::last_os_error());
+        }
+
+        Ok(Waker {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Wake up the listener
+    pub fn wake(&self) {
+        let value: u64 = 1;
+        let res = unsafe {
+            libc::write(
+                self.fd.as_raw_fd(),
+                &value as *const u64 as *const libc::c_void,
+                std::mem::size_of::<u64>(),
+            )
+        };
+        if res < 0 {
+            error!(
+                "Failed to wake input listener: {}",
+                io::Error::last_os_error()
+            );
+        }
+    }
+
+    /// Reset the wake up counter, so the fd is no longer readable
+    pub fn reset(&self) {
+        let mut value: u64 = 0;
+        unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                &mut value as *mut u64 as *mut libc::c_void,
+                std::mem::size_of::<u64>(),
+            );
+        }
+    }
+}
+
+impl AsRawFd for Waker {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_wake_and_reset() {
+        let waker = Waker::try_new().unwrap();
+        assert!(!is_readable(&waker));
+        waker.wake();
+        waker.wake();
+        assert!(is_readable(&waker));
+        waker.reset();
+        assert!(!is_readable(&waker));
+    }
+
+    fn is_readable(waker: &Waker) -> bool {
+        let mut pollfd = libc::pollfd {
+            fd: waker.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut pollfd, 1, 0) == 1 }
+    }
+}
@@ -1,11 +1,23 @@
+mod evdev_gamepad;
 mod evdev_keyboard;
+mod evdev_mouse;
 #[cfg(test)]
 mod mock;
 
+use std::ffi::CString;
+use std::io;
+use std::time::Instant;
+
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{BusType, InputEvent, InputId};
+
+pub use self::evdev_gamepad::EvdevGamepad;
 pub use self::evdev_keyboard::EvdevKeyboard;
+pub use self::evdev_mouse::EvdevMouse;
 #[cfg(test)]
 #[allow(unused)]
-pub use self::mock::MockKeyboard;
+pub use self::mock::{MockKeyboard, MockSink, Summary};
+use crate::config::{Identity, MouseConfig, Output};
 
 /// Keyboard trait definition
 pub trait Keyboard {
@@ -15,6 +27,240 @@ pub trait Keyboard {
     /// Emit a key down event
     fn key_down(&mut self, keycode: evdev::KeyCode) -> anyhow::Result<()>;
 
-    /// Emit a key repeat event
-    fn key_repeat(&mut self, keycode: evdev::KeyCode) -> anyhow::Result<()>;
+    /// Emit a key repeat event.
+    ///
+    /// It's ignored by default, since gamepad and mouse buttons don't auto-repeat.
+    fn key_repeat(&mut self, _keycode: evdev::KeyCode) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Emit a synchronization event, so pending events are delivered to consumers
+    fn sync(&mut self) -> anyhow::Result<()>;
+
+    /// Emit the events due at `now` for the keys held down, such as the pointer motion of a mouse.
+    ///
+    /// It's called on every tick of the listener.
+    fn tick(&mut self, _now: Instant) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Next instant at which [`Keyboard::tick`] has events to emit, if any
+    fn next_deadline(&self) -> Option<Instant> {
+        None
+    }
+}
+
+/// Destination of the events of a virtual device
+pub trait EventSink {
+    /// Emit `events`, followed by a synchronization event
+    fn emit(&mut self, events: &[InputEvent]) -> io::Result<()>;
+
+    /// Emit a synchronization event, so pending events are delivered to consumers
+    fn sync(&mut self) -> anyhow::Result<()> {
+        debug!("Emitting synchronization event");
+        // emit always terminates the events with a SYN_REPORT
+        self.emit(&[])
+            .map_err(|e| anyhow::anyhow!("Failed to emit synchronization event: {}", e))
+    }
+}
+
+impl EventSink for VirtualDevice {
+    fn emit(&mut self, events: &[InputEvent]) -> io::Result<()> {
+        VirtualDevice::emit(self, events)
+    }
+}
+
+/// Default input id of the gamepad and the mouse: the pid.codes vendor, with one of its test
+/// `product` ids.
+///
+/// USB is used as bus type, since some consumers ignore virtual devices.
+fn pid_codes_input_id(product: u16) -> InputId {
+    InputId::new(BusType::BUS_USB, 0x1209, product, 0x0100)
+}
+
+/// Configuration of a virtual output device
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputConfig {
+    pub name: String,
+    pub output: Output,
+    /// Identity overriding the default one of `output`
+    pub identity: Identity,
+    /// Pointer and wheel motion; only set if `output` is a mouse, so other devices are not
+    /// recreated when it changes
+    pub mouse: Option<MouseConfig>,
+}
+
+impl OutputConfig {
+    /// Input id of the device: the default one of `output`, with the configured ids
+    fn input_id(&self) -> InputId {
+        let default = match self.output {
+            Output::Keyboard => EvdevKeyboard::default_input_id(),
+            Output::Gamepad => EvdevGamepad::default_input_id(),
+            Output::Mouse => EvdevMouse::default_input_id(),
+        };
+
+        InputId::new(
+            self.identity
+                .bustype
+                .map(|bustype| bustype.bus_type())
+                .unwrap_or(default.bus_type()),
+            self.identity.vendor.unwrap_or(default.vendor()),
+            self.identity.product.unwrap_or(default.product()),
+            self.identity.version.unwrap_or(default.version()),
+        )
+    }
+
+    /// Physical location of the device, if configured
+    fn phys(&self) -> anyhow::Result<Option<CString>> {
+        self.identity
+            .phys
+            .as_deref()
+            .map(CString::new)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Invalid `phys` of device {:?}: {}", self.name, e))
+    }
+}
+
+/// Identity applied to a virtual device when it's built
+pub struct DeviceIdentity {
+    pub input_id: InputId,
+    pub phys: Option<CString>,
+}
+
+impl DeviceIdentity {
+    /// Create a [`VirtualDeviceBuilder`] for a device named `name` with this identity
+    pub fn builder<'a>(&self, name: &'a str) -> io::Result<VirtualDeviceBuilder<'a>> {
+        let builder = VirtualDevice::builder()?
+            .name(name)
+            .input_id(self.input_id.clone());
+
+        match &self.phys {
+            Some(phys) => builder.with_phys(phys),
+            None => Ok(builder),
+        }
+    }
+}
+
+/// A virtual device, either a keyboard, a gamepad or a mouse
+pub enum OutputDevice {
+    Keyboard(EvdevKeyboard),
+    Gamepad(EvdevGamepad),
+    Mouse(EvdevMouse),
+}
+
+impl OutputDevice {
+    /// Create a new [`OutputDevice`] described by `config`, emitting the specified keycodes
+    pub fn try_new(config: &OutputConfig, listen_to: &[evdev::KeyCode]) -> anyhow::Result<Self> {
+        let identity = DeviceIdentity {
+            input_id: config.input_id(),
+            phys: config.phys()?,
+        };
+        match config.output {
+            Output::Keyboard => {
+                EvdevKeyboard::try_new(&config.name, &identity, listen_to).map(Self::Keyboard)
+            }
+            Output::Gamepad => {
+                EvdevGamepad::try_new(&config.name, &identity, listen_to).map(Self::Gamepad)
+            }
+            Output::Mouse => {
+                let mouse = config.mouse.clone().unwrap_or_default();
+                EvdevMouse::try_new(&config.name, &identity, listen_to, &mouse).map(Self::Mouse)
+            }
+        }
+    }
+
+    fn device(&mut self) -> &mut dyn Keyboard {
+        match self {
+            Self::Keyboard(keyboard) => keyboard,
+            Self::Gamepad(gamepad) => gamepad,
+            Self::Mouse(mouse) => mouse,
+        }
+    }
+}
+
+impl Keyboard for OutputDevice {
+    fn key_up(&mut self, keycode: evdev::KeyCode) -> anyhow::Result<()> {
+        self.device().key_up(keycode)
+    }
+
+    fn key_down(&mut self, keycode: evdev::KeyCode) -> anyhow::Result<()> {
+        self.device().key_down(keycode)
+    }
+
+    fn key_repeat(&mut self, keycode: evdev::KeyCode) -> anyhow::Result<()> {
+        self.device().key_repeat(keycode)
+    }
+
+    fn sync(&mut self) -> anyhow::Result<()> {
+        self.device().sync()
+    }
+
+    fn tick(&mut self, now: Instant) -> anyhow::Result<()> {
+        self.device().tick(now)
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        match self {
+            Self::Keyboard(keyboard) => keyboard.next_deadline(),
+            Self::Gamepad(gamepad) => gamepad.next_deadline(),
+            Self::Mouse(mouse) => mouse.next_deadline(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use evdev::BusType;
+
+    use super::*;
+
+    #[test]
+    fn test_should_override_input_id() {
+        let mut config = OutputConfig {
+            name: "gpio2key".to_string(),
+            output: Output::Gamepad,
+            identity: Identity::default(),
+            mouse: None,
+        };
+        assert_eq!(config.input_id(), EvdevGamepad::default_input_id());
+        assert!(config.phys().unwrap().is_none());
+
+        config.output = Output::Keyboard;
+        config.identity.product = Some(0x0042);
+        let input_id = config.input_id();
+        assert_eq!(input_id.bus_type(), BusType::BUS_USB);
+        assert_eq!(
+            input_id.vendor(),
+            EvdevKeyboard::default_input_id().vendor()
+        );
+        assert_eq!(input_id.product(), 0x0042);
+
+        config.identity.bustype = "virtual".parse().ok();
+        config.identity.version = Some(0x0002);
+        let input_id = config.input_id();
+        assert_eq!(input_id.bus_type(), BusType::BUS_VIRTUAL);
+        assert_eq!(input_id.version(), 0x0002);
+
+        config.output = Output::Mouse;
+        config.identity = Identity::default();
+        assert_eq!(config.input_id(), EvdevMouse::default_input_id());
+    }
+
+    #[test]
+    fn test_should_convert_phys() {
+        let mut config = OutputConfig {
+            name: "gpio2key".to_string(),
+            output: Output::Keyboard,
+            identity: Identity {
+                phys: Some("gpio2key/input0".to_string()),
+                ..Default::default()
+            },
+            mouse: None,
+        };
+        assert_eq!(config.phys().unwrap().as_deref(), Some(c"gpio2key/input0"));
+
+        config.identity.phys = Some("gpio\0key".to_string());
+        assert!(config.phys().is_err());
+    }
 }
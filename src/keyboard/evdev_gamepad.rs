@@ -0,0 +1,246 @@
+use evdev::uinput::VirtualDevice;
+use evdev::{
+    AbsInfo, AbsoluteAxisCode, AbsoluteAxisEvent, AttributeSet, InputId, KeyCode, KeyEvent,
+    UinputAbsSetup,
+};
+
+use crate::config::Keycode;
+use crate::keyboard::{DeviceIdentity, EventSink, Keyboard, pid_codes_input_id};
+
+const KEY_DOWN: i32 = 1;
+const KEY_UP: i32 = 0;
+
+/// pid.codes test product id of the gamepad
+const PRODUCT: u16 = 0x0001;
+
+/// Buttons of the standard gamepad layout, registered even if unused so the layout is stable
+const BUTTONS: &[KeyCode] = &[
+    KeyCode::BTN_SOUTH,
+    KeyCode::BTN_EAST,
+    KeyCode::BTN_NORTH,
+    KeyCode::BTN_WEST,
+    KeyCode::BTN_TL,
+    KeyCode::BTN_TR,
+    KeyCode::BTN_SELECT,
+    KeyCode::BTN_START,
+    KeyCode::BTN_MODE,
+];
+
+/// A evdev virtual gamepad implementation.
+///
+/// Buttons are emitted as key events, while d-pad keys (`BTN_DPAD_*` and the arrow keys)
+/// move the `ABS_HAT0X` and `ABS_HAT0Y` axes.
+pub struct EvdevGamepad<D = VirtualDevice>
+where
+    D: EventSink,
+{
+    device: D,
+    dpad: DPad,
+}
+
+impl EvdevGamepad {
+    /// Create a new [`EvdevGamepad`] with the default buttons and the specified keycodes
+    pub fn try_new(
+        name: &str,
+        identity: &DeviceIdentity,
+        listen_to: &[KeyCode],
+    ) -> anyhow::Result<Self> {
+        let mut keys = AttributeSet::new();
+        for &key in BUTTONS.iter().chain(
+            listen_to
+                .iter()
+                .filter(|key| Direction::of(**key).is_none()),
+        ) {
+            if Keycode::from(key).is_keyboard_key() {
+                warn!(
+                    "Gamepad {name:?} emits keyboard key {key:?}: it will also be seen as a keyboard"
+                );
+            }
+            keys.insert(key);
+            debug!("Listening to button: {:?}", key);
+        }
+        let hat = AbsInfo::new(0, -1, 1, 0, 0, 0);
+
+        // no INPUT_PROP is set, like on real gamepads: the kernel only defines properties of
+        // pointers, touchpads, touchscreens and accelerometers, and udev and SDL would classify
+        // the device as one of them instead of a joystick
+        identity
+            .builder(name)?
+            .with_keys(&keys)?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisCode::ABS_HAT0X, hat))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisCode::ABS_HAT0Y, hat))?
+            .build()
+            .map(EvdevGamepad::new)
+            .map_err(|e| anyhow::anyhow!("Failed to create evdev virtual gamepad: {}", e))
+    }
+
+    /// Input id used when none is configured
+    pub fn default_input_id() -> InputId {
+        pid_codes_input_id(PRODUCT)
+    }
+}
+
+impl<D> EvdevGamepad<D>
+where
+    D: EventSink,
+{
+    fn new(device: D) -> Self {
+        EvdevGamepad {
+            device,
+            dpad: DPad::default(),
+        }
+    }
+
+    /// Emit the event for `keycode` being pressed or released
+    fn emit(&mut self, keycode: KeyCode, pressed: bool) -> anyhow::Result<()> {
+        let ev = match Direction::of(keycode) {
+            Some(direction) => {
+                let axis = self.dpad.set(direction, pressed);
+                *AbsoluteAxisEvent::new_now(axis, self.dpad.value(axis))
+            }
+            None => *KeyEvent::new_now(keycode, if pressed { KEY_DOWN } else { KEY_UP }),
+        };
+        debug!("Emitting gamepad event: {:?}", ev);
+
+        self.device
+            .emit(&[ev])
+            .map_err(|e| anyhow::anyhow!("Failed to emit gamepad event: {}", e))
+    }
+}
+
+impl<D> Keyboard for EvdevGamepad<D>
+where
+    D: EventSink,
+{
+    fn key_down(&mut self, keycode: KeyCode) -> anyhow::Result<()> {
+        self.emit(keycode, true)
+    }
+
+    fn key_up(&mut self, keycode: KeyCode) -> anyhow::Result<()> {
+        self.emit(keycode, false)
+    }
+
+    fn sync(&mut self) -> anyhow::Result<()> {
+        self.device.sync()
+    }
+}
+
+/// A d-pad direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// Get the direction driven by `keycode`, if it's a d-pad key
+    fn of(keycode: KeyCode) -> Option<Self> {
+        match keycode {
+            KeyCode::BTN_DPAD_UP | KeyCode::KEY_UP => Some(Self::Up),
+            KeyCode::BTN_DPAD_DOWN | KeyCode::KEY_DOWN => Some(Self::Down),
+            KeyCode::BTN_DPAD_LEFT | KeyCode::KEY_LEFT => Some(Self::Left),
+            KeyCode::BTN_DPAD_RIGHT | KeyCode::KEY_RIGHT => Some(Self::Right),
+            _ => None,
+        }
+    }
+}
+
+/// Pressed d-pad directions
+#[derive(Debug, Default)]
+struct DPad {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+}
+
+impl DPad {
+    /// Set the state of `direction` and return the axis it moves
+    fn set(&mut self, direction: Direction, pressed: bool) -> AbsoluteAxisCode {
+        match direction {
+            Direction::Up => self.up = pressed,
+            Direction::Down => self.down = pressed,
+            Direction::Left => self.left = pressed,
+            Direction::Right => self.right = pressed,
+        }
+
+        match direction {
+            Direction::Up | Direction::Down => AbsoluteAxisCode::ABS_HAT0Y,
+            Direction::Left | Direction::Right => AbsoluteAxisCode::ABS_HAT0X,
+        }
+    }
+
+    /// Value of the hat `axis`; opposite directions pressed together cancel out
+    fn value(&self, axis: AbsoluteAxisCode) -> i32 {
+        if axis == AbsoluteAxisCode::ABS_HAT0X {
+            i32::from(self.right) - i32::from(self.left)
+        } else {
+            i32::from(self.down) - i32::from(self.up)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::keyboard::{MockSink, Summary};
+
+    #[test]
+    fn test_should_emit_buttons() {
+        let mut gamepad = EvdevGamepad::new(MockSink::default());
+        gamepad.key_down(KeyCode::BTN_SOUTH).unwrap();
+        gamepad.key_repeat(KeyCode::BTN_SOUTH).unwrap();
+        gamepad.key_up(KeyCode::BTN_SOUTH).unwrap();
+        gamepad.sync().unwrap();
+
+        assert_eq!(
+            gamepad.device.summaries(),
+            vec![
+                Summary::Key(KeyCode::BTN_SOUTH, KEY_DOWN),
+                Summary::Key(KeyCode::BTN_SOUTH, KEY_UP),
+            ]
+        );
+        assert_eq!(gamepad.device.reports, 3);
+    }
+
+    #[test]
+    fn test_should_drive_hat_with_dpad() {
+        let mut gamepad = EvdevGamepad::new(MockSink::default());
+        gamepad.key_down(KeyCode::BTN_DPAD_UP).unwrap();
+        gamepad.key_down(KeyCode::BTN_DPAD_RIGHT).unwrap();
+        gamepad.key_up(KeyCode::BTN_DPAD_UP).unwrap();
+        gamepad.key_down(KeyCode::KEY_DOWN).unwrap();
+        gamepad.key_up(KeyCode::BTN_DPAD_RIGHT).unwrap();
+
+        assert_eq!(
+            gamepad.device.summaries(),
+            vec![
+                Summary::Abs(AbsoluteAxisCode::ABS_HAT0Y, -1),
+                Summary::Abs(AbsoluteAxisCode::ABS_HAT0X, 1),
+                Summary::Abs(AbsoluteAxisCode::ABS_HAT0Y, 0),
+                Summary::Abs(AbsoluteAxisCode::ABS_HAT0Y, 1),
+                Summary::Abs(AbsoluteAxisCode::ABS_HAT0X, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_cancel_opposite_directions() {
+        let mut gamepad = EvdevGamepad::new(MockSink::default());
+        gamepad.key_down(KeyCode::BTN_DPAD_LEFT).unwrap();
+        gamepad.key_down(KeyCode::BTN_DPAD_RIGHT).unwrap();
+        gamepad.key_up(KeyCode::BTN_DPAD_LEFT).unwrap();
+
+        assert_eq!(
+            gamepad.device.summaries(),
+            vec![
+                Summary::Abs(AbsoluteAxisCode::ABS_HAT0X, -1),
+                Summary::Abs(AbsoluteAxisCode::ABS_HAT0X, 0),
+                Summary::Abs(AbsoluteAxisCode::ABS_HAT0X, 1),
+            ]
+        );
+    }
+}
@@ -1,12 +1,18 @@
 use evdev::uinput::VirtualDevice;
-use evdev::{AttributeSet, KeyCode, KeyEvent};
+use evdev::{AttributeSet, BusType, InputId, KeyCode, KeyEvent};
 
-use crate::keyboard::Keyboard;
+use crate::keyboard::{DeviceIdentity, EventSink, Keyboard};
 
 const KEY_DOWN: i32 = 1;
 const KEY_UP: i32 = 0;
 const KEY_REPEAT: i32 = 2;
 
+/// Default input id of the keyboard, the same evdev uses when none is set
+const BUS_TYPE: BusType = BusType::BUS_USB;
+const VENDOR: u16 = 0x1234;
+const PRODUCT: u16 = 0x5678;
+const VERSION: u16 = 0x111;
+
 /// A evdev virtual keyboard implementation
 pub struct EvdevKeyboard {
     device: VirtualDevice,
@@ -14,21 +20,30 @@ pub struct EvdevKeyboard {
 
 impl EvdevKeyboard {
     /// Create a new [`EvdevKeyboard`] that listens to the specified keycodes
-    pub fn try_new(name: &str, listen_to: &[KeyCode]) -> anyhow::Result<Self> {
+    pub fn try_new(
+        name: &str,
+        identity: &DeviceIdentity,
+        listen_to: &[KeyCode],
+    ) -> anyhow::Result<Self> {
         let mut keys = AttributeSet::new();
         for &key in listen_to {
             keys.insert(key);
             debug!("Listening to keycode: {:?}", key);
         }
 
-        VirtualDevice::builder()?
-            .name(name)
+        identity
+            .builder(name)?
             .with_keys(&keys)?
             .build()
             .map(|device| EvdevKeyboard { device })
             .map_err(|e| anyhow::anyhow!("Failed to create evdev virtual keyboard: {}", e))
     }
 
+    /// Input id used when none is configured
+    pub fn default_input_id() -> InputId {
+        InputId::new(BUS_TYPE, VENDOR, PRODUCT, VERSION)
+    }
+
     /// Emit a key event with the specified value
     fn emit(&mut self, keycode: evdev::KeyCode, value: i32) -> anyhow::Result<()> {
         let ev = *KeyEvent::new_now(keycode, value);
@@ -52,4 +67,8 @@ impl Keyboard for EvdevKeyboard {
     fn key_up(&mut self, keycode: evdev::KeyCode) -> anyhow::Result<()> {
         self.emit(keycode, KEY_UP)
     }
+
+    fn sync(&mut self) -> anyhow::Result<()> {
+        EventSink::sync(&mut self.device)
+    }
 }
@@ -0,0 +1,420 @@
+use std::time::{Duration, Instant};
+
+use evdev::uinput::VirtualDevice;
+use evdev::{
+    AttributeSet, InputEvent, InputId, KeyCode, KeyEvent, RelativeAxisCode, RelativeAxisEvent,
+};
+
+use crate::config::MouseConfig;
+use crate::keyboard::{DeviceIdentity, EventSink, Keyboard, pid_codes_input_id};
+
+const KEY_DOWN: i32 = 1;
+const KEY_UP: i32 = 0;
+
+/// pid.codes test product id of the mouse
+const PRODUCT: u16 = 0x0002;
+
+/// Interval at which the pointer and the wheel move while their keys are held
+const MOTION_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Left, right and middle buttons, which consumers expect to recognise a mouse
+const BUTTONS: &[KeyCode] = &[KeyCode::BTN_LEFT, KeyCode::BTN_RIGHT, KeyCode::BTN_MIDDLE];
+
+/// A evdev virtual mouse implementation.
+///
+/// Buttons are emitted as key events, while direction keys (the arrow keys and `BTN_DPAD_*`) move the
+/// pointer along `REL_X` and `REL_Y`, and `SCROLLUP` and `SCROLLDOWN` turn `REL_WHEEL`.
+/// The pointer and the wheel move on every tick while their keys are held, the pointer accelerating
+/// from `speed` to `max_speed` along the acceleration curve of the [`MouseConfig`].
+pub struct EvdevMouse<D = VirtualDevice>
+where
+    D: EventSink,
+{
+    device: D,
+    config: MouseConfig,
+    controls: Controls,
+    pointer: Option<Motion>,
+    wheel: Option<Motion>,
+}
+
+impl EvdevMouse {
+    /// Create a new [`EvdevMouse`] with the default buttons and the specified keycodes
+    pub fn try_new(
+        name: &str,
+        identity: &DeviceIdentity,
+        listen_to: &[KeyCode],
+        config: &MouseConfig,
+    ) -> anyhow::Result<Self> {
+        let mut keys = AttributeSet::new();
+        for &key in BUTTONS
+            .iter()
+            .chain(listen_to.iter().filter(|key| Control::of(**key).is_none()))
+        {
+            keys.insert(key);
+            debug!("Listening to button: {:?}", key);
+        }
+        let mut axes = AttributeSet::new();
+        for axis in [
+            RelativeAxisCode::REL_X,
+            RelativeAxisCode::REL_Y,
+            RelativeAxisCode::REL_WHEEL,
+        ] {
+            axes.insert(axis);
+        }
+
+        identity
+            .builder(name)?
+            .with_keys(&keys)?
+            .with_relative_axes(&axes)?
+            .build()
+            .map(|device| EvdevMouse::new(device, config.clone()))
+            .map_err(|e| anyhow::anyhow!("Failed to create evdev virtual mouse: {}", e))
+    }
+
+    /// Input id used when none is configured
+    pub fn default_input_id() -> InputId {
+        pid_codes_input_id(PRODUCT)
+    }
+}
+
+impl<D> EvdevMouse<D>
+where
+    D: EventSink,
+{
+    fn new(device: D, config: MouseConfig) -> Self {
+        EvdevMouse {
+            device,
+            config,
+            controls: Controls::default(),
+            pointer: None,
+            wheel: None,
+        }
+    }
+
+    /// Emit the event for `keycode` being pressed or released at `now`
+    fn emit(&mut self, keycode: KeyCode, pressed: bool, now: Instant) -> anyhow::Result<()> {
+        let ev = match Control::of(keycode) {
+            Some(control) => match self.set(control, pressed, now) {
+                Some(ev) => ev,
+                None => return Ok(()),
+            },
+            None => *KeyEvent::new_now(keycode, if pressed { KEY_DOWN } else { KEY_UP }),
+        };
+        debug!("Emitting mouse event: {:?}", ev);
+
+        self.device
+            .emit(&[ev])
+            .map_err(|e| anyhow::anyhow!("Failed to emit mouse event: {}", e))
+    }
+
+    /// Set the state of `control` at `now`, starting or stopping the motion it drives.
+    ///
+    /// Returns the event to emit right away, if any: pressing a wheel key turns the wheel by a notch.
+    fn set(&mut self, control: Control, pressed: bool, now: Instant) -> Option<InputEvent> {
+        self.controls.set(control, pressed);
+        if control.is_wheel() {
+            if !self.controls.is_scrolling() {
+                self.wheel = None;
+                return None;
+            }
+            if !pressed {
+                return None;
+            }
+            // a press turns the wheel by a notch right away, then it keeps turning while held
+            self.wheel = (self.config.wheel_speed > 0).then(|| Motion::new(now));
+            let notches = self.controls.wheel();
+            return (notches != 0)
+                .then(|| *RelativeAxisEvent::new_now(RelativeAxisCode::REL_WHEEL, notches));
+        }
+        if !self.controls.is_pointing() {
+            self.pointer = None;
+        } else if self.pointer.is_none() {
+            self.pointer = Some(Motion::new(now));
+        }
+
+        None
+    }
+}
+
+impl<D> Keyboard for EvdevMouse<D>
+where
+    D: EventSink,
+{
+    fn key_down(&mut self, keycode: KeyCode) -> anyhow::Result<()> {
+        self.emit(keycode, true, Instant::now())
+    }
+
+    fn key_up(&mut self, keycode: KeyCode) -> anyhow::Result<()> {
+        self.emit(keycode, false, Instant::now())
+    }
+
+    fn sync(&mut self) -> anyhow::Result<()> {
+        self.device.sync()
+    }
+
+    fn tick(&mut self, now: Instant) -> anyhow::Result<()> {
+        let mut events = Vec::new();
+        if let Some(pointer) = &mut self.pointer {
+            let speed = self
+                .config
+                .pointer_speed(now.saturating_duration_since(pointer.since));
+            let (x, y) = pointer.advance(now, speed, self.controls.pointer());
+            if x != 0 {
+                events.push(*RelativeAxisEvent::new_now(RelativeAxisCode::REL_X, x));
+            }
+            if y != 0 {
+                events.push(*RelativeAxisEvent::new_now(RelativeAxisCode::REL_Y, y));
+            }
+        }
+        if let Some(wheel) = &mut self.wheel {
+            let speed = f64::from(self.config.wheel_speed);
+            let (_, notches) = wheel.advance(now, speed, (0, self.controls.wheel()));
+            if notches != 0 {
+                events.push(*RelativeAxisEvent::new_now(
+                    RelativeAxisCode::REL_WHEEL,
+                    notches,
+                ));
+            }
+        }
+        if events.is_empty() {
+            return Ok(());
+        }
+        debug!("Emitting mouse motion: {:?}", events);
+
+        self.device
+            .emit(&events)
+            .map_err(|e| anyhow::anyhow!("Failed to emit mouse motion: {}", e))
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.pointer
+            .iter()
+            .chain(&self.wheel)
+            .map(|motion| motion.last + MOTION_INTERVAL)
+            .min()
+    }
+}
+
+/// A key moving the pointer or turning the wheel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Up,
+    Down,
+    Left,
+    Right,
+    WheelUp,
+    WheelDown,
+}
+
+impl Control {
+    /// Get the control driven by `keycode`, if it's a direction or wheel key
+    fn of(keycode: KeyCode) -> Option<Self> {
+        match keycode {
+            KeyCode::BTN_DPAD_UP | KeyCode::KEY_UP => Some(Self::Up),
+            KeyCode::BTN_DPAD_DOWN | KeyCode::KEY_DOWN => Some(Self::Down),
+            KeyCode::BTN_DPAD_LEFT | KeyCode::KEY_LEFT => Some(Self::Left),
+            KeyCode::BTN_DPAD_RIGHT | KeyCode::KEY_RIGHT => Some(Self::Right),
+            KeyCode::KEY_SCROLLUP => Some(Self::WheelUp),
+            KeyCode::KEY_SCROLLDOWN => Some(Self::WheelDown),
+            _ => None,
+        }
+    }
+
+    fn is_wheel(self) -> bool {
+        matches!(self, Self::WheelUp | Self::WheelDown)
+    }
+}
+
+/// Pressed controls
+#[derive(Debug, Default)]
+struct Controls {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+    wheel_up: bool,
+    wheel_down: bool,
+}
+
+impl Controls {
+    fn set(&mut self, control: Control, pressed: bool) {
+        match control {
+            Control::Up => self.up = pressed,
+            Control::Down => self.down = pressed,
+            Control::Left => self.left = pressed,
+            Control::Right => self.right = pressed,
+            Control::WheelUp => self.wheel_up = pressed,
+            Control::WheelDown => self.wheel_down = pressed,
+        }
+    }
+
+    /// Whether a direction key is pressed
+    fn is_pointing(&self) -> bool {
+        self.up || self.down || self.left || self.right
+    }
+
+    /// Whether a wheel key is pressed
+    fn is_scrolling(&self) -> bool {
+        self.wheel_up || self.wheel_down
+    }
+
+    /// Direction of the pointer along the X and Y axes; opposite directions cancel out
+    fn pointer(&self) -> (i32, i32) {
+        (
+            i32::from(self.right) - i32::from(self.left),
+            i32::from(self.down) - i32::from(self.up),
+        )
+    }
+
+    /// Direction of the wheel, positive upwards; opposite directions cancel out
+    fn wheel(&self) -> i32 {
+        i32::from(self.wheel_up) - i32::from(self.wheel_down)
+    }
+}
+
+/// Motion of the pointer or the wheel while their keys are held
+#[derive(Debug)]
+struct Motion {
+    /// Instant the motion started
+    since: Instant,
+    /// Instant of the last move
+    last: Instant,
+    /// Fractions of pixels, or notches, moved but not emitted yet, along each axis
+    remainder: (f64, f64),
+}
+
+impl Motion {
+    fn new(now: Instant) -> Self {
+        Motion {
+            since: now,
+            last: now,
+            remainder: (0.0, 0.0),
+        }
+    }
+
+    /// Move along `direction` at `speed` units per second since the last move, up to `now`.
+    ///
+    /// Returns the whole units moved along each axis.
+    fn advance(&mut self, now: Instant, speed: f64, direction: (i32, i32)) -> (i32, i32) {
+        let distance = speed * now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = self.last.max(now);
+        let x = self.remainder.0 + f64::from(direction.0) * distance;
+        let y = self.remainder.1 + f64::from(direction.1) * distance;
+        self.remainder = (x.fract(), y.fract());
+
+        (x.trunc() as i32, y.trunc() as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::keyboard::{MockSink, Summary};
+    use crate::test_utils::at;
+
+    #[test]
+    fn test_should_emit_buttons() {
+        let mut mouse = EvdevMouse::new(MockSink::default(), MouseConfig::default());
+        mouse.key_down(KeyCode::BTN_LEFT).unwrap();
+        mouse.key_repeat(KeyCode::BTN_LEFT).unwrap();
+        mouse.key_up(KeyCode::BTN_LEFT).unwrap();
+        mouse.sync().unwrap();
+
+        assert_eq!(
+            mouse.device.summaries(),
+            vec![
+                Summary::Key(KeyCode::BTN_LEFT, KEY_DOWN),
+                Summary::Key(KeyCode::BTN_LEFT, KEY_UP),
+            ]
+        );
+        assert_eq!(mouse.device.reports, 3);
+        assert_eq!(mouse.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_should_move_pointer_while_held() {
+        let mut mouse = EvdevMouse::new(MockSink::default(), config());
+        let start = Instant::now();
+
+        mouse.emit(KeyCode::KEY_RIGHT, true, start).unwrap();
+        assert!(mouse.device.events.is_empty());
+        assert_eq!(mouse.next_deadline(), Some(start + MOTION_INTERVAL));
+        // 235 px/s for 15 ms, then 415 px/s for 20 ms
+        mouse.tick(at(start, 15)).unwrap();
+        mouse.tick(at(start, 35)).unwrap();
+        mouse
+            .emit(KeyCode::BTN_DPAD_UP, true, at(start, 35))
+            .unwrap();
+        // 865 px/s for 50 ms, diagonally
+        mouse.tick(at(start, 85)).unwrap();
+        mouse
+            .emit(KeyCode::KEY_RIGHT, false, at(start, 85))
+            .unwrap();
+        mouse
+            .emit(KeyCode::BTN_DPAD_UP, false, at(start, 85))
+            .unwrap();
+        mouse.tick(at(start, 200)).unwrap();
+
+        assert_eq!(
+            mouse.device.summaries(),
+            vec![
+                Summary::Rel(RelativeAxisCode::REL_X, 3),
+                Summary::Rel(RelativeAxisCode::REL_X, 8),
+                Summary::Rel(RelativeAxisCode::REL_X, 44),
+                Summary::Rel(RelativeAxisCode::REL_Y, -43),
+            ]
+        );
+        assert_eq!(mouse.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_should_cancel_opposite_directions() {
+        let mut mouse = EvdevMouse::new(MockSink::default(), config());
+        let start = Instant::now();
+
+        mouse.emit(KeyCode::KEY_LEFT, true, start).unwrap();
+        mouse.emit(KeyCode::KEY_RIGHT, true, start).unwrap();
+        mouse.tick(at(start, 100)).unwrap();
+        assert!(mouse.device.events.is_empty());
+    }
+
+    #[test]
+    fn test_should_turn_wheel() {
+        let mut mouse = EvdevMouse::new(MockSink::default(), config());
+        let start = Instant::now();
+
+        mouse.emit(KeyCode::KEY_SCROLLDOWN, true, start).unwrap();
+        mouse.tick(at(start, 90)).unwrap();
+        mouse.tick(at(start, 150)).unwrap();
+        mouse.tick(at(start, 250)).unwrap();
+        mouse
+            .emit(KeyCode::KEY_SCROLLDOWN, false, at(start, 250))
+            .unwrap();
+        mouse
+            .emit(KeyCode::KEY_SCROLLUP, true, at(start, 300))
+            .unwrap();
+        mouse
+            .emit(KeyCode::KEY_SCROLLUP, false, at(start, 300))
+            .unwrap();
+
+        assert_eq!(
+            mouse.device.summaries(),
+            vec![
+                Summary::Rel(RelativeAxisCode::REL_WHEEL, -1),
+                Summary::Rel(RelativeAxisCode::REL_WHEEL, -1),
+                Summary::Rel(RelativeAxisCode::REL_WHEEL, -1),
+                Summary::Rel(RelativeAxisCode::REL_WHEEL, 1),
+            ]
+        );
+        assert_eq!(mouse.next_deadline(), None);
+    }
+
+    /// Linear acceleration from 100 to 1000 px/s over 100 ms, 10 wheel notches per second
+    fn config() -> MouseConfig {
+        toml::from_str(
+            "speed = 100\nmax_speed = 1000\nacceleration_ms = 100\nacceleration_curve = 1.0\nwheel_speed = 10",
+        )
+        .unwrap()
+    }
+}
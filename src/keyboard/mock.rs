@@ -1,4 +1,8 @@
-use crate::keyboard::Keyboard;
+use std::io;
+
+use evdev::{AbsoluteAxisCode, EventSummary, InputEvent, KeyCode, RelativeAxisCode};
+
+use crate::keyboard::{EventSink, Keyboard};
 
 const KEY_DOWN: i32 = 1;
 const KEY_UP: i32 = 0;
@@ -7,6 +11,7 @@ const KEY_REPEAT: i32 = 2;
 #[derive(Debug, Default)]
 pub struct MockKeyboard {
     pub events: Vec<(evdev::KeyCode, i32)>,
+    pub syncs: usize,
 }
 
 impl Keyboard for MockKeyboard {
@@ -24,6 +29,50 @@ impl Keyboard for MockKeyboard {
         self.events.push((keycode, KEY_UP));
         Ok(())
     }
+
+    fn sync(&mut self) -> anyhow::Result<()> {
+        self.syncs += 1;
+        Ok(())
+    }
+}
+
+/// An [`EventSink`] recording the emitted events
+#[derive(Debug, Default)]
+pub struct MockSink {
+    pub events: Vec<InputEvent>,
+    /// Number of synchronization events
+    pub reports: usize,
+}
+
+impl MockSink {
+    /// The recorded events, in a form easy to compare
+    pub fn summaries(&self) -> Vec<Summary> {
+        self.events
+            .iter()
+            .map(|ev| match ev.destructure() {
+                EventSummary::Key(_, code, value) => Summary::Key(code, value),
+                EventSummary::AbsoluteAxis(_, axis, value) => Summary::Abs(axis, value),
+                EventSummary::RelativeAxis(_, axis, value) => Summary::Rel(axis, value),
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect()
+    }
+}
+
+impl EventSink for MockSink {
+    fn emit(&mut self, events: &[InputEvent]) -> io::Result<()> {
+        self.events.extend_from_slice(events);
+        self.reports += 1;
+        Ok(())
+    }
+}
+
+/// An event recorded by a [`MockSink`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Summary {
+    Key(KeyCode, i32),
+    Abs(AbsoluteAxisCode, i32),
+    Rel(RelativeAxisCode, i32),
 }
 
 #[cfg(test)]
@@ -37,6 +86,7 @@ mod tests {
         keyboard.key_down(evdev::KeyCode::KEY_A).unwrap();
         keyboard.key_repeat(evdev::KeyCode::KEY_A).unwrap();
         keyboard.key_up(evdev::KeyCode::KEY_A).unwrap();
+        keyboard.sync().unwrap();
         assert_eq!(
             keyboard.events,
             vec![
@@ -45,5 +95,6 @@ mod tests {
                 (evdev::KeyCode::KEY_A, KEY_UP),
             ]
         );
+        assert_eq!(keyboard.syncs, 1);
     }
 }
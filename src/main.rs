@@ -1,213 +1,116 @@
 #[macro_use]
 extern crate log;
 
+mod app;
 mod app_log;
 mod cli;
 mod config;
 mod gpio;
 mod input_listener;
 mod keyboard;
+mod signals;
+#[cfg(test)]
+mod test_utils;
+mod watcher;
 
+use std::io::Write;
 use std::path::Path;
-use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
-use std::time::Duration;
 
-use self::config::Config;
-use self::gpio::RaspberryGpio;
-use self::input_listener::{
-    InputListener, InputListenerConfig, KeyConfig, PowerSwitch, RepeatConfig,
-};
-use self::keyboard::EvdevKeyboard;
-use crate::gpio::LinuxGpio;
-
-const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(500);
-const DEFAULT_REPEAT_RATE: Duration = Duration::from_millis(100);
+use self::app::App;
+use self::cli::Command;
+use self::gpio::{LinuxBackend, RaspberryBackend};
 
 fn main() -> anyhow::Result<()> {
     let args: cli::Args = argh::from_env();
     app_log::init_app_log(args.log_level)?;
 
+    match args.command {
+        Some(Command::Check(check_args)) => {
+            return check(check_args.config.as_deref().unwrap_or(&args.config));
+        }
+        Some(Command::ListKeys(list_keys_args)) => {
+            return list_keys(list_keys_args.filter.as_deref());
+        }
+        None => {}
+    }
+
     info!("Starting gpio2key with config file: {:?}", args.config);
     // load configuration
     debug!("Loading configuration...");
     let config = config::Config::load_from_file(&args.config)?;
     debug!("Configuration loaded successfully.");
-    log_config(&config);
-
-    // setup keyboard
-    debug!("Initializing keyboard device...");
-    let keys: Vec<_> = config.keys.iter().map(|k| k.keycode.keycode()).collect();
-    let keyboard = EvdevKeyboard::try_new(&args.keyboard_name, &keys)?;
-    info!("Keyboard device initialized.");
 
     // run application
+    let identity = args.identity();
     if args.raspberry {
         info!("Running on Raspberry Pi board.");
-        run_on_raspberry_pi(config, keyboard)?;
+        App::new(
+            RaspberryBackend,
+            config,
+            args.config,
+            args.keyboard_name,
+            identity,
+            args.watch,
+        )
+        .run()?;
     } else {
         info!("Running on generic Linux system.");
-        run_on_linux_generic(config, keyboard, &args.device)?;
+        App::new(
+            LinuxBackend::new(args.device),
+            config,
+            args.config,
+            args.keyboard_name,
+            identity,
+            args.watch,
+        )
+        .run()?;
     }
 
     Ok(())
 }
 
-fn run_on_raspberry_pi(config: Config, keyboard: EvdevKeyboard) -> anyhow::Result<()> {
-    // setup gpios
-    debug!("Initializing GPIOs...");
-    let keys = config
-        .keys
-        .iter()
-        .map(|k| {
-            RaspberryGpio::try_new(k.gpio, k.active_low.unwrap_or(config.default_active_low)).map(
-                |gpio| KeyConfig {
-                    gpio,
-                    keycode: k.keycode,
-                    debounce: k.debounce().unwrap_or_else(|| config.default_debounce()),
-                    repeat: if k.repeat {
-                        Some(RepeatConfig {
-                            delay: k.repeat_delay().unwrap_or(DEFAULT_REPEAT_DELAY),
-                            rate: k.repeat_rate().unwrap_or(DEFAULT_REPEAT_RATE),
-                        })
-                    } else {
-                        None
-                    },
-                },
-            )
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-    let power_switches = config
-        .power_switches
-        .iter()
-        .map(|ps| {
-            RaspberryGpio::try_new(ps.gpio, ps.active_low.unwrap_or(config.default_active_low))
-                .map(|gpio| PowerSwitch { gpio })
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-    info!("GPIOs initialized.");
-
-    // setup ctrlc handler
-    let exit = Arc::new(AtomicBool::default());
-    {
-        let exit = exit.clone();
-        ctrlc::set_handler(move || {
-            exit.store(true, std::sync::atomic::Ordering::SeqCst);
-        })
-        .expect("Error setting Ctrl-C handler");
+/// Check the configuration file at `path`, printing every problem found
+fn check(path: &Path) -> anyhow::Result<()> {
+    let config_str = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read config file {:?}: {}", path, e))?;
+    let problems = config::check(&config_str);
+    if problems.is_empty() {
+        println!("{}: configuration is valid", path.display());
+        return Ok(());
     }
 
-    // setup input listener
-    let config = InputListenerConfig {
-        exit,
-        keyboard,
-        keys,
-        power_switches,
-        poll_interval: config.poll_interval(),
-    };
-    InputListener::new(config).run();
-
-    Ok(())
+    for problem in &problems {
+        eprintln!("{}:{problem}", path.display());
+    }
+    anyhow::bail!("{} problem(s) found in {:?}", problems.len(), path)
 }
 
-fn run_on_linux_generic(
-    config: Config,
-    keyboard: EvdevKeyboard,
-    device: &Path,
-) -> anyhow::Result<()> {
-    // setup gpios
-    debug!("Initializing GPIOs...");
-    let keys = config
-        .keys
-        .iter()
-        .map(|k| {
-            LinuxGpio::try_new(
-                device,
-                k.gpio,
-                k.active_low.unwrap_or(config.default_active_low),
-            )
-            .map(|gpio| KeyConfig {
-                gpio,
-                keycode: k.keycode,
-                debounce: k.debounce().unwrap_or_else(|| config.default_debounce()),
-                repeat: if k.repeat {
-                    Some(RepeatConfig {
-                        delay: k.repeat_delay().unwrap_or(DEFAULT_REPEAT_DELAY),
-                        rate: k.repeat_rate().unwrap_or(DEFAULT_REPEAT_RATE),
-                    })
-                } else {
-                    None
-                },
-            })
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-    let power_switches = config
-        .power_switches
-        .iter()
-        .map(|ps| {
-            LinuxGpio::try_new(
-                device,
-                ps.gpio,
-                ps.active_low.unwrap_or(config.default_active_low),
-            )
-            .map(|gpio| PowerSwitch { gpio })
-        })
-        .collect::<Result<Vec<_>, _>>()?;
-    info!("GPIOs initialized.");
-
-    // setup ctrlc handler
-    let exit = Arc::new(AtomicBool::default());
-    {
-        let exit = exit.clone();
-        ctrlc::set_handler(move || {
-            exit.store(true, std::sync::atomic::Ordering::SeqCst);
-        })
-        .expect("Error setting Ctrl-C handler");
+/// Print the key names accepted in the configuration, optionally only those matching `filter`
+fn list_keys(filter: Option<&str>) -> anyhow::Result<()> {
+    let filter = filter.map(str::to_ascii_uppercase);
+    match print_keys(&mut std::io::stdout().lock(), filter.as_deref()) {
+        // output piped to a command which exited early, such as `head`
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|e| anyhow::anyhow!("Failed to print keys: {}", e)),
     }
-
-    // setup input listener
-    let config = InputListenerConfig {
-        exit,
-        keyboard,
-        keys,
-        power_switches,
-        poll_interval: config.poll_interval(),
-    };
-    InputListener::new(config).run();
-
-    Ok(())
 }
 
-fn log_config(config: &Config) {
-    info!("Configuration:");
-    info!(
-        "  Default debounce: {}",
-        config.default_debounce().as_millis()
-    );
-    info!("  Default active_low: {}", config.default_active_low);
-    info!("  Poll interval: {}", config.poll_interval().as_millis());
-    info!("  Keys:");
-    for key in &config.keys {
-        info!("    GPIO: {}", key.gpio);
-        info!("    Keycode: {}", key.keycode);
-        if let Some(debounce) = key.debounce() {
-            info!("    Debounce (ms): {}", debounce.as_millis());
-        }
-        info!("    Active Low: {:?}", key.active_low);
-        info!("    Repeat: {}", key.repeat);
-        if let Some(delay) = key.repeat_delay() {
-            info!("    Repeat Delay (ms): {}", delay.as_millis());
+fn print_keys(out: &mut impl Write, filter: Option<&str>) -> std::io::Result<()> {
+    writeln!(out, "{:<28} {:<28} {:<6} CATEGORY", "NAME", "KEY", "CODE")?;
+    for (name, keycode) in config::key_names() {
+        let key = keycode.to_string();
+        let category = keycode.category().to_string();
+        if let Some(filter) = filter {
+            if !name.contains(filter)
+                && !key.contains(filter)
+                && !category.to_ascii_uppercase().contains(filter)
+            {
+                continue;
+            }
         }
-        if let Some(rate) = key.repeat_rate() {
-            info!("    Repeat Rate (ms): {}", rate.as_millis());
-        }
-    }
-    info!("  Power Switches:");
-    for ps in &config.power_switches {
-        info!("    GPIO {}", ps.gpio);
-        info!(
-            "    Active Low: {}",
-            ps.active_low.unwrap_or(config.default_active_low)
-        );
+        let code = format!("{:#05x}", keycode.keycode().code());
+        writeln!(out, "{name:<28} {key:<28} {code:<6} {category}")?;
     }
+
+    Ok(())
 }
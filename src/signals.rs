@@ -0,0 +1,43 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use crate::input_listener::Waker;
+
+/// Install the process signal handlers.
+///
+/// - `SIGINT` and `SIGTERM` set `exit`, so the application shuts down gracefully
+/// - `SIGHUP` sets `reload`, so the configuration is reloaded
+///
+/// The `waker` is woken up on every signal, so the input listener handles it immediately.
+pub fn install(
+    exit: Arc<AtomicBool>,
+    reload: Arc<AtomicBool>,
+    waker: Arc<Waker>,
+) -> anyhow::Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])
+        .map_err(|e| anyhow::anyhow!("Failed to install signal handlers: {}", e))?;
+
+    std::thread::Builder::new()
+        .name("signals".to_string())
+        .spawn(move || {
+            for signal in signals.forever() {
+                match signal {
+                    SIGHUP => {
+                        info!("Received SIGHUP, reloading configuration");
+                        reload.store(true, Ordering::SeqCst);
+                    }
+                    signal => {
+                        info!("Received signal {signal}, shutting down");
+                        exit.store(true, Ordering::SeqCst);
+                    }
+                }
+                waker.wake();
+            }
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to spawn signal handler thread: {}", e))?;
+
+    Ok(())
+}
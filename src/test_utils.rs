@@ -0,0 +1,6 @@
+use std::time::{Duration, Instant};
+
+/// Instant `ms` milliseconds after `start`
+pub fn at(start: Instant, ms: u64) -> Instant {
+    start + Duration::from_millis(ms)
+}
@@ -0,0 +1,197 @@
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::input_listener::Waker;
+
+/// Events on the config directory which may replace the config file content.
+///
+/// The directory is watched instead of the file itself, so saves which write a new file
+/// and rename it over the config file are detected too.
+const WATCH_MASK: u32 = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE;
+
+/// Size of the buffer used to read inotify events
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+/// Watch the configuration file at `path` and request a reload whenever it changes.
+///
+/// A reload is requested by setting `reload` and waking up the input listener with `waker`.
+pub fn watch(path: &Path, reload: Arc<AtomicBool>, waker: Arc<Waker>) -> anyhow::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid config file path {:?}", path))?
+        .to_os_string();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        anyhow::bail!(
+            "Failed to initialize inotify: {}",
+            io::Error::last_os_error()
+        );
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let dir_cstr = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| anyhow::anyhow!("Invalid config directory {:?}: {}", dir, e))?;
+    if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), dir_cstr.as_ptr(), WATCH_MASK) } < 0 {
+        anyhow::bail!(
+            "Failed to watch config directory {:?}: {}",
+            dir,
+            io::Error::last_os_error()
+        );
+    }
+    info!("Watching {:?} for changes", path);
+
+    std::thread::Builder::new()
+        .name("config-watcher".to_string())
+        .spawn(move || {
+            loop {
+                match read_events(&fd) {
+                    Ok(names) if names.iter().any(|name| name == &file_name) => {
+                        info!("Config file changed, reloading configuration");
+                        reload.store(true, Ordering::SeqCst);
+                        waker.wake();
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Failed to read config file changes: {e}; stopped watching");
+                        break;
+                    }
+                }
+            }
+        })
+        .map_err(|e| anyhow::anyhow!("Failed to spawn config watcher thread: {}", e))?;
+
+    Ok(())
+}
+
+/// Block until inotify events are available and return the names of the changed files
+fn read_events(fd: &OwnedFd) -> io::Result<Vec<std::ffi::OsString>> {
+    #[repr(C, align(8))]
+    struct Buffer([u8; EVENT_BUFFER_SIZE]);
+
+    let mut buffer = Buffer([0; EVENT_BUFFER_SIZE]);
+    let len = loop {
+        let res = unsafe {
+            libc::read(
+                fd.as_raw_fd(),
+                buffer.0.as_mut_ptr() as *mut libc::c_void,
+                EVENT_BUFFER_SIZE,
+            )
+        };
+        if res >= 0 {
+            break res as usize;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    };
+
+    Ok(parse_events(&buffer.0[..len]))
+}
+
+/// Parse the names of the files in a buffer of `inotify_event`s
+fn parse_events(buffer: &[u8]) -> Vec<std::ffi::OsString> {
+    const HEADER_SIZE: usize = std::mem::size_of::<libc::inotify_event>();
+
+    let mut names = Vec::new();
+    let mut offset = 0;
+    while offset + HEADER_SIZE <= buffer.len() {
+        let event = unsafe {
+            std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const libc::inotify_event)
+        };
+        let name_start = offset + HEADER_SIZE;
+        let name_end = (name_start + event.len as usize).min(buffer.len());
+        // the name is nul-padded
+        let name = &buffer[name_start..name_end];
+        let name = name.split(|b| *b == 0).next().unwrap_or_default();
+        trace!(
+            "inotify event {:#x} for {:?}",
+            event.mask,
+            OsStr::from_bytes(name)
+        );
+        if !name.is_empty() {
+            names.push(OsStr::from_bytes(name).to_os_string());
+        }
+        offset = name_start + event.len as usize;
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_should_parse_events() {
+        let mut buffer = Vec::new();
+        push_event(&mut buffer, libc::IN_CLOSE_WRITE, "config.toml");
+        push_event(&mut buffer, libc::IN_MOVED_TO, "other.toml.swp");
+
+        assert_eq!(
+            parse_events(&buffer),
+            vec![
+                std::ffi::OsString::from("config.toml"),
+                std::ffi::OsString::from("other.toml.swp"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_detect_rename_over_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "a").unwrap();
+
+        let reload = Arc::new(AtomicBool::default());
+        let waker = Arc::new(Waker::try_new().unwrap());
+        watch(&path, reload.clone(), waker).unwrap();
+
+        // unrelated file
+        std::fs::write(dir.path().join("other.toml"), "b").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!reload.load(Ordering::SeqCst));
+
+        // save by rename
+        let tmp = dir.path().join("config.toml.tmp");
+        std::fs::write(&tmp, "c").unwrap();
+        std::fs::rename(&tmp, &path).unwrap();
+        let mut attempts = 0;
+        while !reload.load(Ordering::SeqCst) && attempts < 100 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            attempts += 1;
+        }
+        assert!(reload.load(Ordering::SeqCst));
+    }
+
+    fn push_event(buffer: &mut Vec<u8>, mask: u32, name: &str) {
+        // name padded to 16 bytes, nul terminated
+        let len = (name.len() / 16 + 1) * 16;
+        let event = libc::inotify_event {
+            wd: 1,
+            mask,
+            cookie: 0,
+            len: len as u32,
+        };
+        let header = unsafe {
+            std::slice::from_raw_parts(
+                &event as *const libc::inotify_event as *const u8,
+                std::mem::size_of::<libc::inotify_event>(),
+            )
+        };
+        buffer.extend_from_slice(header);
+        let mut name = name.as_bytes().to_vec();
+        name.resize(len, 0);
+        buffer.extend_from_slice(&name);
+    }
+}